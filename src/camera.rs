@@ -1,11 +1,105 @@
 use crate::canvas::Canvas;
-use crate::color::black;
+use crate::color::{black, Color};
 use crate::matrix::Matrix;
 use crate::rays::Ray;
 use crate::tuple::point;
 use crate::world::World;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
 use std::time::Instant;
 
+#[cfg(test)]
+mod camera_test {
+    use crate::color::{white, Color};
+
+    use super::{adaptive_color, Sampling};
+
+    #[test]
+    fn adaptive_sampling_traces_at_most_its_worst_case_rays() {
+        for max_depth in 0..4 {
+            // no square is ever flat below a negative threshold, so all of them get split
+            let mut rays = 0;
+            let mut trace = |_| -> Color {
+                rays += 1;
+                white()
+            };
+
+            adaptive_color(&mut trace, -1.0, max_depth);
+
+            assert_eq!(rays, Sampling::adaptive_rays(max_depth));
+        }
+        assert_eq!(Sampling::adaptive_rays(2), 29);
+    }
+
+    #[test]
+    fn adaptive_depth_keeps_to_the_samples() {
+        let depth = |samples| match Sampling::adaptive(0.1, samples) {
+            Sampling::Adaptive { max_depth, .. } => max_depth,
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!(depth(1), 0);
+        assert_eq!(depth(8), 0);
+        assert_eq!(depth(9), 1);
+        assert_eq!(depth(16), 1);
+        assert_eq!(depth(29), 2);
+        assert_eq!(depth(100), 2);
+        assert_eq!(depth(109), 3);
+    }
+}
+
+/*
+ * How the rays for a pixel are picked. Grid and Jittered split the pixel into n by n
+ * cells and shoot one ray per cell, through its center or through a random point in it.
+ * Adaptive starts with the four corners and keeps splitting the parts whose corners
+ * differ by more than `threshold` in any channel, at most `max_depth` times.
+ * */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    Grid(usize),
+    Jittered(usize),
+    Adaptive { threshold: f64, max_depth: u32 },
+}
+
+impl Sampling {
+    // the deepest adaptive sampling that never traces more than `samples` rays for a pixel
+    pub fn adaptive(threshold: f64, samples: usize) -> Sampling {
+        let mut max_depth = 0;
+        while Sampling::adaptive_rays(max_depth + 1) <= samples {
+            max_depth += 1;
+        }
+
+        Sampling::Adaptive {
+            threshold,
+            max_depth,
+        }
+    }
+
+    /*
+     * The rays traced for a pixel when every square is split all the way down: the four
+     * corners, then five more for each split. Points on the edges between squares and
+     * pixels are traced again by each of them.
+     * */
+    pub fn adaptive_rays(max_depth: u32) -> usize {
+        4 + 5 * (4usize.pow(max_depth) - 1) / 3
+    }
+}
+
+/*
+ * How the color seen along a camera ray is found. Whitted is `World::color_at`, with
+ * Phong lighting and perfect reflection and refraction. PathTracing averages `samples`
+ * random paths from `World::path_color` for every camera ray, which brings in light
+ * bouncing between surfaces at the cost of noise.
+ * */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    Whitted,
+    PathTracing { samples: usize },
+}
+
 pub struct Camera {
     pub hsize: i32,
     pub vsize: i32,
@@ -15,6 +109,12 @@ pub struct Camera {
     pub pixel_size: f64,
     pub half_width: f64,
     pub half_height: f64,
+    pub threads: usize,
+    pub max_depth: i32,
+    pub sampling: Sampling,
+    pub integrator: Integrator,
+    pub aperture: f64,
+    pub focal_distance: f64,
 }
 
 impl Camera {
@@ -42,21 +142,45 @@ impl Camera {
             pixel_size: (half_width * 2.0) / (hsize as f64),
             half_width,
             half_height,
+            threads: available_threads(),
+            max_depth: 5,
+            sampling: Sampling::Grid(1),
+            integrator: Integrator::Whitted,
+            aperture: 0.0,
+            focal_distance: 1.0,
         }
     }
 
-    pub fn ray_for_pixel(&self, px: i32, py: i32) -> Ray {
-        let x_offset = ((px as f64) + 0.5) * self.pixel_size;
-        let y_offset = ((py as f64) + 0.5) * self.pixel_size;
+    // offset is where in the pixel the ray passes, (0.5, 0.5) being the center
+    pub fn ray_for_pixel(&self, px: i32, py: i32, offset: (f64, f64)) -> Ray {
+        self.ray_through_lens(px, py, offset, (0.5, 0.5))
+    }
+
+    /*
+     * Thin lens: every ray through the pixel meets at the same point on the focal plane,
+     * `focal_distance` in front of the camera, but starts from a different point on the
+     * lens. Things on the focal plane stay sharp and the rest blurs. `lens` is a point in
+     * the unit square, mapped onto the lens disk, with (0.5, 0.5) its center.
+     * */
+    pub fn ray_through_lens(&self, px: i32, py: i32, offset: (f64, f64), lens: (f64, f64)) -> Ray {
+        let x_offset = ((px as f64) + offset.0) * self.pixel_size;
+        let y_offset = ((py as f64) + offset.1) * self.pixel_size;
 
         let world_x = self.half_width - x_offset;
         let world_y = self.half_height - y_offset;
 
         let inv_transform = self.inverse_transform;
 
-        let pixel = &point(world_x, world_y, -1.0) * &inv_transform;
-        let origin = &point(0.0, 0.0, 0.0) * &inv_transform;
-        let direction = (pixel - origin).normalize();
+        let (lens_x, lens_y) = square_to_disk(lens);
+        let lens_radius = self.aperture / 2.0;
+
+        let focal_point = &point(
+            world_x * self.focal_distance,
+            world_y * self.focal_distance,
+            -self.focal_distance,
+        ) * &inv_transform;
+        let origin = &point(lens_x * lens_radius, lens_y * lens_radius, 0.0) * &inv_transform;
+        let direction = (focal_point - origin).normalize();
 
         Ray::with(origin, direction)
     }
@@ -67,20 +191,58 @@ impl Camera {
         self
     }
 
-    pub fn render(&self, world: World) -> Canvas {
-        let mut image = Canvas::new(self.hsize, self.vsize, black());
+    pub fn with_threads(mut self, threads: usize) -> Camera {
+        assert!(threads > 0, "a camera needs at least one thread to render");
+        self.threads = threads;
+        self
+    }
 
+    pub fn with_max_depth(mut self, max_depth: i32) -> Camera {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Camera {
+        if let Sampling::Grid(n) | Sampling::Jittered(n) = sampling {
+            assert!(n > 0, "a pixel needs at least one sample");
+        }
+        self.sampling = sampling;
+        self
+    }
+
+    pub fn with_integrator(mut self, integrator: Integrator) -> Camera {
+        if let Integrator::PathTracing { samples } = integrator {
+            assert!(samples > 0, "path tracing needs at least one path per ray");
+        }
+        self.integrator = integrator;
+        self
+    }
+
+    // the diameter of the lens, 0.0 is a pinhole camera where everything is sharp
+    pub fn with_aperture(mut self, aperture: f64) -> Camera {
+        assert!(aperture >= 0.0, "the aperture can't be negative");
+        self.aperture = aperture;
+        self
+    }
+
+    pub fn with_focal_distance(mut self, focal_distance: f64) -> Camera {
+        assert!(focal_distance > 0.0, "the focal distance must be positive");
+        self.focal_distance = focal_distance;
+        self
+    }
+
+    pub fn render(&self, world: World) -> Canvas {
         let n_pixels = self.vsize * self.hsize;
 
         let start_time = Instant::now();
 
-        for y in 0..self.vsize {
-            for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(x, y);
-                let color = world.color_at(&ray, 5);
-                image = image.write_pixel(x, y, color);
-            }
-        }
+        let pixels = if self.threads == 1 {
+            self.render_serial(&world)
+        } else {
+            self.render_parallel(&world)
+        };
+
+        let image = Canvas::from_pixels(self.hsize, self.vsize, pixels);
 
         let duration = start_time.elapsed();
 
@@ -93,4 +255,225 @@ impl Camera {
 
         image
     }
+
+    fn render_serial(&self, world: &World) -> Vec<Color> {
+        (0..self.vsize)
+            .flat_map(|y| self.render_row(world, y))
+            .collect()
+    }
+
+    /*
+     * Rows are handed out one at a time from a shared counter, so threads that get
+     * cheap rows (sky, floor) keep picking up work instead of idling. Every pixel is
+     * traced exactly like in the serial path, so the output is identical.
+     * */
+    fn render_parallel(&self, world: &World) -> Vec<Color> {
+        let mut pixels = vec![black(); (self.vsize * self.hsize) as usize];
+        let next_row = AtomicI32::new(0);
+
+        thread::scope(|scope| {
+            let workers = (0..self.threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut rows = vec![];
+                        loop {
+                            let y = next_row.fetch_add(1, Ordering::Relaxed);
+                            if y >= self.vsize {
+                                break;
+                            }
+                            rows.push((y, self.render_row(world, y)));
+                        }
+                        rows
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for worker in workers {
+                for (y, row) in worker.join().expect("render thread panicked") {
+                    let start = (y * self.hsize) as usize;
+                    pixels[start..start + row.len()].copy_from_slice(&row);
+                }
+            }
+        });
+
+        pixels
+    }
+
+    fn render_row(&self, world: &World, y: i32) -> Vec<Color> {
+        (0..self.hsize)
+            .map(|x| self.render_pixel(world, x, y))
+            .collect()
+    }
+
+    fn render_pixel(&self, world: &World, x: i32, y: i32) -> Color {
+        match self.sampling {
+            Sampling::Grid(n) => self.average_of_cells(n, |(left, top), size| {
+                self.sample(world, x, y, (left + size / 2.0, top + size / 2.0))
+            }),
+            Sampling::Jittered(n) => {
+                let mut rng = pixel_rng(x, y);
+                self.average_of_cells(n, |(left, top), size| {
+                    let offset = (
+                        left + rng.gen::<f64>() * size,
+                        top + rng.gen::<f64>() * size,
+                    );
+                    self.sample(world, x, y, offset)
+                })
+            }
+            Sampling::Adaptive {
+                threshold,
+                max_depth,
+            } => adaptive_color(
+                &mut |offset| self.sample(world, x, y, offset),
+                threshold,
+                max_depth,
+            ),
+        }
+    }
+
+    // `sample_cell` gets the top left corner and the size of a cell within the pixel
+    fn average_of_cells(
+        &self,
+        n: usize,
+        mut sample_cell: impl FnMut((f64, f64), f64) -> Color,
+    ) -> Color {
+        let size = 1.0 / n as f64;
+        let mut sum = black();
+
+        for row in 0..n {
+            for col in 0..n {
+                sum = sum + sample_cell((col as f64 * size, row as f64 * size), size);
+            }
+        }
+
+        sum * (1.0 / (n * n) as f64)
+    }
+
+    /*
+     * With an aperture every ray also starts from its own point on the lens, and path
+     * tracing picks its bounces at random. Seeding both from the ray keeps renders
+     * repeatable, like the jitter of the offsets.
+     * */
+    fn sample(&self, world: &World, x: i32, y: i32, offset: (f64, f64)) -> Color {
+        let mut rng = StdRng::seed_from_u64(
+            offset.0.to_bits() ^ offset.1.to_bits().rotate_left(32) ^ pixel_seed(x, y),
+        );
+
+        let ray = if self.aperture > 0.0 {
+            self.ray_through_lens(x, y, offset, (rng.gen(), rng.gen()))
+        } else {
+            self.ray_for_pixel(x, y, offset)
+        };
+
+        match self.integrator {
+            Integrator::Whitted => world.color_at(&ray, self.max_depth),
+            Integrator::PathTracing { samples } => {
+                let sum = (0..samples).fold(black(), |sum, _| {
+                    sum + world.path_color(&ray, self.max_depth, &mut rng)
+                });
+                sum * (1.0 / samples as f64)
+            }
+        }
+    }
+}
+
+// a pixel's color from its four corners, refined where they differ, see `Sampling`
+fn adaptive_color(
+    trace: &mut impl FnMut((f64, f64)) -> Color,
+    threshold: f64,
+    max_depth: u32,
+) -> Color {
+    let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(&mut *trace);
+    refine(trace, (0.0, 0.0), 1.0, corners, threshold, max_depth)
+}
+
+/*
+ * `corners` are the colors at the top left, top right, bottom left and bottom right
+ * of the square at `origin`. When they agree the square is flat and their average is
+ * used, otherwise the square is split into four and the five new points are traced.
+ * */
+fn refine(
+    trace: &mut impl FnMut((f64, f64)) -> Color,
+    origin: (f64, f64),
+    size: f64,
+    corners: [Color; 4],
+    threshold: f64,
+    depth: u32,
+) -> Color {
+    let [top_left, top_right, bottom_left, bottom_right] = corners;
+    let average = (top_left + top_right + bottom_left + bottom_right) * 0.25;
+
+    let is_flat = corners.iter().all(|corner| {
+        (corner.r - average.r).abs() <= threshold
+            && (corner.g - average.g).abs() <= threshold
+            && (corner.b - average.b).abs() <= threshold
+    });
+    if depth == 0 || is_flat {
+        return average;
+    }
+
+    let half = size / 2.0;
+    let (ox, oy) = origin;
+    let mut at = |dx: f64, dy: f64| trace((ox + dx, oy + dy));
+
+    let top = at(half, 0.0);
+    let left = at(0.0, half);
+    let center = at(half, half);
+    let right = at(size, half);
+    let bottom = at(half, size);
+
+    let quadrants = [
+        ((ox, oy), [top_left, top, left, center]),
+        ((ox + half, oy), [top, top_right, center, right]),
+        ((ox, oy + half), [left, center, bottom_left, bottom]),
+        (
+            (ox + half, oy + half),
+            [center, right, bottom, bottom_right],
+        ),
+    ];
+
+    quadrants
+        .into_iter()
+        .fold(black(), |sum, (origin, corners)| {
+            sum + refine(trace, origin, half, corners, threshold, depth - 1)
+        })
+        * 0.25
+}
+
+/*
+ * Seeded from the pixel, so every pixel gets its own jitter but a render comes out the
+ * same every time, no matter how many threads there are.
+ * */
+fn pixel_rng(x: i32, y: i32) -> StdRng {
+    StdRng::seed_from_u64(pixel_seed(x, y))
+}
+
+fn pixel_seed(x: i32, y: i32) -> u64 {
+    (x as u64) << 32 | y as u32 as u64
+}
+
+/*
+ * Concentric mapping from the unit square onto the unit disk. Unlike picking an angle
+ * and a radius it keeps evenly spread samples evenly spread, so the blur stays smooth.
+ * */
+fn square_to_disk((u, v): (f64, f64)) -> (f64, f64) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let (radius, angle) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+    };
+
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+fn available_threads() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
 }
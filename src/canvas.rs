@@ -1,8 +1,42 @@
+mod png;
+mod ppm;
+
+pub use self::ppm::PpmError;
+
 use crate::color::Color;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    // plain text P3, what `.ppm` files have always been saved as
+    Ppm,
+    // binary P6, much smaller and faster to read
+    PpmBinary,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "ppm-binary" => Some(ImageFormat::PpmBinary),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    // picks the format from the extension of the path, like `render.ppm`
+    pub fn from_path(path: &str) -> Option<ImageFormat> {
+        Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(ImageFormat::from_name)
+    }
+}
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Canvas {
     pub width: i32,
     pub height: i32,
@@ -18,6 +52,15 @@ impl Canvas {
         }
     }
 
+    pub fn from_pixels(width: i32, height: i32, pixels: Vec<Color>) -> Canvas {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        Canvas {
+            width,
+            height,
+            pixels,
+        }
+    }
+
     pub fn write_pixel(mut self, x: i32, y: i32, c: Color) -> Self {
         let idx = self.i(x, y);
         self.pixels[idx] = c;
@@ -65,11 +108,62 @@ impl Canvas {
         format!("{}\n{}", header, body)
     }
 
+    pub fn to_ppm_binary(&self) -> Vec<u8> {
+        ppm::write_p6(self)
+    }
+
+    // reads both P3 and P6 files
+    pub fn from_ppm(bytes: &[u8]) -> Result<Canvas, PpmError> {
+        ppm::read(bytes)
+    }
+
+    pub fn load_ppm(path: &str) -> Result<Canvas, PpmError> {
+        let bytes = fs::read(path).map_err(|e| PpmError(format!("{}: {}", path, e)))?;
+        Canvas::from_ppm(&bytes)
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        let rgb = self
+            .pixels
+            .iter()
+            .flat_map(|color| color.de_normalized())
+            .map(|channel| channel as u8)
+            .collect::<Vec<u8>>();
+
+        png::encode(self.width as u32, self.height as u32, &rgb)
+    }
+
+    // the format is picked from the extension, `.png` or `.ppm` for a plain text PPM
     pub fn save_to_file(&self, path: &str) -> std::io::Result<()> {
-        let filename = path;
-        let content = self.to_ppm();
-        let mut file = File::create(filename)?;
-        file.write_all(content.as_bytes())
+        let format = ImageFormat::from_path(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("can't tell the image format of {}", path),
+            )
+        })?;
+
+        self.save_as(path, format)
+    }
+
+    pub fn save_png(&self, path: &str) -> std::io::Result<()> {
+        self.save_as(path, ImageFormat::Png)
+    }
+
+    pub fn save_as(&self, path: &str, format: ImageFormat) -> std::io::Result<()> {
+        if format == ImageFormat::Png && self.length() == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "a PNG needs at least one pixel",
+            ));
+        }
+
+        let content = match format {
+            ImageFormat::Ppm => self.to_ppm().into_bytes(),
+            ImageFormat::PpmBinary => self.to_ppm_binary(),
+            ImageFormat::Png => self.to_png(),
+        };
+        let mut file = File::create(path)?;
+        file.write_all(&content)
     }
 
     pub fn length(&self) -> usize {
@@ -83,8 +177,9 @@ impl Canvas {
 
 #[cfg(test)]
 mod canvas_test {
-    use crate::canvas::Canvas;
+    use crate::canvas::{Canvas, ImageFormat};
     use crate::color::{black, color};
+    use std::fs;
 
     #[test]
     fn create() {
@@ -166,5 +261,71 @@ mod canvas_test {
             '\n'
         );
     }
-}
 
+    #[test]
+    fn image_format_from_extension() {
+        assert_eq!(ImageFormat::from_path("render.png"), Some(ImageFormat::Png));
+        assert_eq!(
+            ImageFormat::from_path("out/Render.PPM"),
+            Some(ImageFormat::Ppm)
+        );
+        assert_eq!(ImageFormat::from_path("render.gif"), None);
+        assert_eq!(ImageFormat::from_path("render"), None);
+    }
+
+    #[test]
+    fn png_header() {
+        let png = Canvas::new(5, 3, black()).to_png();
+
+        assert_eq!(&png[..8], &[137, 80, 78, 71, 13, 10, 26, 10]);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 5, 0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn saving_picks_format_from_extension() {
+        let canvas = Canvas::new(4, 2, color(1.0, 0.6, 0.0));
+
+        canvas.save_to_file("tests/output/saving.png").unwrap();
+        canvas.save_to_file("tests/output/saving.ppm").unwrap();
+
+        assert_eq!(
+            fs::read("tests/output/saving.png").unwrap(),
+            canvas.to_png()
+        );
+        assert_eq!(
+            fs::read_to_string("tests/output/saving.ppm").unwrap(),
+            canvas.to_ppm()
+        );
+        assert_eq!(
+            Canvas::load_ppm("tests/output/saving.ppm").unwrap().pixels,
+            canvas.pixels
+        );
+        assert!(canvas.save_to_file("tests/output/saving.gif").is_err());
+        assert!(Canvas::new(0, 3, black())
+            .save_to_file("tests/output/empty.png")
+            .is_err());
+    }
+
+    #[test]
+    fn binary_ppm_is_opt_in() {
+        let canvas = Canvas::new(4, 2, color(1.0, 0.6, 0.0));
+
+        canvas
+            .save_as("tests/output/binary.ppm", ImageFormat::PpmBinary)
+            .unwrap();
+
+        assert_eq!(
+            ImageFormat::from_name("ppm-binary"),
+            Some(ImageFormat::PpmBinary)
+        );
+        assert_eq!(
+            fs::read("tests/output/binary.ppm").unwrap(),
+            canvas.to_ppm_binary()
+        );
+        assert_eq!(
+            Canvas::load_ppm("tests/output/binary.ppm").unwrap().pixels,
+            canvas.pixels
+        );
+    }
+}
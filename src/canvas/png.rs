@@ -0,0 +1,420 @@
+/*
+ * A small PNG encoder for 8 bit RGB images. Every row is filtered with whichever of the
+ * five PNG filters gives the smallest bytes, and the result is compressed with LZ77 and
+ * the fixed Huffman codes of deflate. Dynamic Huffman tables would save a little more,
+ * but the filters already turn the smooth gradients of a render into runs of zeros.
+ * */
+
+#[cfg(test)]
+mod png_test {
+    use super::{adler32, crc32, encode, filter_rows, paeth, SIGNATURE};
+
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut chunks = vec![];
+        let mut i = SIGNATURE.len();
+        while i < png.len() {
+            let length = u32::from_be_bytes(png[i..i + 4].try_into().unwrap()) as usize;
+            let kind = String::from_utf8(png[i + 4..i + 8].to_vec()).unwrap();
+            let data = png[i + 8..i + 8 + length].to_vec();
+            let crc = u32::from_be_bytes(png[i + 8 + length..i + 12 + length].try_into().unwrap());
+            assert_eq!(
+                crc,
+                crc32(&png[i + 4..i + 8 + length]),
+                "bad crc in {}",
+                kind
+            );
+            chunks.push((kind, data));
+            i += 12 + length;
+        }
+        chunks
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+        assert_eq!(adler32(b""), 1);
+    }
+
+    #[test]
+    fn paeth_predicts_from_the_closest_neighbour() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 30), 10);
+        assert_eq!(paeth(0, 0, 0), 0);
+    }
+
+    #[test]
+    fn filtering_picks_a_filter_per_row() {
+        // the second row is the same as the first, so "up" leaves only zeros
+        let rgb = [10, 20, 30, 40, 50, 60, 10, 20, 30, 40, 50, 60];
+        let filtered = filter_rows(6, &rgb);
+
+        assert_eq!(filtered.len(), 14);
+        assert_eq!(filtered[7], 2);
+        assert_eq!(&filtered[8..], &[0; 6]);
+    }
+
+    #[test]
+    fn encoding_an_image() {
+        let png = encode(3, 2, &[255; 18]);
+
+        assert_eq!(&png[..8], &SIGNATURE);
+
+        let chunks = chunks(&png);
+        let kinds = chunks
+            .iter()
+            .map(|(kind, _)| kind.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(kinds, ["IHDR", "IDAT", "IEND"]);
+
+        assert_eq!(chunks[0].1, [0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+
+        let zlib = &chunks[1].1;
+        assert_eq!((u16::from(zlib[0]) << 8 | u16::from(zlib[1])) % 31, 0);
+        let filtered = filter_rows(9, &[255; 18]);
+        assert_eq!(&zlib[zlib.len() - 4..], &adler32(&filtered).to_be_bytes());
+
+        assert!(chunks[2].1.is_empty());
+    }
+
+    #[test]
+    fn images_without_pixels_have_no_rows() {
+        assert!(filter_rows(0, &[]).is_empty());
+        assert_eq!(chunks(&encode(0, 4, &[])).len(), 3);
+    }
+
+    #[test]
+    fn flat_images_compress_well() {
+        let png = encode(200, 100, &[128; 200 * 100 * 3]);
+
+        assert!(png.len() < 1000, "{} bytes", png.len());
+    }
+}
+
+pub const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// `rgb` has three bytes per pixel, row by row from the top left
+pub fn encode(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    assert_eq!(rgb.len(), width as usize * height as usize * 3);
+
+    let mut header = vec![];
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // bit depth 8, truecolor, deflate, adaptive filtering, no interlacing
+    header.extend([8, 2, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(
+        &mut png,
+        b"IDAT",
+        &zlib(&filter_rows(width as usize * 3, rgb)),
+    );
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/*
+ * Each filter predicts a byte from its neighbours to the left (a), above (b) and above
+ * left (c) and stores the difference. The row is prefixed with the filter type. The
+ * filter with the smallest sum of differences usually compresses best.
+ * */
+fn filter_rows(stride: usize, rgb: &[u8]) -> Vec<u8> {
+    const BYTES_PER_PIXEL: usize = 3;
+
+    // an image without pixels has no rows
+    if stride == 0 {
+        return vec![];
+    }
+
+    let mut filtered = Vec::with_capacity(rgb.len() + rgb.len() / stride.max(1));
+    let no_row = vec![0; stride];
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+
+    for (y, row) in rgb.chunks(stride).enumerate() {
+        let above = if y == 0 {
+            &no_row[..]
+        } else {
+            &rgb[(y - 1) * stride..y * stride]
+        };
+
+        let mut best_filter = 0;
+        let mut best_score = u64::MAX;
+
+        for filter in 0..5 {
+            for i in 0..stride {
+                let a = if i >= BYTES_PER_PIXEL {
+                    row[i - BYTES_PER_PIXEL]
+                } else {
+                    0
+                };
+                let b = above[i];
+                let c = if i >= BYTES_PER_PIXEL {
+                    above[i - BYTES_PER_PIXEL]
+                } else {
+                    0
+                };
+
+                let predicted = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                candidate[i] = row[i].wrapping_sub(predicted);
+            }
+
+            let score = candidate
+                .iter()
+                .map(|byte| u64::from((*byte as i8).unsigned_abs()))
+                .sum();
+            if score < best_score {
+                best_score = score;
+                best_filter = filter;
+                best.copy_from_slice(&candidate);
+            }
+        }
+
+        filtered.push(best_filter);
+        filtered.extend(&best);
+    }
+
+    filtered
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, no dictionary, header check bits
+    let mut stream = vec![0x78, 0x01];
+    stream.extend(deflate(data));
+    stream.extend(adler32(data).to_be_bytes());
+    stream
+}
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: usize = 15;
+const END_OF_BLOCK: u32 = 256;
+
+const LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/*
+ * One final block with the fixed Huffman codes. Earlier positions with the same first
+ * three bytes are kept in hash chains, and the longest match among the most recent
+ * MAX_CHAIN of them is used.
+ * */
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // last block, fixed Huffman codes
+    bits.write_bits(1, 1);
+    bits.write_bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; data.len()];
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut candidate = insert(data, i, &mut head, &mut previous);
+        let max_length = (data.len() - i).min(MAX_MATCH);
+        let (mut best_length, mut best_distance) = (0, 0);
+        let mut chain = 0;
+
+        while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[i..i + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best_length {
+                best_length = length;
+                best_distance = i - candidate;
+                if length == max_length {
+                    break;
+                }
+            }
+            candidate = previous[candidate];
+            chain += 1;
+        }
+
+        if best_length >= MIN_MATCH {
+            bits.write_length(best_length);
+            bits.write_distance(best_distance);
+            for j in i + 1..i + best_length {
+                insert(data, j, &mut head, &mut previous);
+            }
+            i += best_length;
+        } else {
+            bits.write_literal(u32::from(data[i]));
+            i += 1;
+        }
+    }
+
+    bits.write_literal(END_OF_BLOCK);
+    bits.finish()
+}
+
+// adds position i to its hash chain and returns the previous position in that chain
+fn insert(data: &[u8], i: usize, head: &mut [usize], previous: &mut [usize]) -> usize {
+    if i + MIN_MATCH > data.len() {
+        return usize::MAX;
+    }
+
+    let hash =
+        ((usize::from(data[i]) << 10) ^ (usize::from(data[i + 1]) << 5) ^ usize::from(data[i + 2]))
+            & ((1 << HASH_BITS) - 1);
+    let candidate = head[hash];
+    previous[i] = candidate;
+    head[hash] = i;
+    candidate
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    n_bits: u32,
+}
+
+impl BitWriter {
+    // deflate packs values starting from the least significant bit
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.n_bits;
+        self.n_bits += count;
+        while self.n_bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.n_bits -= 8;
+        }
+    }
+
+    // except Huffman codes, which start from the most significant bit
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+
+    fn write_literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_length(&mut self, length: usize) {
+        let code = LENGTH_BASE
+            .iter()
+            .rposition(|base| *base <= length)
+            .unwrap();
+        self.write_literal(257 + code as u32);
+        self.write_bits((length - LENGTH_BASE[code]) as u32, LENGTH_EXTRA_BITS[code]);
+    }
+
+    fn write_distance(&mut self, distance: usize) {
+        let code = DISTANCE_BASE
+            .iter()
+            .rposition(|base| *base <= distance)
+            .unwrap();
+        self.write_code(code as u32, 5);
+        self.write_bits(
+            (distance - DISTANCE_BASE[code]) as u32,
+            DISTANCE_EXTRA_BITS[code],
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n_bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xEDB88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, byte| {
+        CRC_TABLE[((crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1, 0);
+
+    // 5552 is the most bytes that can be summed before b could overflow
+    for chunk in bytes.chunks(5552) {
+        for byte in chunk {
+            a += u32::from(*byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+
+    (b << 16) | a
+}
@@ -0,0 +1,314 @@
+use std::fmt;
+
+use crate::color::{color, Color};
+
+use super::Canvas;
+
+#[cfg(test)]
+mod ppm_test {
+    use crate::canvas::Canvas;
+    use crate::color::color;
+
+    use super::{read, write_p6, PpmError};
+
+    fn error_for(ppm: &[u8]) -> String {
+        read(ppm).err().unwrap().0
+    }
+
+    #[test]
+    fn reading_a_file_with_the_wrong_magic_number() {
+        let ppm = "P32\n1 1\n255\n0 0 0\n";
+
+        assert_eq!(
+            read(ppm.as_bytes()).err(),
+            Some(PpmError("not a PPM file".to_owned()))
+        );
+    }
+
+    #[test]
+    fn reading_a_p3_file() {
+        let ppm = "P3
+10 2
+255
+0 0 0  1 1 1  2 2 2  3 3 3  4 4 4  5 5 5  6 6 6  7 7 7  8 8 8  9 9 9
+10 10 10  11 11 11  12 12 12  13 13 13  14 14 14
+15 15 15  16 16 16  17 17 17  18 18 18  19 19 19
+";
+        let canvas = read(ppm.as_bytes()).unwrap();
+
+        assert_eq!(canvas.width, 10);
+        assert_eq!(canvas.height, 2);
+        assert_eq!(canvas.pixel_at(0, 0), color(0.0, 0.0, 0.0));
+        assert_eq!(
+            canvas.pixel_at(9, 0),
+            color(9.0 / 255.0, 9.0 / 255.0, 9.0 / 255.0)
+        );
+        assert_eq!(
+            canvas.pixel_at(9, 1),
+            color(19.0 / 255.0, 19.0 / 255.0, 19.0 / 255.0)
+        );
+    }
+
+    #[test]
+    fn comments_are_ignored() {
+        let ppm = "P3
+# this is a comment
+2 1 # so is this
+# and this
+255
+# and one in the body
+255 127 0  0 127 255
+";
+        let canvas = read(ppm.as_bytes()).unwrap();
+
+        assert_eq!(canvas.pixel_at(0, 0), color(1.0, 127.0 / 255.0, 0.0));
+        assert_eq!(canvas.pixel_at(1, 0), color(0.0, 127.0 / 255.0, 1.0));
+    }
+
+    #[test]
+    fn colors_may_span_lines() {
+        let ppm = "P3\n1 1\n255\n51\n153\n\n204\n";
+        let canvas = read(ppm.as_bytes()).unwrap();
+
+        assert_eq!(canvas.pixel_at(0, 0), color(0.2, 0.6, 0.8));
+    }
+
+    #[test]
+    fn colors_are_scaled_by_maxval() {
+        let ppm = "P3\n2 2\n100\n100 100 100  50 50 50\n75 50 25  0 0 0\n";
+        let canvas = read(ppm.as_bytes()).unwrap();
+
+        assert_eq!(canvas.pixel_at(0, 1), color(0.75, 0.5, 0.25));
+    }
+
+    #[test]
+    fn reading_a_p6_file() {
+        let mut ppm = b"P6\n# binary\n2 1\n255\n".to_vec();
+        ppm.extend([255, 0, 51, 0, 102, 255]);
+        let canvas = read(&ppm).unwrap();
+
+        assert_eq!(canvas.pixel_at(0, 0), color(1.0, 0.0, 0.2));
+        assert_eq!(canvas.pixel_at(1, 0), color(0.0, 0.4, 1.0));
+    }
+
+    #[test]
+    fn reading_a_p6_file_with_two_bytes_per_sample() {
+        let mut ppm = b"P6 1 1 1000\n".to_vec();
+        ppm.extend([0x03, 0xE8, 0x01, 0xF4, 0x00, 0x00]);
+        let canvas = read(&ppm).unwrap();
+
+        assert_eq!(canvas.pixel_at(0, 0), color(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn writing_p6() {
+        let canvas = Canvas::new(2, 1, color(1.0, 0.5, 0.0));
+        let ppm = write_p6(&canvas);
+
+        assert_eq!(&ppm[..11], b"P6\n2 1\n255\n");
+        assert_eq!(&ppm[11..], &[255, 128, 0, 255, 128, 0]);
+    }
+
+    #[test]
+    fn p3_and_p6_round_trip() {
+        let canvas =
+            Canvas::new(3, 2, color(0.2, 0.4, 0.6)).write_pixel(1, 1, color(1.0, 0.0, 1.0));
+
+        for ppm in [canvas.to_ppm().into_bytes(), write_p6(&canvas)] {
+            let read_back = read(&ppm).unwrap();
+            assert_eq!(read_back.width, 3);
+            assert_eq!(read_back.height, 2);
+            assert_eq!(read_back.pixels, canvas.pixels);
+        }
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert_eq!(
+            error_for(b"P3\n2 1\n255\n0 0 0\n"),
+            "expected 6 samples, found 3"
+        );
+        assert_eq!(
+            error_for(b"P3\n1 1\n255\n0 0 256\n"),
+            "sample 256 is larger than maxval 255"
+        );
+        assert_eq!(error_for(b"P3\n1 x\n255\n"), "expected a number, found `x`");
+        assert_eq!(
+            error_for(b"P6\n2 1\n255\n\x00\x00\x00"),
+            "expected 6 bytes of pixels, found 3"
+        );
+        assert_eq!(
+            error_for(b"P3\n1 1\n0\n"),
+            "maxval must be between 1 and 65535, found 0"
+        );
+        assert_eq!(
+            error_for(b"P3\n46340 46340\n255\n0 0 0\n"),
+            "expected 6442186800 samples, found 3"
+        );
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PpmError(pub String);
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for PpmError {}
+
+pub fn write_p6(canvas: &Canvas) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", canvas.width, canvas.height).into_bytes();
+    ppm.extend(
+        canvas
+            .pixels
+            .iter()
+            .flat_map(|color| color.de_normalized())
+            .map(|channel| channel as u8),
+    );
+    ppm
+}
+
+/*
+ * Reads plain (P3) and binary (P6) PPM files. The header is the magic number, width,
+ * height and maxval separated by whitespace, with `#` comments running to the end of
+ * the line. Samples go from 0 to maxval and are scaled to 0.0 to 1.0.
+ * */
+pub fn read(bytes: &[u8]) -> Result<Canvas, PpmError> {
+    let mut tokens = Tokens { bytes, pos: 0 };
+
+    let binary = match tokens.next() {
+        Some(b"P3") => false,
+        Some(b"P6") => true,
+        _ => return Err(error("not a PPM file")),
+    };
+
+    let width = tokens.number()?;
+    let height = tokens.number()?;
+    let maxval = tokens.number()?;
+
+    if !(1..=65535).contains(&maxval) {
+        return Err(error(&format!(
+            "maxval must be between 1 and 65535, found {}",
+            maxval
+        )));
+    }
+    if width == 0 || height == 0 || width > i32::MAX as usize / height {
+        return Err(error(&format!("invalid size {}x{}", width, height)));
+    }
+
+    let n_samples = width * height * 3;
+    let samples = if binary {
+        // exactly one whitespace byte separates the header from the pixels
+        let start = tokens.pos + 1;
+        let bytes_per_sample = if maxval < 256 { 1 } else { 2 };
+        let data = bytes.get(start..).unwrap_or_default();
+
+        if data.len() < n_samples * bytes_per_sample {
+            return Err(error(&format!(
+                "expected {} bytes of pixels, found {}",
+                n_samples * bytes_per_sample,
+                data.len()
+            )));
+        }
+
+        data.chunks(bytes_per_sample)
+            .take(n_samples)
+            .map(|sample| sample.iter().fold(0, |n, byte| n << 8 | usize::from(*byte)))
+            .collect::<Vec<usize>>()
+    } else {
+        // grown as samples are read, the header alone can ask for more than there is memory
+        let mut samples = vec![];
+        while samples.len() < n_samples {
+            match tokens.peek() {
+                Some(_) => samples.push(tokens.number()?),
+                None => break,
+            }
+        }
+        if samples.len() != n_samples {
+            return Err(error(&format!(
+                "expected {} samples, found {}",
+                n_samples,
+                samples.len()
+            )));
+        }
+        samples
+    };
+
+    if let Some(sample) = samples.iter().find(|sample| **sample > maxval) {
+        return Err(error(&format!(
+            "sample {} is larger than maxval {}",
+            sample, maxval
+        )));
+    }
+
+    let scale = maxval as f64;
+    let pixels = samples
+        .chunks(3)
+        .map(|rgb| {
+            color(
+                rgb[0] as f64 / scale,
+                rgb[1] as f64 / scale,
+                rgb[2] as f64 / scale,
+            )
+        })
+        .collect::<Vec<Color>>();
+
+    Ok(Canvas::from_pixels(width as i32, height as i32, pixels))
+}
+
+fn error(message: &str) -> PpmError {
+    PpmError(message.to_owned())
+}
+
+struct Tokens<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(byte) = self.bytes.get(self.pos) {
+            if *byte == b'#' {
+                while self.bytes.get(self.pos).is_some_and(|b| *b != b'\n') {
+                    self.pos += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<&'a [u8]> {
+        self.skip_whitespace_and_comments();
+        let start = self.pos;
+        let end = self.bytes[start..]
+            .iter()
+            .position(|b| b.is_ascii_whitespace() || *b == b'#')
+            .map_or(self.bytes.len(), |length| start + length);
+
+        if start == end {
+            None
+        } else {
+            Some(&self.bytes[start..end])
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let token = self.peek()?;
+        self.pos += token.len();
+        Some(token)
+    }
+
+    fn number(&mut self) -> Result<usize, PpmError> {
+        let token = self.next().ok_or_else(|| error("unexpected end of file"))?;
+        let text = String::from_utf8_lossy(token);
+
+        text.parse::<usize>()
+            .map_err(|_| error(&format!("expected a number, found `{}`", text)))
+    }
+}
@@ -0,0 +1,553 @@
+use std::f64::consts::FRAC_PI_3;
+use std::fmt;
+use std::path::Path;
+
+use crate::{
+    camera::{Camera, Integrator, Sampling},
+    canvas::ImageFormat,
+    color::white,
+    lights::PointLight,
+    obj_file::ObjExporter,
+    scene_file::load_scene_file,
+    scenes::{scene_by_name, SCENE_NAMES},
+    transformation::view_transformation,
+    tuple::{point, vector},
+    world::World,
+};
+
+pub const USAGE: &str = "Render a scene with the ray tracer.
+
+Usage: ray-tracer-challenge [OPTIONS]
+
+Options:
+  -s, --scene <NAME>        Render one of the built in scenes [default: csg]
+  -f, --scene-file <PATH>   Render a YAML scene file instead
+  -o, --output <PATH>       Where to write the image [default: render.png]
+      --format <FORMAT>     Image format, png, ppm or ppm-binary [default: from the output extension]
+  -W, --width <PIXELS>      Width of the image [default: 1920, or from the scene file]
+  -H, --height <PIXELS>     Height of the image [default: 1080, or from the scene file]
+      --fov <RADIANS>       Field of view [default: pi/3, or from the scene file]
+      --aperture <SIZE>     Size of the lens, blurs what is not in focus [default: 0]
+      --focal-distance <D>  How far away things are in focus [default: distance to
+                            the point the camera looks at]
+  -d, --depth <N>           How many times rays may bounce or refract [default: 5]
+      --samples <N>         Rays per pixel, a square number like 4 or 9 [default: 1]
+                            With adaptive sampling, the most rays per pixel
+      --sampling <MODE>     Where in the pixel rays go: grid, jittered or adaptive [default: grid]
+      --threshold <T>       How much adaptive samples may differ before the pixel
+                            is refined [default: 0.05]
+      --integrator <MODE>   How light is traced: whitted or path [default: whitted]
+      --paths <N>           With path tracing, the paths traced for every sample [default: 16]
+  -j, --threads <N>         Number of render threads [default: all cores]
+      --export-obj <PATH>   Write the shapes of the scene to an OBJ file instead of rendering
+      --list-scenes         Print the names of the built in scenes
+  -h, --help                Print this help";
+
+#[cfg(test)]
+mod cli_test {
+    use crate::{
+        camera::{Integrator, Sampling},
+        canvas::ImageFormat,
+    };
+
+    use super::{parse_args, Command, Options, SceneSource};
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string())).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn no_arguments_renders_the_default_scene() {
+        assert_eq!(parse(&[]), Ok(Command::Render(Options::default())));
+        assert_eq!(
+            Options::default().scene,
+            SceneSource::Named("csg".to_owned())
+        );
+    }
+
+    #[test]
+    fn parsing_all_options() {
+        let command = parse(&[
+            "--scene-file",
+            "scenes/room.yml",
+            "-o",
+            "out/room.ppm",
+            "--format",
+            "ppm",
+            "-W",
+            "320",
+            "--height=180",
+            "--fov",
+            "1.2",
+            "--aperture",
+            "0.1",
+            "--focal-distance",
+            "8.5",
+            "-d",
+            "3",
+            "--samples",
+            "9",
+            "-j",
+            "2",
+            "--export-obj",
+            "out/room.obj",
+        ]);
+
+        assert_eq!(
+            command,
+            Ok(Command::Render(Options {
+                scene: SceneSource::File("scenes/room.yml".to_owned()),
+                output: "out/room.ppm".to_owned(),
+                format: Some(ImageFormat::Ppm),
+                width: Some(320),
+                height: Some(180),
+                field_of_view: Some(1.2),
+                aperture: Some(0.1),
+                focal_distance: Some(8.5),
+                max_depth: 3,
+                sampling: Sampling::Grid(3),
+                integrator: Integrator::Whitted,
+                threads: Some(2),
+                export_obj: Some("out/room.obj".to_owned()),
+            }))
+        );
+    }
+
+    #[test]
+    fn parsing_sampling_modes() {
+        let sampling = |args: &[&str]| match parse(args) {
+            Ok(Command::Render(options)) => options.sampling,
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!(sampling(&["--samples", "16"]), Sampling::Grid(4));
+        assert_eq!(
+            sampling(&["--sampling", "jittered", "--samples", "4"]),
+            Sampling::Jittered(2)
+        );
+        assert_eq!(
+            sampling(&["--samples=36", "--sampling=adaptive", "--threshold=0.1"]),
+            Sampling::Adaptive {
+                threshold: 0.1,
+                max_depth: 2
+            }
+        );
+        assert_eq!(
+            sampling(&["--sampling", "adaptive", "--samples", "9"]),
+            Sampling::Adaptive {
+                threshold: 0.05,
+                max_depth: 1
+            }
+        );
+        assert_eq!(
+            sampling(&["--sampling", "adaptive"]),
+            Sampling::Adaptive {
+                threshold: 0.05,
+                max_depth: 0
+            }
+        );
+        assert_eq!(
+            parse(&["--sampling", "random"]),
+            Err("unknown sampling `random`, use grid, jittered or adaptive".to_owned())
+        );
+    }
+
+    #[test]
+    fn parsing_integrators() {
+        let integrator = |args: &[&str]| match parse(args) {
+            Ok(Command::Render(options)) => options.integrator,
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!(integrator(&[]), Integrator::Whitted);
+        assert_eq!(
+            integrator(&["--integrator", "path"]),
+            Integrator::PathTracing { samples: 16 }
+        );
+        assert_eq!(
+            integrator(&["--paths=64", "--integrator=path"]),
+            Integrator::PathTracing { samples: 64 }
+        );
+        assert_eq!(
+            parse(&["--integrator", "photons"]),
+            Err("unknown integrator `photons`, use whitted or path".to_owned())
+        );
+    }
+
+    #[test]
+    fn help_and_list_scenes() {
+        assert_eq!(parse(&["--scene", "csg", "-h"]), Ok(Command::Help));
+        assert_eq!(parse(&["--list-scenes"]), Ok(Command::ListScenes));
+    }
+
+    #[test]
+    fn invalid_arguments_are_usage_errors() {
+        assert_eq!(
+            parse(&["--bogus"]),
+            Err("unknown option `--bogus`".to_owned())
+        );
+        assert_eq!(
+            parse(&["--width"]),
+            Err("`--width` needs a value".to_owned())
+        );
+        assert_eq!(
+            parse(&["--width", "wide"]),
+            Err("`--width` expects a positive whole number, found `wide`".to_owned())
+        );
+        assert_eq!(
+            parse(&["--threads", "0"]),
+            Err("`--threads` expects a positive whole number, found `0`".to_owned())
+        );
+        assert_eq!(
+            parse(&["--samples", "5"]),
+            Err("`--samples` must be a square number like 1, 4, 9 or 16, found `5`".to_owned())
+        );
+        assert_eq!(
+            parse(&["--scene", "moon"]),
+            Err("unknown scene `moon`, see --list-scenes".to_owned())
+        );
+        assert_eq!(
+            parse(&["--format", "gif"]),
+            Err("unknown image format `gif`".to_owned())
+        );
+        assert_eq!(
+            parse(&["-o", "render.gif"]),
+            Err("can't tell the image format of `render.gif`, use --format".to_owned())
+        );
+        assert_eq!(
+            parse(&["-s", "csg", "-f", "room.yml"]),
+            Err("use either --scene or --scene-file, not both".to_owned())
+        );
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    // the arguments were wrong, the help text is shown
+    Usage(String),
+    // the arguments were fine but rendering or writing the image failed
+    Failed(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Failed(_) => 1,
+            CliError::Usage(_) => 2,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) | CliError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SceneSource {
+    Named(String),
+    File(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub scene: SceneSource,
+    pub output: String,
+    pub format: Option<ImageFormat>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub field_of_view: Option<f64>,
+    pub aperture: Option<f64>,
+    pub focal_distance: Option<f64>,
+    pub max_depth: i32,
+    pub sampling: Sampling,
+    pub integrator: Integrator,
+    pub threads: Option<usize>,
+    pub export_obj: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scene: SceneSource::Named("csg".to_owned()),
+            output: "render.png".to_owned(),
+            format: None,
+            width: None,
+            height: None,
+            field_of_view: None,
+            aperture: None,
+            focal_distance: None,
+            max_depth: 5,
+            sampling: Sampling::Grid(1),
+            integrator: Integrator::Whitted,
+            threads: None,
+            export_obj: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Render(Options),
+    Help,
+    ListScenes,
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command, CliError> {
+    let mut options = Options::default();
+    let mut scene_given = false;
+    let mut scene_file_given = false;
+    let mut samples = 1;
+    let mut sampling = "grid".to_owned();
+    let mut threshold = 0.05;
+    let mut integrator = "whitted".to_owned();
+    let mut paths = 16;
+
+    while let Some(arg) = args.next() {
+        // --name=value is the same as --name value
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (name.to_owned(), Some(value.to_owned()))
+            }
+            _ => (arg.clone(), None),
+        };
+
+        let mut value = || -> Result<String, CliError> {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| usage(&format!("`{}` needs a value", name)))
+        };
+
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--list-scenes" => return Ok(Command::ListScenes),
+            "-s" | "--scene" => {
+                let scene = value()?;
+                if !SCENE_NAMES.contains(&scene.as_str()) {
+                    return Err(usage(&format!(
+                        "unknown scene `{}`, see --list-scenes",
+                        scene
+                    )));
+                }
+                options.scene = SceneSource::Named(scene);
+                scene_given = true;
+            }
+            "-f" | "--scene-file" => {
+                options.scene = SceneSource::File(value()?);
+                scene_file_given = true;
+            }
+            "-o" | "--output" => options.output = value()?,
+            "--format" => {
+                let format = value()?;
+                options.format = Some(
+                    ImageFormat::from_name(&format)
+                        .ok_or_else(|| usage(&format!("unknown image format `{}`", format)))?,
+                );
+            }
+            "-W" | "--width" => options.width = Some(positive(&name, &value()?)? as i32),
+            "-H" | "--height" => options.height = Some(positive(&name, &value()?)? as i32),
+            "--fov" => {
+                let fov = value()?;
+                options.field_of_view = Some(
+                    fov.parse::<f64>()
+                        .ok()
+                        .filter(|fov| *fov > 0.0 && *fov < std::f64::consts::PI)
+                        .ok_or_else(|| {
+                            usage(&format!(
+                                "`--fov` expects radians between 0 and pi, found `{}`",
+                                fov
+                            ))
+                        })?,
+                );
+            }
+            "--aperture" => options.aperture = Some(non_negative(&name, &value()?)?),
+            "--focal-distance" => {
+                let distance = non_negative(&name, &value()?)?;
+                if distance == 0.0 {
+                    return Err(usage("`--focal-distance` must be more than 0"));
+                }
+                options.focal_distance = Some(distance);
+            }
+            "-d" | "--depth" => {
+                let depth = value()?;
+                options.max_depth =
+                    depth
+                        .parse::<i32>()
+                        .ok()
+                        .filter(|d| *d >= 0)
+                        .ok_or_else(|| {
+                            usage(&format!(
+                                "`{}` expects a whole number, found `{}`",
+                                name, depth
+                            ))
+                        })?;
+            }
+            "--samples" => {
+                let n = value()?;
+                samples = n
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| is_square(*n))
+                    .ok_or_else(|| {
+                        usage(&format!(
+                            "`--samples` must be a square number like 1, 4, 9 or 16, found `{}`",
+                            n
+                        ))
+                    })?;
+            }
+            "--sampling" => {
+                sampling = value()?;
+                if !["grid", "jittered", "adaptive"].contains(&sampling.as_str()) {
+                    return Err(usage(&format!(
+                        "unknown sampling `{}`, use grid, jittered or adaptive",
+                        sampling
+                    )));
+                }
+            }
+            "--threshold" => {
+                let t = value()?;
+                threshold = t.parse::<f64>().ok().filter(|t| *t >= 0.0).ok_or_else(|| {
+                    usage(&format!("`--threshold` expects a number, found `{}`", t))
+                })?;
+            }
+            "--integrator" => {
+                integrator = value()?;
+                if !["whitted", "path"].contains(&integrator.as_str()) {
+                    return Err(usage(&format!(
+                        "unknown integrator `{}`, use whitted or path",
+                        integrator
+                    )));
+                }
+            }
+            "--paths" => paths = positive(&name, &value()?)?,
+            "-j" | "--threads" => options.threads = Some(positive(&name, &value()?)?),
+            "--export-obj" => options.export_obj = Some(value()?),
+            _ => return Err(usage(&format!("unknown option `{}`", arg))),
+        }
+    }
+
+    let side = (samples as f64).sqrt().round() as usize;
+    options.sampling = match sampling.as_str() {
+        "jittered" => Sampling::Jittered(side),
+        "adaptive" => Sampling::adaptive(threshold, samples),
+        _ => Sampling::Grid(side),
+    };
+
+    if integrator == "path" {
+        options.integrator = Integrator::PathTracing { samples: paths };
+    }
+
+    if scene_given && scene_file_given {
+        return Err(usage("use either --scene or --scene-file, not both"));
+    }
+
+    if options.format.is_none() && ImageFormat::from_path(&options.output).is_none() {
+        return Err(usage(&format!(
+            "can't tell the image format of `{}`, use --format",
+            options.output
+        )));
+    }
+
+    Ok(Command::Render(options))
+}
+
+pub fn run(args: impl Iterator<Item = String>) -> Result<(), CliError> {
+    match parse_args(args)? {
+        Command::Help => println!("{}", USAGE),
+        Command::ListScenes => SCENE_NAMES.iter().for_each(|name| println!("{}", name)),
+        Command::Render(options) => render(&options)?,
+    }
+
+    Ok(())
+}
+
+fn render(options: &Options) -> Result<(), CliError> {
+    let (world, camera) = match &options.scene {
+        SceneSource::Named(name) => {
+            let world = World::with_light(PointLight::with(point(-10.0, 10.0, -10.0), white()));
+            let world = scene_by_name(name, world)
+                .ok_or_else(|| usage(&format!("unknown scene `{}`, see --list-scenes", name)))?
+                .map_err(CliError::Failed)?;
+            let (from, to) = (point(-4.0, 3.0, -10.0), point(0.0, 0.0, 0.0));
+            let camera = Camera::new(1920, 1080, FRAC_PI_3)
+                .set_transform(view_transformation(from, to, vector(0.0, 1.0, 0.0)))
+                .with_focal_distance((to - from).magnitude());
+            (world, camera)
+        }
+        SceneSource::File(path) => {
+            load_scene_file(path).map_err(|e| CliError::Failed(format!("{}: {}", path, e)))?
+        }
+    };
+
+    if let Some(path) = &options.export_obj {
+        ObjExporter::new()
+            .save(&world, Path::new(path))
+            .map_err(|e| CliError::Failed(format!("could not write {}: {}", path, e)))?;
+        println!("Saved {}", path);
+        return Ok(());
+    }
+
+    let mut camera = Camera::new(
+        options.width.unwrap_or(camera.hsize),
+        options.height.unwrap_or(camera.vsize),
+        options.field_of_view.unwrap_or(camera.field_of_view),
+    )
+    .set_transform(camera.transform)
+    .with_aperture(options.aperture.unwrap_or(camera.aperture))
+    .with_focal_distance(options.focal_distance.unwrap_or(camera.focal_distance))
+    .with_max_depth(options.max_depth)
+    .with_sampling(options.sampling)
+    .with_integrator(options.integrator);
+
+    if let Some(threads) = options.threads {
+        camera = camera.with_threads(threads);
+    }
+
+    let format = options
+        .format
+        .or_else(|| ImageFormat::from_path(&options.output))
+        .unwrap_or(ImageFormat::Ppm);
+
+    camera
+        .render(world)
+        .save_as(&options.output, format)
+        .map_err(|e| CliError::Failed(format!("could not write {}: {}", options.output, e)))?;
+
+    println!("Saved {}", options.output);
+
+    Ok(())
+}
+
+fn usage(message: &str) -> CliError {
+    CliError::Usage(message.to_owned())
+}
+
+fn positive(name: &str, value: &str) -> Result<usize, CliError> {
+    value
+        .parse::<usize>()
+        .ok()
+        .filter(|n| *n > 0 && *n <= i32::MAX as usize)
+        .ok_or_else(|| {
+            usage(&format!(
+                "`{}` expects a positive whole number, found `{}`",
+                name, value
+            ))
+        })
+}
+
+fn non_negative(name: &str, value: &str) -> Result<f64, CliError> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n >= 0.0)
+        .ok_or_else(|| usage(&format!("`{}` expects a number, found `{}`", name, value)))
+}
+
+fn is_square(n: usize) -> bool {
+    let side = (n as f64).sqrt().round() as usize;
+    n > 0 && side * side == n
+}
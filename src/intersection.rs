@@ -50,6 +50,22 @@ impl PreparedComputation {
 
         r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
+
+    // where a ray going through the surface continues, None on total internal reflection
+    pub fn refracted_direction(&self) -> Option<Tuple> {
+        let n_ratio = self.n1 / self.n2;
+        let cos_i = self.eye_vector.dot(&self.normal_vector);
+        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+
+        if sin2_t > 1.0 {
+            return None;
+        }
+
+        // cos(theta_t) via trig identity
+        let cos_t = f64::sqrt(1.0 - sin2_t);
+
+        Some(self.normal_vector * (n_ratio * cos_i - cos_t) - self.eye_vector * n_ratio)
+    }
 }
 
 impl Intersection {
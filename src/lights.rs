@@ -1,6 +1,29 @@
-use crate::color::Color;
+use crate::color::{black, Color};
+use crate::matrix::Matrix;
+use crate::shape::ShapeType;
 use crate::tuple::Tuple;
-use crate::{point, white};
+use crate::world::ShapeId;
+use crate::{point, vector, white, World};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::{PI, TAU};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Light {
+    Point(PointLight),
+    Area(AreaLight),
+    Directional(DirectionalLight),
+    Spot(SpotLight),
+    Mesh(MeshLight),
+}
+
+// How one sample of a light is seen from a point being lit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    pub direction: Tuple, // normalized, from the point towards the light
+    pub distance: f64,    // how far a shadow ray has to go, infinite for directional lights
+    pub intensity: Color, // what arrives at the point, after any falloff
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct PointLight {
@@ -8,6 +31,149 @@ pub struct PointLight {
     pub intensity: Color,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct AreaLight {
+    pub corner: Tuple,
+    pub uvec: Tuple, // one cell along the first edge
+    pub usteps: usize,
+    pub vvec: Tuple, // one cell along the second edge
+    pub vsteps: usize,
+    pub intensity: Color,
+    pub jitter: bool,
+}
+
+// A light infinitely far away, like the sun. Every ray from it is parallel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectionalLight {
+    pub direction: Tuple, // the direction the light travels in
+    pub intensity: Color,
+}
+
+// An emissive shape lighting the scene, from points spread over its surface
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshLight {
+    pub shape: ShapeId,
+    pub positions: Vec<Tuple>,
+    pub normals: Vec<Tuple>, // of the surface at each position, outward on spheres and cubes
+    pub two_sided: Vec<bool>, // triangles give off light on both sides, closed shapes outwards
+    pub emission: Vec<Color>, // of the surface at each position
+    pub area: f64,           // of all the surfaces the positions are spread over
+    pub intensity: Color,    // the average emission, used for ambient
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotLight {
+    pub position: Tuple,
+    pub direction: Tuple,
+    pub inner_angle: f64, // full intensity inside this angle from the direction
+    pub outer_angle: f64, // no light outside this angle, smooth falloff in between
+    pub intensity: Color,
+}
+
+impl Light {
+    pub fn intensity(&self) -> Color {
+        match self {
+            Light::Point(light) => light.intensity,
+            Light::Area(light) => light.intensity,
+            Light::Directional(light) => light.intensity,
+            Light::Spot(light) => light.intensity,
+            Light::Mesh(light) => light.intensity,
+        }
+    }
+
+    // the intensity sent towards `point`, none outside the cone of a spot light
+    pub fn intensity_towards(&self, point: Tuple) -> Color {
+        match self {
+            Light::Spot(light) => light.intensity * light.falloff(point - light.position),
+            _ => self.intensity(),
+        }
+    }
+
+    // the shape a light comes from, which doesn't stand in the way of its own light
+    pub fn shape(&self) -> Option<ShapeId> {
+        match self {
+            Light::Mesh(light) => Some(light.shape),
+            _ => None,
+        }
+    }
+
+    /*
+     * The samples of the light that are used when lighting `point`. Shading and
+     * shadow tests both go through here, so they always agree on where the light is.
+     * */
+    pub fn samples_at(&self, point: Tuple) -> Vec<LightSample> {
+        match self {
+            Light::Point(light) => {
+                vec![sample_from_position(light.position, point, light.intensity)]
+            }
+            Light::Area(light) => light
+                .sample_positions(point)
+                .into_iter()
+                .map(|position| sample_from_position(position, point, light.intensity))
+                .collect(),
+            Light::Directional(light) => vec![LightSample {
+                direction: -light.direction.normalize(),
+                distance: f64::INFINITY,
+                intensity: light.intensity,
+            }],
+            Light::Spot(light) => {
+                let mut sample = sample_from_position(light.position, point, light.intensity);
+                sample.intensity = sample.intensity * light.falloff(-sample.direction);
+                vec![sample]
+            }
+            Light::Mesh(light) => light
+                .positions
+                .iter()
+                .enumerate()
+                .map(|(i, position)| {
+                    let mut sample = sample_from_position(*position, point, light.emission[i]);
+                    sample.intensity = sample.intensity * light.weight(i, &sample);
+                    sample
+                })
+                .collect(),
+        }
+    }
+}
+
+fn sample_from_position(position: Tuple, point: Tuple, intensity: Color) -> LightSample {
+    let v = position - point;
+    LightSample {
+        direction: v.normalize(),
+        distance: v.magnitude(),
+        intensity,
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Self {
+        Light::Point(light)
+    }
+}
+
+impl From<AreaLight> for Light {
+    fn from(light: AreaLight) -> Self {
+        Light::Area(light)
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Self {
+        Light::Directional(light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Light::Spot(light)
+    }
+}
+
+impl From<MeshLight> for Light {
+    fn from(light: MeshLight) -> Self {
+        Light::Mesh(light)
+    }
+}
+
 impl PointLight {
     pub fn with(position: Tuple, intensity: Color) -> PointLight {
         assert!(position.is_point());
@@ -16,20 +182,700 @@ impl PointLight {
             intensity,
         }
     }
+}
 
-    pub fn default() -> Self {
+impl Default for PointLight {
+    fn default() -> Self {
         PointLight::with(point(-10.0, 10.0, -10.0), white())
     }
 }
 
-#[test]
-fn position_and_intensity() {
-    use crate::{point, white};
+impl AreaLight {
+    pub fn with(
+        corner: Tuple,
+        full_uvec: Tuple,
+        usteps: usize,
+        full_vvec: Tuple,
+        vsteps: usize,
+        intensity: Color,
+    ) -> AreaLight {
+        assert!(corner.is_point());
+        assert!(full_uvec.is_vector() && full_vvec.is_vector());
+        assert!(usteps > 0 && vsteps > 0);
 
-    let position = point(0.0, 0.0, 0.0);
-    let intensity = white();
-    let point_light = PointLight::with(position, intensity);
+        AreaLight {
+            corner,
+            uvec: full_uvec / usteps as f64,
+            usteps,
+            vvec: full_vvec / vsteps as f64,
+            vsteps,
+            intensity,
+            jitter: false,
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn samples(&self) -> usize {
+        self.usteps * self.vsteps
+    }
+
+    pub fn position(&self) -> Tuple {
+        self.corner + (self.uvec * self.usteps as f64 + self.vvec * self.vsteps as f64) / 2.0
+    }
+
+    // (u_offset, v_offset) is where in the cell to sample, 0.5 being the middle
+    pub fn point_on_light(&self, u: usize, v: usize, offset: (f64, f64)) -> Tuple {
+        self.corner + self.uvec * (u as f64 + offset.0) + self.vvec * (v as f64 + offset.1)
+    }
+
+    /*
+     * Jitter is seeded from the lit point, so the same point is always lit by the same
+     * samples. That keeps renders reproducible, and identical no matter how many threads
+     * are used, while neighbouring points still get different samples to break up banding.
+     * */
+    fn sample_positions(&self, point: Tuple) -> Vec<Tuple> {
+        let mut rng = StdRng::seed_from_u64(
+            point.x.to_bits()
+                ^ point.y.to_bits().rotate_left(21)
+                ^ point.z.to_bits().rotate_left(42),
+        );
+
+        let mut positions = Vec::with_capacity(self.samples());
+        for v in 0..self.vsteps {
+            for u in 0..self.usteps {
+                let offset = match self.jitter {
+                    true => (rng.gen::<f64>(), rng.gen::<f64>()),
+                    false => (0.5, 0.5),
+                };
+                positions.push(self.point_on_light(u, v, offset));
+            }
+        }
+
+        positions
+    }
+}
+
+impl DirectionalLight {
+    pub fn with(direction: Tuple, intensity: Color) -> DirectionalLight {
+        assert!(direction.is_vector());
+        DirectionalLight {
+            direction: direction.normalize(),
+            intensity,
+        }
+    }
+}
+
+impl MeshLight {
+    /*
+     * Spreads `samples` points over the surface of `shape`, or over all the triangles,
+     * spheres and cubes in it when it is a group or csg, bigger surfaces getting more of
+     * them. Each point gives off the emissive color of its surface. None when there is
+     * nothing to spread the points over, or when a sphere is scaled unevenly. The points
+     * are placed once, with a seeded rng, so build the light after the shape has its
+     * final transformation.
+     * */
+    pub fn from_shape(world: &World, shape: ShapeId, samples: usize) -> Option<MeshLight> {
+        assert!(samples > 0, "a mesh light needs at least one sample");
+
+        let mut surfaces = vec![];
+        collect_surfaces(world, shape, &to_world(world, shape), &mut surfaces)?;
+        let total_area = surfaces.iter().map(|surface| surface.area).sum::<f64>();
+        if surfaces.is_empty() || total_area <= 0.0 {
+            return None;
+        }
+
+        let intensity = surfaces.iter().fold(black(), |sum, surface| {
+            sum + world.get_shape(surface.id).material.emissive * (surface.area / total_area)
+        });
+
+        let mut light = MeshLight {
+            shape,
+            positions: vec![],
+            normals: vec![],
+            two_sided: vec![],
+            emission: vec![],
+            area: total_area,
+            intensity,
+        };
+        let mut rng = StdRng::seed_from_u64(shape as u64);
+        for _ in 0..samples {
+            // pick a surface by area, then a point on it
+            let mut pick = rng.gen::<f64>() * total_area;
+            let surface = surfaces
+                .iter()
+                .find(|surface| {
+                    pick -= surface.area;
+                    pick <= 0.0
+                })
+                .unwrap_or(&surfaces[surfaces.len() - 1]);
+            let surface_shape = world.get_shape(surface.id);
+            let on_shape = point_on_surface(&surface_shape.shape_type, surface.face, &mut rng);
+            let mut normal =
+                &normal_on_surface(&surface_shape.shape_type, on_shape) * &surface.normal_to_world;
+            normal.w = 0.0;
+
+            light.positions.push(&on_shape * &surface.to_world);
+            light.normals.push(normal.normalize());
+            light.two_sided.push(matches!(
+                surface_shape.shape_type,
+                ShapeType::Triangle(..) | ShapeType::SmoothTriangle(..)
+            ));
+            light.emission.push(surface_shape.material.emissive);
+        }
+
+        Some(light)
+    }
+
+    /*
+     * How much of the light's emission sample `i` stands for, as seen in `sample`: its
+     * share of the area, foreshortened by the angle it is seen at and spread over the
+     * square of the distance. Divided by pi, so that lighting a diffuse surface with the
+     * samples gives what a path tracer finds bouncing off it onto the emissive surface.
+     * */
+    fn weight(&self, i: usize, sample: &LightSample) -> f64 {
+        let cos_light = self.normals[i].dot(&-sample.direction);
+        let cos_light = if self.two_sided[i] {
+            cos_light.abs()
+        } else {
+            cos_light.max(0.0)
+        };
+
+        // the lighting averages the samples, which takes care of the share of the area
+        self.area * cos_light / (PI * sample.distance.powi(2))
+    }
+}
+
+// a shape that can give off light, with what turns its points into world space
+struct Surface {
+    id: ShapeId,
+    to_world: Matrix,
+    normal_to_world: Matrix,
+    area: f64,
+    face: Option<usize>, // which side of a cube, they are picked one by one
+}
+
+fn to_world(world: &World, id: ShapeId) -> Matrix {
+    let shape = world.get_shape(id);
+    match shape.parent {
+        Some(parent) => &to_world(world, parent) * &shape.transformation,
+        None => shape.transformation,
+    }
+}
+
+// None when a sphere is scaled unevenly, its area and the spread of its points would be off
+fn collect_surfaces(
+    world: &World,
+    id: ShapeId,
+    to_world: &Matrix,
+    surfaces: &mut Vec<Surface>,
+) -> Option<()> {
+    let surface = |area, face| Surface {
+        id,
+        to_world: *to_world,
+        normal_to_world: to_world.inverse().transpose(),
+        area,
+        face,
+    };
+
+    let area = match &world.get_shape(id).shape_type {
+        ShapeType::Group(children, _) => {
+            for child in children {
+                let child_to_world = to_world * &world.get_shape(*child).transformation;
+                collect_surfaces(world, *child, &child_to_world, surfaces)?;
+            }
+            return Some(());
+        }
+        ShapeType::CSG(_, left, right) => {
+            for child in [left, right] {
+                let child_to_world = to_world * &world.get_shape(*child).transformation;
+                collect_surfaces(world, *child, &child_to_world, surfaces)?;
+            }
+            return Some(());
+        }
+        ShapeType::Triangle(p1, p2, p3, ..) | ShapeType::SmoothTriangle(p1, p2, p3, ..) => {
+            let [p1, p2, p3] = [p1, p2, p3].map(|p| p * to_world);
+            (p2 - p1).cross(&(p3 - p1)).magnitude() / 2.0
+        }
+        ShapeType::Sphere => {
+            // evenly scaled, the axes stay square to each other and as long as the radius
+            let [x, y, z] = [0, 1, 2].map(|axis| &axis_vector(axis) * to_world);
+            let radius = x.magnitude();
+            let tolerance = 1e-9 * radius * radius;
+            let is_uniform = [y.dot(&y), z.dot(&z)]
+                .iter()
+                .all(|length2| (length2 - radius * radius).abs() <= tolerance)
+                && [x.dot(&y), y.dot(&z), z.dot(&x)]
+                    .iter()
+                    .all(|cos| cos.abs() <= tolerance);
+            if !is_uniform {
+                return None;
+            }
+            4.0 * PI * radius * radius
+        }
+        ShapeType::Cube => {
+            // face by face, a cube flattened into a panel has nearly all its area on two
+            for face in 0..6 {
+                let [u, v, _] = cube_face_axes(face);
+                let edge = |axis| &(axis_vector(axis) * 2.0) * to_world;
+                surfaces.push(surface(edge(u).cross(&edge(v)).magnitude(), Some(face)));
+            }
+            return Some(());
+        }
+        _ => return Some(()),
+    };
+
+    surfaces.push(surface(area, None));
+    Some(())
+}
+
+fn axis_vector(axis: usize) -> Tuple {
+    let mut coordinates = [0.0; 3];
+    coordinates[axis] = 1.0;
+    vector(coordinates[0], coordinates[1], coordinates[2])
+}
 
-    assert_eq!(point_light.position, position);
-    assert_eq!(point_light.intensity, intensity);
+// the axes along the two edges of a cube face, then the one it faces along
+fn cube_face_axes(face: usize) -> [usize; 3] {
+    let k = face / 2;
+    [k, (1 + k) % 3, (2 + k) % 3]
+}
+
+// a random point on a triangle, sphere or cube face, in object space
+fn point_on_surface(shape_type: &ShapeType, face: Option<usize>, rng: &mut StdRng) -> Tuple {
+    match shape_type {
+        ShapeType::Triangle(p1, p2, p3, ..) | ShapeType::SmoothTriangle(p1, p2, p3, ..) => {
+            let (mut a, mut b) = (rng.gen::<f64>(), rng.gen::<f64>());
+            // folding the far half of the square back keeps the point inside
+            if a + b > 1.0 {
+                (a, b) = (1.0 - a, 1.0 - b);
+            }
+            *p1 + (*p2 - *p1) * a + (*p3 - *p1) * b
+        }
+        ShapeType::Sphere => {
+            let y = 1.0 - 2.0 * rng.gen::<f64>();
+            let radius = (1.0 - y * y).sqrt();
+            let angle = TAU * rng.gen::<f64>();
+            point(radius * angle.cos(), y, radius * angle.sin())
+        }
+        ShapeType::Cube => {
+            let face = face.expect("cubes are collected face by face");
+            let [u, v, across] = cube_face_axes(face);
+            let mut coordinates = [0.0; 3];
+            coordinates[u] = rng.gen::<f64>() * 2.0 - 1.0;
+            coordinates[v] = rng.gen::<f64>() * 2.0 - 1.0;
+            coordinates[across] = if face.is_multiple_of(2) { -1.0 } else { 1.0 };
+            point(coordinates[0], coordinates[1], coordinates[2])
+        }
+        _ => unreachable!("only triangles, spheres and cubes are collected"),
+    }
+}
+
+// the outward normal at a point from `point_on_surface`, in object space
+fn normal_on_surface(shape_type: &ShapeType, on_shape: Tuple) -> Tuple {
+    match shape_type {
+        ShapeType::Triangle(p1, p2, p3, ..) | ShapeType::SmoothTriangle(p1, p2, p3, ..) => {
+            (*p2 - *p1).cross(&(*p3 - *p1))
+        }
+        ShapeType::Sphere => on_shape - point(0.0, 0.0, 0.0),
+        ShapeType::Cube => {
+            let largest = on_shape.x.abs().max(on_shape.y.abs()).max(on_shape.z.abs());
+            let axis = |c: f64| if c.abs() == largest { c.signum() } else { 0.0 };
+            vector(axis(on_shape.x), axis(on_shape.y), axis(on_shape.z))
+        }
+        _ => unreachable!("only triangles, spheres and cubes are collected"),
+    }
+}
+
+impl SpotLight {
+    pub fn with(
+        position: Tuple,
+        direction: Tuple,
+        inner_angle: f64,
+        outer_angle: f64,
+        intensity: Color,
+    ) -> SpotLight {
+        assert!(position.is_point());
+        assert!(direction.is_vector());
+        assert!(inner_angle <= outer_angle);
+        SpotLight {
+            position,
+            direction: direction.normalize(),
+            inner_angle,
+            outer_angle,
+            intensity,
+        }
+    }
+
+    // how much of the light goes in `direction`, smoothstep between the two cone angles
+    pub fn falloff(&self, direction: Tuple) -> f64 {
+        let cos_angle = direction.normalize().dot(&self.direction);
+        let cos_inner = self.inner_angle.cos();
+        let cos_outer = self.outer_angle.cos();
+
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+#[cfg(test)]
+mod lights_test {
+    use crate::color::{black, color, white};
+    use crate::lights::{
+        AreaLight, DirectionalLight, Light, LightSample, MeshLight, PointLight, SpotLight,
+    };
+    use crate::material::Material;
+    use crate::matrix::Matrix;
+    use crate::shape::Shape;
+    use crate::tuple::{point, point_i, vector, vector_i};
+    use crate::world::{ShapeId, World};
+    use std::f64::consts::{FRAC_PI_4, FRAC_PI_6, PI};
+
+    #[test]
+    fn position_and_intensity() {
+        let position = point(0.0, 0.0, 0.0);
+        let intensity = white();
+        let point_light = PointLight::with(position, intensity);
+
+        assert_eq!(point_light.position, position);
+        assert_eq!(point_light.intensity, intensity);
+    }
+
+    #[test]
+    fn creating_an_area_light() {
+        let corner = point_i(0, 0, 0);
+        let light = AreaLight::with(corner, vector_i(2, 0, 0), 4, vector_i(0, 0, 1), 2, white());
+
+        assert_eq!(light.corner, corner);
+        assert_eq!(light.uvec, vector(0.5, 0.0, 0.0));
+        assert_eq!(light.usteps, 4);
+        assert_eq!(light.vvec, vector(0.0, 0.0, 0.5));
+        assert_eq!(light.vsteps, 2);
+        assert_eq!(light.samples(), 8);
+        assert_eq!(light.position(), point(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn finding_single_point_on_area_light() {
+        let light = AreaLight::with(
+            point_i(0, 0, 0),
+            vector_i(2, 0, 0),
+            4,
+            vector_i(0, 0, 1),
+            2,
+            white(),
+        );
+
+        let cases = [
+            (0, 0, point(0.25, 0.0, 0.25)),
+            (1, 0, point(0.75, 0.0, 0.25)),
+            (0, 1, point(0.25, 0.0, 0.75)),
+            (2, 0, point(1.25, 0.0, 0.25)),
+            (3, 1, point(1.75, 0.0, 0.75)),
+        ];
+
+        for (u, v, expected) in cases {
+            assert_eq!(light.point_on_light(u, v, (0.5, 0.5)), expected);
+        }
+    }
+
+    #[test]
+    fn point_light_is_sampled_at_its_position() {
+        let light = Light::from(PointLight::with(point_i(0, 3, 4), white()));
+
+        assert_eq!(
+            light.samples_at(point_i(0, 0, 0)),
+            vec![LightSample {
+                direction: vector(0.0, 0.6, 0.8),
+                distance: 5.0,
+                intensity: white(),
+            }]
+        );
+    }
+
+    #[test]
+    fn directional_light_comes_from_the_same_direction_everywhere() {
+        let light = Light::from(DirectionalLight::with(vector_i(0, -2, 0), white()));
+
+        for p in [point_i(0, 0, 0), point_i(100, -3, 7)] {
+            assert_eq!(
+                light.samples_at(p),
+                vec![LightSample {
+                    direction: vector_i(0, 1, 0),
+                    distance: f64::INFINITY,
+                    intensity: white(),
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn spot_light_falls_off_between_inner_and_outer_cone() {
+        let spot = SpotLight::with(
+            point_i(0, 10, 0),
+            vector_i(0, -1, 0),
+            FRAC_PI_6,
+            FRAC_PI_4,
+            white(),
+        );
+
+        assert_eq!(spot.falloff(vector_i(0, -1, 0)), 1.0);
+        assert_eq!(spot.falloff(vector_i(1, 0, 0)), 0.0);
+        assert_eq!(spot.falloff(vector(0.5, -1.0, 0.0)), 1.0); // ~26.6 degrees
+        assert_eq!(spot.falloff(vector(1.0, -0.9, 0.0)), 0.0); // ~48 degrees
+
+        let halfway = spot.falloff(vector(0.8, -1.0, 0.0)); // ~38.7 degrees
+        assert!(halfway > 0.0 && halfway < 1.0);
+    }
+
+    #[test]
+    fn spot_light_intensity_depends_on_where_the_point_is() {
+        let light = Light::from(SpotLight::with(
+            point_i(0, 10, 0),
+            vector_i(0, -1, 0),
+            FRAC_PI_6,
+            FRAC_PI_4,
+            color(1.0, 0.5, 0.5),
+        ));
+
+        let under = light.samples_at(point_i(0, 0, 0));
+        let outside = light.samples_at(point_i(20, 0, 0));
+
+        assert_eq!(under[0].intensity, color(1.0, 0.5, 0.5));
+        assert_eq!(under[0].direction, vector_i(0, 1, 0));
+        assert_eq!(under[0].distance, 10.0);
+        assert_eq!(outside[0].intensity, black());
+    }
+
+    #[test]
+    fn jittered_samples_stay_in_their_cell_and_are_reproducible() {
+        let light = AreaLight::with(
+            point_i(0, 0, 0),
+            vector_i(2, 0, 0),
+            4,
+            vector_i(0, 0, 1),
+            2,
+            white(),
+        )
+        .with_jitter(true);
+        let lit_point = point(0.3, -2.0, 1.7);
+
+        let samples = light.sample_positions(lit_point);
+
+        assert_eq!(samples.len(), 8);
+        for (i, sample) in samples.iter().enumerate() {
+            let (u, v) = ((i % 4) as f64, (i / 4) as f64);
+            assert!(sample.x >= u * 0.5 && sample.x <= (u + 1.0) * 0.5);
+            assert!(sample.z >= v * 0.5 && sample.z <= (v + 1.0) * 0.5);
+        }
+        assert_eq!(samples, light.sample_positions(lit_point));
+    }
+
+    fn glowing(emissive: crate::color::Color) -> Material {
+        Material {
+            emissive,
+            ..Material::default()
+        }
+    }
+
+    #[test]
+    fn mesh_light_samples_lie_on_a_sphere() {
+        let mut world = World::with_lights(vec![]);
+        let sphere = world.add_shape(
+            Shape::sphere_default()
+                .with_transform(
+                    Matrix::identity()
+                        .scale(2.0, 2.0, 2.0)
+                        .translate(0.0, 5.0, 0.0),
+                )
+                .with_material(glowing(color(1.0, 0.5, 0.0))),
+        );
+
+        let light = MeshLight::from_shape(&world, sphere, 16).unwrap();
+
+        assert_eq!(light.positions.len(), 16);
+        assert_eq!(light.intensity, color(1.0, 0.5, 0.0));
+        for position in &light.positions {
+            let distance = (*position - point(0.0, 5.0, 0.0)).magnitude();
+            assert!((distance - 2.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn mesh_light_samples_lie_on_a_cube() {
+        let mut world = World::with_lights(vec![]);
+        let cube = world.add_shape(Shape::cube_default().with_material(glowing(white())));
+
+        let light = MeshLight::from_shape(&world, cube, 32).unwrap();
+
+        for p in &light.positions {
+            let largest = p.x.abs().max(p.y.abs()).max(p.z.abs());
+            assert!((largest - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn mesh_light_from_a_quad_of_triangles() {
+        let mut world = World::with_lights(vec![]);
+        let quad = world.add_shape(Shape::group());
+        for (p1, p2, p3) in [
+            (point_i(0, 2, 0), point_i(1, 2, 0), point_i(1, 2, 1)),
+            (point_i(0, 2, 0), point_i(1, 2, 1), point_i(0, 2, 1)),
+        ] {
+            let triangle =
+                world.add_shape(Shape::triangle(p1, p2, p3).with_material(glowing(white())));
+            world.add_shape_to_group(quad, triangle);
+        }
+
+        let light = MeshLight::from_shape(&world, quad, 20).unwrap();
+
+        assert_eq!(light.shape, quad);
+        assert_eq!(light.intensity, white());
+        for p in &light.positions {
+            assert_eq!(p.y, 2.0);
+            assert!((0.0..=1.0).contains(&p.x) && (0.0..=1.0).contains(&p.z));
+        }
+        assert_eq!(
+            light.positions,
+            MeshLight::from_shape(&world, quad, 20).unwrap().positions
+        );
+    }
+
+    // what a point at the origin gets from a glowing unit sphere `distance` above it
+    fn light_from_sphere(distance: f64, samples: usize) -> f64 {
+        let mut world = World::with_lights(vec![]);
+        let sphere = world.add_shape(
+            Shape::sphere_default()
+                .with_transform(Matrix::identity().translate(0.0, distance, 0.0))
+                .with_material(glowing(white())),
+        );
+        let light: Light = MeshLight::from_shape(&world, sphere, samples)
+            .unwrap()
+            .into();
+
+        let samples = light.samples_at(point(0.0, 0.0, 0.0));
+        samples.iter().map(|sample| sample.intensity.r).sum::<f64>() / samples.len() as f64
+    }
+
+    #[test]
+    fn mesh_light_samples_are_weighted_by_area_angle_and_distance() {
+        // the sphere covers a solid angle of about pi / distance^2
+        assert!((light_from_sphere(10.0, 4096) - 0.01).abs() < 0.001);
+        assert!((light_from_sphere(20.0, 4096) - 0.0025).abs() < 0.00025);
+    }
+
+    #[test]
+    fn mesh_lights_are_as_bright_with_any_number_of_samples() {
+        let few = light_from_sphere(10.0, 64);
+        let many = light_from_sphere(10.0, 4096);
+
+        assert!((few - many).abs() < 0.2 * many);
+    }
+
+    // the light a point 2 below the middle of a glowing 2 by 2 panel at y = 0 gets
+    fn light_from_panel(world: &World, panel: ShapeId) -> (MeshLight, f64) {
+        let light = MeshLight::from_shape(world, panel, 4096).unwrap();
+        let samples = Light::from(light.clone()).samples_at(point(0.0, -2.0, 0.0));
+        let average =
+            samples.iter().map(|sample| sample.intensity.r).sum::<f64>() / samples.len() as f64;
+
+        (light, average)
+    }
+
+    #[test]
+    fn a_cube_panel_lights_like_a_quad_of_triangles() {
+        let mut world = World::with_lights(vec![]);
+        let cube = world.add_shape(
+            Shape::cube_default()
+                .with_transform(Matrix::identity().scale(1.0, 0.001, 1.0))
+                .with_material(glowing(white())),
+        );
+        let quad = world.add_shape(Shape::group());
+        for (p1, p2, p3) in [
+            (point_i(-1, 0, -1), point_i(1, 0, -1), point_i(1, 0, 1)),
+            (point_i(-1, 0, -1), point_i(1, 0, 1), point_i(-1, 0, 1)),
+        ] {
+            let triangle =
+                world.add_shape(Shape::triangle(p1, p2, p3).with_material(glowing(white())));
+            world.add_shape_to_group(quad, triangle);
+        }
+
+        let (cube_light, from_cube) = light_from_panel(&world, cube);
+        let (_, from_quad) = light_from_panel(&world, quad);
+
+        assert!((cube_light.area - 8.016).abs() < 1e-9);
+        assert!((from_cube - from_quad).abs() < 0.05 * from_quad);
+        // the thin edges only get their share of the samples
+        let on_edges = cube_light
+            .positions
+            .iter()
+            .filter(|p| p.y.abs() < 0.001 - 1e-9)
+            .count();
+        assert!(on_edges < 40);
+    }
+
+    #[test]
+    fn unevenly_scaled_spheres_cannot_be_mesh_lights() {
+        let mut world = World::with_lights(vec![]);
+        let egg = world.add_shape(
+            Shape::sphere_default()
+                .with_transform(Matrix::identity().scale(1.0, 2.0, 1.0))
+                .with_material(glowing(white())),
+        );
+        let ball = world.add_shape(
+            Shape::sphere_default()
+                .with_transform(Matrix::identity().scale(2.0, 2.0, 2.0).rotate_x(0.5))
+                .with_material(glowing(white())),
+        );
+
+        assert_eq!(MeshLight::from_shape(&world, egg, 4), None);
+        assert!((MeshLight::from_shape(&world, ball, 4).unwrap().area - 16.0 * PI).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mesh_light_samples_give_off_the_color_of_their_surface() {
+        let mut world = World::with_lights(vec![]);
+        let lamps = world.add_shape(Shape::group());
+        for (emissive, x) in [(color(1.0, 0.0, 0.0), -2.0), (color(0.0, 0.0, 1.0), 2.0)] {
+            let lamp = world.add_shape(
+                Shape::sphere_default()
+                    .with_transform(Matrix::identity().translate(x, 0.0, 0.0))
+                    .with_material(glowing(emissive)),
+            );
+            world.add_shape_to_group(lamps, lamp);
+        }
+
+        let light = MeshLight::from_shape(&world, lamps, 64).unwrap();
+
+        assert_eq!(light.intensity, color(0.5, 0.0, 0.5));
+        for (position, emission) in light.positions.iter().zip(&light.emission) {
+            let expected = if position.x < 0.0 {
+                color(1.0, 0.0, 0.0)
+            } else {
+                color(0.0, 0.0, 1.0)
+            };
+            assert_eq!(*emission, expected);
+        }
+        let lit = Light::from(light).samples_at(point(-2.0, 5.0, 0.0));
+        assert!(lit.iter().all(|sample| sample.intensity.g == 0.0
+            && (sample.intensity.r == 0.0 || sample.intensity.b == 0.0)));
+    }
+
+    #[test]
+    fn planes_cannot_be_mesh_lights() {
+        let mut world = World::with_lights(vec![]);
+        let plane = world.add_shape(Shape::plane_default().with_material(glowing(white())));
+
+        assert_eq!(MeshLight::from_shape(&world, plane, 4), None);
+    }
 }
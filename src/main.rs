@@ -1,7 +1,15 @@
 extern crate core;
 
-use ray_tracer_challenge::main_lib;
+use std::{env, process};
+
+use ray_tracer_challenge::cli::{run, CliError, USAGE};
 
 fn main() {
-    main_lib()
+    if let Err(error) = run(env::args().skip(1)) {
+        eprintln!("error: {}", error);
+        if let CliError::Usage(_) = error {
+            eprintln!("\n{}", USAGE);
+        }
+        process::exit(error.exit_code());
+    }
 }
@@ -1,11 +1,16 @@
+pub mod microfacet;
+
+pub use self::microfacet::Microfacet;
+
 use crate::color::{black, rgb, white, Color};
-use crate::lights::PointLight;
+use crate::lights::Light;
+use crate::matrix::is_zero_float;
 use crate::pattern::Pattern;
 use crate::shape::Shape;
 use crate::tuple::Tuple;
 use crate::World;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Material {
     pub color: Color,
     pub ambient: f64,
@@ -16,6 +21,10 @@ pub struct Material {
     pub transparency: f64,
     pub refractive_index: f64,
     pub pattern: Option<Pattern>,
+    pub emissive: Color, // light given off by the surface itself, see `MeshLight` to light others
+    pub microfacet: Option<Microfacet>, // physically based shading instead of Phong
+    pub roughness: f64,  // blurs reflections and refractions, 0.0 is a perfect mirror
+    pub absorption: Color, // lost for each unit travelled inside, see `transmittance`
 }
 
 impl Default for Material {
@@ -36,6 +45,10 @@ impl Material {
             transparency: 0.0,
             refractive_index: 1.0,
             pattern: None,
+            emissive: black(),
+            microfacet: None,
+            roughness: 0.0,
+            absorption: black(),
         }
     }
 
@@ -53,6 +66,19 @@ impl Material {
         }
     }
 
+    /*
+     * A physically based material, see `Microfacet`. Mirror reflections follow Fresnel
+     * instead of `reflective`, and a little ambient stands in for the light around.
+     * */
+    pub fn microfacet(color: Color, metallic: f64, roughness: f64) -> Material {
+        Material {
+            color,
+            ambient: 0.05,
+            microfacet: Some(Microfacet::new(metallic, roughness)),
+            ..Material::default()
+        }
+    }
+
     pub fn glass() -> Self {
         Material {
             color: white(),
@@ -64,6 +90,10 @@ impl Material {
             specular: 0.9,
             reflective: 1.0,
             pattern: None,
+            emissive: black(),
+            microfacet: None,
+            roughness: 0.0,
+            absorption: black(),
         }
     }
 
@@ -78,6 +108,10 @@ impl Material {
             transparency: 0.0,
             refractive_index: 1.0,
             pattern: None,
+            emissive: black(),
+            microfacet: None,
+            roughness: 0.0,
+            absorption: black(),
         }
     }
 
@@ -92,6 +126,10 @@ impl Material {
             transparency: 0.0,
             refractive_index: 1.0,
             pattern: None,
+            emissive: black(),
+            microfacet: None,
+            roughness: 0.0,
+            absorption: black(),
         }
     }
 
@@ -106,6 +144,10 @@ impl Material {
             transparency: 0.9,
             refractive_index: 1.0000034,
             pattern: None,
+            emissive: black(),
+            microfacet: None,
+            roughness: 0.0,
+            absorption: black(),
         }
     }
 
@@ -120,6 +162,10 @@ impl Material {
             transparency: 0.8,
             refractive_index: 1.0000034,
             pattern: None,
+            emissive: black(),
+            microfacet: None,
+            roughness: 0.0,
+            absorption: black(),
         }
     }
 
@@ -133,61 +179,128 @@ impl Material {
         }
     }
 
+    // how blurred reflections and refractions are, a microfacet's own roughness if it has one
+    pub fn lobe_roughness(&self) -> f64 {
+        match &self.microfacet {
+            Some(microfacet) => microfacet.roughness,
+            None => self.roughness,
+        }
+    }
+
+    /*
+     * How much of the light is left after travelling `distance` inside, following
+     * Beer-Lambert: every unit of distance takes away the same fraction, `absorption` per
+     * channel. Thin glass stays almost clear, thick glass gets darker and tinted.
+     * */
+    pub fn transmittance(&self, distance: f64) -> Color {
+        Color {
+            r: (-self.absorption.r * distance).exp(),
+            g: (-self.absorption.g * distance).exp(),
+            b: (-self.absorption.b * distance).exp(),
+        }
+    }
+
+    // the color of the surface at `point`, from the pattern if there is one
+    pub fn color_at(&self, object: &Shape, point: Tuple, world: &World) -> Color {
+        match &self.pattern {
+            Some(pattern) => pattern.color_at_object(world, object, point),
+            None => self.color,
+        }
+    }
+
+    /*
+     * `light_intensity` is how much of the light reaches the point, from 0.0 when it is
+     * completely in shadow to 1.0 when nothing is in the way.
+     * */
     pub fn lighting(
         &self,
         object: &Shape,
-        light: &PointLight,
+        light: &Light,
         point: Tuple,
         eye_vector: Tuple,
         normal_vector: Tuple,
-        in_shadow: bool,
+        light_intensity: f64,
         world: &World,
     ) -> Color {
-        let diffuse: Color;
-        let specular: Color;
-
-        let black = black();
-        let color = match self.pattern {
-            Some(pattern) => pattern.color_at_object(world, object, point),
-            None => self.color,
-        };
-
         // combine surface color with lights color/intensity
-        let effective_color = color * light.intensity;
-
-        // direction of light source
-        let light_vector = (light.position - point).normalize();
+        let effective_color = self.color_at(object, point, world) * light.intensity_towards(point);
 
         // ambient contribution
         let ambient = effective_color * self.ambient;
 
-        if in_shadow {
-            return ambient;
+        ambient
+            + self.direct_lighting(
+                object,
+                light,
+                point,
+                eye_vector,
+                normal_vector,
+                light_intensity,
+                world,
+            )
+    }
+
+    // the diffuse and specular part of `lighting`, what actually comes from the light
+    pub fn direct_lighting(
+        &self,
+        object: &Shape,
+        light: &Light,
+        point: Tuple,
+        eye_vector: Tuple,
+        normal_vector: Tuple,
+        light_intensity: f64,
+        world: &World,
+    ) -> Color {
+        let black = black();
+
+        if is_zero_float(light_intensity) {
+            return black;
         }
 
-        // light_dot_normal is cosine of angle between light vector and normal vector.
-        let light_dot_normal = light_vector.dot(&normal_vector);
+        let color = self.color_at(object, point, world);
+
+        let samples = light.samples_at(point);
+        let mut sum = black;
+
+        for sample in &samples {
+            // direction of light source
+            let light_vector = sample.direction;
+
+            if let Some(microfacet) = &self.microfacet {
+                sum = sum
+                    + microfacet.shade(color, normal_vector, light_vector, eye_vector)
+                        * sample.intensity;
+                continue;
+            }
+
+            // only what actually reaches the point, after e.g. the falloff of a spot light
+            let effective_color = color * sample.intensity;
 
-        if light_dot_normal < 0.0 {
-            // light on other side of surface, or in shadow of other object.
-            diffuse = black;
-            specular = black;
-        } else {
-            diffuse = effective_color * (self.diffuse * light_dot_normal);
+            // light_dot_normal is cosine of angle between light vector and normal vector.
+            let light_dot_normal = light_vector.dot(&normal_vector);
+
+            if light_dot_normal < 0.0 {
+                // light on other side of surface, or in shadow of other object.
+                continue;
+            }
+
+            let diffuse = effective_color * (self.diffuse * light_dot_normal);
 
             // cosine of angle between reflection vector and eye_vector
             let reflect_vector = (-light_vector).reflect(&normal_vector);
             let reflect_dot_eye = reflect_vector.dot(&eye_vector);
 
-            if reflect_dot_eye <= 0.0 {
+            let specular = if reflect_dot_eye <= 0.0 {
                 // light reflects away from the eye
-                specular = black;
+                black
             } else {
                 let factor = reflect_dot_eye.powf(self.shininess);
-                specular = light.intensity * self.specular * factor;
-            }
+                sample.intensity * self.specular * factor
+            };
+
+            sum = sum + diffuse + specular;
         }
 
-        ambient + diffuse + specular
+        sum * (light_intensity / samples.len() as f64)
     }
 }
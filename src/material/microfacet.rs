@@ -0,0 +1,179 @@
+use std::f64::consts::PI;
+
+use crate::color::{black, white, Color};
+use crate::tuple::Tuple;
+
+#[cfg(test)]
+mod microfacet_test {
+    use crate::color::{color, white};
+    use crate::tuple::vector;
+
+    use super::Microfacet;
+
+    #[test]
+    fn dielectrics_reflect_four_percent_head_on() {
+        let plastic = Microfacet::new(0.0, 0.5);
+
+        assert_eq!(
+            plastic.reflectance(color(1.0, 0.0, 0.0), 1.0),
+            color(0.04, 0.04, 0.04)
+        );
+    }
+
+    #[test]
+    fn metals_reflect_their_base_color() {
+        let gold = Microfacet::new(1.0, 0.2);
+
+        assert_eq!(
+            gold.reflectance(color(1.0, 0.8, 0.3), 1.0),
+            color(1.0, 0.8, 0.3)
+        );
+    }
+
+    #[test]
+    fn smooth_surfaces_reflect_everything_at_grazing_angles() {
+        let smooth = Microfacet::new(0.0, 0.0);
+        let rough = Microfacet::new(0.0, 1.0);
+
+        assert_eq!(smooth.reflectance(white(), 0.0), white());
+        assert_eq!(rough.reflectance(white(), 0.0), color(0.04, 0.04, 0.04));
+    }
+
+    #[test]
+    fn a_matte_dielectric_is_close_to_lambert() {
+        let matte = Microfacet::new(0.0, 1.0);
+        let normal = vector(0.0, 1.0, 0.0);
+
+        let lit = matte.shade(white(), normal, normal, normal);
+
+        assert!(lit.r > 0.9 && lit.r < 1.1);
+    }
+
+    #[test]
+    fn highlights_get_smaller_and_brighter_with_less_roughness() {
+        let normal = vector(0.0, 1.0, 0.0);
+        let light = vector(0.0, 1.0, 1.0).normalize();
+        let mirrored = vector(0.0, 1.0, -1.0).normalize();
+        let off = vector(0.3, 1.0, -1.0).normalize();
+
+        let glossy = Microfacet::new(1.0, 0.1);
+        let rough = Microfacet::new(1.0, 0.6);
+
+        let glossy_peak = glossy.shade(white(), normal, light, mirrored).r;
+        let rough_peak = rough.shade(white(), normal, light, mirrored).r;
+        assert!(glossy_peak > rough_peak);
+        assert!(
+            glossy.shade(white(), normal, light, off).r
+                < rough.shade(white(), normal, light, off).r
+        );
+    }
+
+    #[test]
+    fn metals_have_no_diffuse() {
+        let metal = Microfacet::new(1.0, 0.3);
+        let normal = vector(0.0, 1.0, 0.0);
+        let light = vector(0.0, 1.0, 1.0).normalize();
+        let away = vector(0.0, 1.0, 1.0).normalize();
+
+        // eye and light on the same side, far from the mirror direction
+        let lit = metal.shade(color(1.0, 0.0, 0.0), normal, light, away);
+
+        assert!(lit.g < 1e-9 && lit.b < 1e-9);
+    }
+}
+
+// reflectance of dielectrics looked at head on, about that of plastic, glass or water
+const DIELECTRIC_REFLECTANCE: f64 = 0.04;
+
+// below this GGX gives highlights too small to ever be hit by a sample
+const MIN_ROUGHNESS: f64 = 0.02;
+
+/*
+ * Physically based shading, used instead of Phong's diffuse, specular and shininess when a
+ * material has one. The base color is the material's color or pattern. `metallic` goes
+ * from a dielectric (0.0) like plastic, with a white highlight over a diffuse color, to a
+ * metal (1.0) that reflects its base color and has no diffuse at all. `roughness` goes
+ * from a polished (0.0) to a matte (1.0) surface. Highlights are GGX / Cook-Torrance and
+ * how much is reflected follows Fresnel, growing towards grazing angles.
+ * */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Microfacet {
+    pub metallic: f64,
+    pub roughness: f64,
+}
+
+impl Microfacet {
+    pub fn new(metallic: f64, roughness: f64) -> Self {
+        Microfacet {
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+        }
+    }
+
+    // what is reflected looked at head on, dielectrics are uncolored and metals tinted
+    fn base_reflectance(&self, base_color: Color) -> Color {
+        white() * (DIELECTRIC_REFLECTANCE * (1.0 - self.metallic)) + base_color * self.metallic
+    }
+
+    /*
+     * How much of what the surface mirrors reaches the eye, `cos` being the cosine of the
+     * angle between eye and normal. Schlick's Fresnel, held back for rough surfaces that
+     * scatter their reflection instead of mirroring it at grazing angles.
+     * */
+    pub fn reflectance(&self, base_color: Color, cos: f64) -> Color {
+        let f0 = self.base_reflectance(base_color);
+        let grazing = 1.0 - self.roughness;
+        let edge = Color {
+            r: grazing.max(f0.r),
+            g: grazing.max(f0.g),
+            b: grazing.max(f0.b),
+        };
+
+        f0 + (edge - f0) * (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+    }
+
+    /*
+     * The light reflected towards the eye for each unit of light arriving from
+     * `light_vector`, cosine included. Scaled by pi, so that a white matte dielectric lit
+     * head on is about as bright as a Phong material with a diffuse of 1.0.
+     * */
+    pub fn shade(
+        &self,
+        base_color: Color,
+        normal_vector: Tuple,
+        light_vector: Tuple,
+        eye_vector: Tuple,
+    ) -> Color {
+        let light_dot_normal = light_vector.dot(&normal_vector);
+        let eye_dot_normal = eye_vector.dot(&normal_vector);
+        if light_dot_normal <= 0.0 || eye_dot_normal <= 0.0 {
+            return black();
+        }
+
+        let halfway = (light_vector + eye_vector).normalize();
+        let halfway_dot_normal = halfway.dot(&normal_vector).max(0.0);
+        let halfway_dot_eye = halfway.dot(&eye_vector).max(0.0);
+
+        let roughness = self.roughness.max(MIN_ROUGHNESS);
+        let alpha2 = roughness.powi(4);
+
+        // GGX: how many microfacets face halfway between light and eye
+        let distribution =
+            alpha2 / (PI * (halfway_dot_normal.powi(2) * (alpha2 - 1.0) + 1.0).powi(2));
+
+        // Smith: how many of them are neither hidden from the light nor from the eye
+        let k = (roughness + 1.0).powi(2) / 8.0;
+        let masking = |cos: f64| cos / (cos * (1.0 - k) + k);
+        let geometry = masking(light_dot_normal) * masking(eye_dot_normal);
+
+        let f0 = self.base_reflectance(base_color);
+        let fresnel = f0 + (white() - f0) * (1.0 - halfway_dot_eye).powi(5);
+
+        let specular =
+            fresnel * (distribution * geometry / (4.0 * light_dot_normal * eye_dot_normal));
+        // what isn't reflected goes into the surface, where metals absorb it
+        let diffuse = (white() - fresnel) * base_color * ((1.0 - self.metallic) / PI);
+
+        (diffuse + specular) * (light_dot_normal * PI)
+    }
+}
@@ -1,19 +1,51 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
 use crate::{
-    shape::Shape,
+    material::Material,
+    shape::{bounds::Bounds, Shape},
     tuple::{point, point_i, vector, Tuple},
     world::World,
 };
 
+mod export;
+mod mtl;
+
+pub use self::export::ObjExporter;
+pub use self::mtl::{parse_mtl, MtlLibrary};
+
 #[cfg(test)]
 mod obj_file_test {
+    use std::{
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
     use crate::{
-        obj_file::parse_obj,
-        shape::{Shape, ShapeType},
+        color::color,
+        shape::{Shape, ShapeType, TextureCoordinates},
         tuple::{point, point_i, vector, vector_i, Tuple},
         world::World,
     };
 
-    use super::add_obj_file;
+    use super::{
+        add_obj_file, add_obj_file_from_path, add_obj_file_with_materials, parse_obj_from,
+        ObjError, ObjErrorReason, ObjLoader, ObjProgress, ObjResult, ParsedObj,
+    };
+
+    fn parse_obj(content: &str) -> ObjResult<ParsedObj> {
+        parse_obj_with(content, false)
+    }
+
+    fn parse_obj_with(content: &str, lenient: bool) -> ObjResult<ParsedObj> {
+        parse_obj_from(content.as_bytes(), lenient, &|_| {})
+    }
 
     #[test]
     fn ignoring_unregognized_lines() {
@@ -26,7 +58,7 @@ mod obj_file_test {
 
             ";
 
-        let result = parse_obj(gibberish);
+        let result = parse_obj(gibberish).unwrap();
         assert!(result.groups.is_empty());
         assert_eq!(result.vertices.len(), 1);
     }
@@ -40,7 +72,7 @@ mod obj_file_test {
             v 1 1 0
             ";
 
-        let result = parse_obj(file);
+        let result = parse_obj(file).unwrap();
         assert_eq!(result.vertices[1], point_i(-1, 1, 0));
         assert_eq!(result.vertices[2], point(-1.0, 0.5, 0.0));
         assert_eq!(result.vertices[3], point_i(1, 0, 0));
@@ -49,14 +81,14 @@ mod obj_file_test {
 
     fn get_points(triangle: &Shape) -> (Tuple, Tuple, Tuple) {
         match triangle.shape_type {
-            ShapeType::Triangle(p1, p2, p3, _, _, _) => (p1, p2, p3),
-            ShapeType::SmoothTriangle(p1, p2, p3, _, _, _, _, _) => (p1, p2, p3),
+            ShapeType::Triangle(p1, p2, p3, _, _, _, _) => (p1, p2, p3),
+            ShapeType::SmoothTriangle(p1, p2, p3, _, _, _, _, _, _) => (p1, p2, p3),
             _ => panic!("{:?} was not a triangle", triangle),
         }
     }
 
     fn get_normals(triangle: &Shape) -> (Tuple, Tuple, Tuple) {
-        if let ShapeType::SmoothTriangle(_, _, _, _, _, n1, n2, n3) = triangle.shape_type {
+        if let ShapeType::SmoothTriangle(_, _, _, _, _, n1, n2, n3, _) = triangle.shape_type {
             return (n1, n2, n3);
         }
         panic!("{:?} was not a smooth triangle", triangle);
@@ -74,7 +106,7 @@ mod obj_file_test {
             f 1 3 4
             ";
 
-        let result = parse_obj(file);
+        let result = parse_obj(file).unwrap();
         let t1 = &result.groups.first().unwrap().items[0];
         let t2 = &result.groups.first().unwrap().items[1];
         let (t1p1, t1p2, t1p3) = get_points(t1);
@@ -101,7 +133,7 @@ mod obj_file_test {
             f 1 2 3 4 5
             ";
 
-        let result = parse_obj(file);
+        let result = parse_obj(file).unwrap();
         assert_eq!(result.vertices.len(), 5 + 1);
         assert_eq!(result.groups.first().unwrap().items.len(), 3);
         let t1 = &result.groups.first().unwrap().items[0];
@@ -138,7 +170,7 @@ mod obj_file_test {
 
     #[test]
     fn triangles_in_groups() {
-        let result = parse_obj(TRIANGLES_OBJ);
+        let result = parse_obj(TRIANGLES_OBJ).unwrap();
         assert_eq!(result.vertices.len(), 4 + 1);
         assert_eq!(result.groups.len(), 2);
         let g1 = &result.groups.get(0).unwrap();
@@ -164,7 +196,7 @@ mod obj_file_test {
     fn converting_obj_file_to_group() {
         let mut world = World::default();
 
-        add_obj_file(&mut world, TRIANGLES_OBJ);
+        add_obj_file(&mut world, TRIANGLES_OBJ).unwrap();
 
         let mut sub_group_ids = world.get_children(0).into_iter();
 
@@ -186,7 +218,7 @@ mod obj_file_test {
             vn 1 2 3
             ";
 
-        let result = parse_obj(file);
+        let result = parse_obj(file).unwrap();
 
         assert_eq!(result.normals[1], vector_i(0, 0, 1));
         assert_eq!(result.normals[2], vector(0.707, 0.0, -0.707));
@@ -208,7 +240,7 @@ mod obj_file_test {
             f 1/0/3 2/102/1 3/14/2
             ";
 
-        let result = parse_obj(file);
+        let result = parse_obj(file).unwrap();
 
         let g = &result.groups[0];
         let t1 = &g.items[0];
@@ -231,176 +263,931 @@ mod obj_file_test {
 
         assert_eq!(t1, t2);
     }
+
+    fn get_texture_coordinates(triangle: &Shape) -> Option<TextureCoordinates> {
+        match triangle.shape_type {
+            ShapeType::Triangle(_, _, _, _, _, _, uvs) => uvs,
+            ShapeType::SmoothTriangle(_, _, _, _, _, _, _, _, uvs) => uvs,
+            _ => panic!("{:?} was not a triangle", triangle),
+        }
+    }
+
+    #[test]
+    fn texture_coordinate_records() {
+        let file = "
+            vt 0 0
+            vt 0.5 1
+            vt 0.25 0.75 0
+            ";
+
+        let result = parse_obj(file).unwrap();
+
+        assert_eq!(result.texture_coordinates.len(), 3 + 1);
+        assert_eq!(result.texture_coordinates[1], (0.0, 0.0));
+        assert_eq!(result.texture_coordinates[2], (0.5, 1.0));
+        assert_eq!(result.texture_coordinates[3], (0.25, 0.75));
+    }
+
+    const TEXTURED_OBJ: &str = "
+        v 0 1 0
+        v -1 0 0
+        v 1 0 0
+
+        vt 0.5 1
+        vt 0 0
+        vt 1 0
+
+        vn -1 0 0
+        vn 1 0 0
+        vn 0 1 0
+        ";
+
+    #[test]
+    fn faces_with_texture_coordinates() {
+        let file = format!("{}\nf 1/1 2/2 3/3\nf 1/1/3 2/2/1 3/3/2\n", TEXTURED_OBJ);
+
+        let result = parse_obj(&file).unwrap();
+        let flat = &result.groups[0].items[0];
+        let smooth = &result.groups[0].items[1];
+        let uvs = Some([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)]);
+
+        assert!(matches!(flat.shape_type, ShapeType::Triangle(..)));
+        assert_eq!(get_texture_coordinates(flat), uvs);
+
+        assert_eq!(
+            get_normals(smooth),
+            (vector_i(0, 1, 0), vector_i(-1, 0, 0), vector_i(1, 0, 0))
+        );
+        assert_eq!(get_texture_coordinates(smooth), uvs);
+    }
+
+    #[test]
+    fn faces_with_normals_and_no_texture_coordinates() {
+        let file = format!("{}\nf 1//3 2//1 3//2\n", TEXTURED_OBJ);
+
+        let result = parse_obj(&file).unwrap();
+        let triangle = &result.groups[0].items[0];
+
+        assert_eq!(get_texture_coordinates(triangle), None);
+        assert_eq!(
+            get_normals(triangle),
+            (vector_i(0, 1, 0), vector_i(-1, 0, 0), vector_i(1, 0, 0))
+        );
+    }
+
+    #[test]
+    fn faces_mixing_corners_with_and_without_normals_are_flat() {
+        let file = format!("{}\nf 1//3 2 3/3\n", TEXTURED_OBJ);
+
+        let result = parse_obj(&file).unwrap();
+        let triangle = &result.groups[0].items[0];
+
+        assert!(matches!(triangle.shape_type, ShapeType::Triangle(..)));
+        assert_eq!(get_texture_coordinates(triangle), None);
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_record() {
+        let file = format!("{}\nf -3/-3/-1 -2/-2/-3 -1/-1/-2\n", TEXTURED_OBJ);
+
+        let result = parse_obj(&file).unwrap();
+        let triangle = &result.groups[0].items[0];
+
+        assert_eq!(
+            get_points(triangle),
+            (point_i(0, 1, 0), point_i(-1, 0, 0), point_i(1, 0, 0))
+        );
+        assert_eq!(
+            get_normals(triangle),
+            (vector_i(0, 1, 0), vector_i(-1, 0, 0), vector_i(1, 0, 0))
+        );
+        assert_eq!(
+            get_texture_coordinates(triangle),
+            Some([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)])
+        );
+    }
+
+    #[test]
+    fn negative_indices_are_relative_to_where_the_face_is() {
+        let file = "
+            v 0 1 0
+            v -1 0 0
+            v 1 0 0
+            f -3 -2 -1
+            v 5 5 5
+            f 1 2 -1
+            ";
+
+        let result = parse_obj(file).unwrap();
+        let (_, _, first_p3) = get_points(&result.groups[0].items[0]);
+        let (_, _, second_p3) = get_points(&result.groups[0].items[1]);
+
+        assert_eq!(first_p3, point_i(1, 0, 0));
+        assert_eq!(second_p3, point_i(5, 5, 5));
+    }
+
+    #[test]
+    fn textured_triangles_interpolate_their_texture_coordinates() {
+        let mut world = World::default();
+        let mesh = add_obj_file(&mut world, &format!("{}\nf 1/1 2/2 3/3\n", TEXTURED_OBJ)).unwrap();
+        let triangle = world.get_shape(world.get_children(world.get_children(mesh)[0])[0]);
+
+        let at = |x, y| triangle.texture_coordinates_at(point(x, y, 0.0)).unwrap();
+        let (u, v) = at(0.0, 1.0);
+        assert!((u - 0.5).abs() < 1e-9 && (v - 1.0).abs() < 1e-9);
+        let (u, v) = at(0.0, 0.0);
+        assert!((u - 0.5).abs() < 1e-9 && v.abs() < 1e-9);
+        let (u, v) = at(0.5, 0.25);
+        assert!((u - 0.75).abs() < 1e-9 && (v - 0.25).abs() < 1e-9);
+    }
+
+    const MATERIAL_OBJ: &str = "
+        mtllib materials.mtl
+        v 0 1 0
+        v -1 0 0
+        v 1 0 0
+        g first
+        usemtl red
+        f 1 2 3
+        usemtl glass
+        f 1 2 3
+        g second
+        f 1 2 3
+        ";
+
+    #[test]
+    fn material_libraries_take_the_rest_of_the_line() {
+        let result = parse_obj("mtllib American Pint Glass.mtl\nmtllib other.mtl\n").unwrap();
+
+        assert_eq!(
+            result.material_libraries,
+            vec!["American Pint Glass.mtl", "other.mtl"]
+        );
+    }
+
+    #[test]
+    fn usemtl_splits_a_group_by_material() {
+        let result = parse_obj(MATERIAL_OBJ).unwrap();
+
+        let groups = result
+            .groups
+            .iter()
+            .map(|g| (g.name.as_str(), g.material.as_deref(), g.items.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                ("first", Some("red"), 1),
+                ("first", Some("glass"), 1),
+                ("second", None, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn usemtl_before_any_group_goes_in_the_default_group() {
+        let result = parse_obj("v 0 1 0\nv -1 0 0\nv 1 0 0\nusemtl red\nf 1 2 3\n").unwrap();
+
+        assert_eq!(result.groups.len(), 1);
+        assert_eq!(result.groups[0].name, "Default");
+        assert_eq!(result.groups[0].material.as_deref(), Some("red"));
+    }
+
+    #[test]
+    fn faces_get_the_material_from_the_library() {
+        let mut world = World::default();
+        let mesh =
+            add_obj_file_with_materials(&mut world, MATERIAL_OBJ, Path::new("src/scenes/files"))
+                .unwrap();
+        let triangle_in =
+            |group| world.get_shape(world.get_children(world.get_children(mesh)[group])[0]);
+
+        assert_eq!(triangle_in(0).material.color, color(1.0, 0.0, 0.0));
+        assert_eq!(triangle_in(0).material.shininess, 50.0);
+        assert_eq!(triangle_in(1).material.refractive_index, 1.5);
+        assert!((triangle_in(1).material.transparency - 0.9).abs() < 1e-9);
+        assert_eq!(triangle_in(2).material, Default::default());
+    }
+
+    #[test]
+    fn missing_material_libraries_leave_the_default_material() {
+        let mut world = World::default();
+        let loaded = ObjLoader::new()
+            .with_materials(Path::new("nowhere"))
+            .load(&mut world, MATERIAL_OBJ)
+            .unwrap();
+        let triangle = world.get_shape(world.get_children(world.get_children(loaded.mesh)[0])[0]);
+
+        assert_eq!(triangle.material, Default::default());
+        assert_eq!(loaded.warnings.len(), 1);
+        assert!(loaded.warnings[0]
+            .to_string()
+            .starts_with("could not read material library nowhere/"));
+    }
+
+    #[test]
+    fn bad_numbers_are_errors() {
+        let result = parse_obj("v 0 0 0\n  v 1 one 0  \n");
+
+        assert_eq!(
+            result.err(),
+            Some(ObjError {
+                line: 2,
+                text: "v 1 one 0".to_owned(),
+                reason: ObjErrorReason::BadNumber("one".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn records_with_too_few_values_are_errors() {
+        let reason = |content| parse_obj(content).err().map(|e| e.reason);
+
+        assert_eq!(reason("v 1 2\n"), Some(ObjErrorReason::TooFewValues));
+        assert_eq!(reason("vn 0 1\n"), Some(ObjErrorReason::TooFewValues));
+        assert_eq!(reason("vt 0.5\n"), None);
+    }
+
+    #[test]
+    fn face_errors() {
+        let reason = |face| {
+            let file = format!("{}\n{}\n", TEXTURED_OBJ, face);
+            parse_obj(&file).err().map(|e| e.reason)
+        };
+
+        assert_eq!(reason("f 1 2 4"), Some(ObjErrorReason::IndexOutOfRange(4)));
+        assert_eq!(reason("f 0 1 2"), Some(ObjErrorReason::IndexOutOfRange(0)));
+        assert_eq!(
+            reason("f -4 1 2"),
+            Some(ObjErrorReason::IndexOutOfRange(-4))
+        );
+        assert_eq!(
+            reason("f 1/4 2 3"),
+            Some(ObjErrorReason::IndexOutOfRange(4))
+        );
+        assert_eq!(
+            reason("f 1//5 2 3"),
+            Some(ObjErrorReason::IndexOutOfRange(5))
+        );
+        assert_eq!(
+            reason("f 1 2 x"),
+            Some(ObjErrorReason::BadNumber("x".to_owned()))
+        );
+        assert_eq!(reason("f 1 2"), Some(ObjErrorReason::TooFewVertices(2)));
+        assert_eq!(reason("f 1 2 3"), None);
+    }
+
+    #[test]
+    fn errors_show_the_line_and_the_reason() {
+        let error = parse_obj("v 0 0 0\nf 1 1\n").err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "line 2: a face needs at least 3 vertices but this one has 2 in `f 1 1`"
+        );
+    }
+
+    #[test]
+    fn lenient_parsing_skips_bad_faces() {
+        let file = format!("{}\nf 1 2 3\nf 1 2 9\nf 3 2 1\n", TEXTURED_OBJ);
+
+        let result = parse_obj_with(&file, true).unwrap();
+
+        assert_eq!(result.groups[0].items.len(), 2);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(
+            result.warnings[0].reason,
+            ObjErrorReason::IndexOutOfRange(9)
+        );
+    }
+
+    #[test]
+    fn lenient_parsing_still_fails_on_bad_vertices() {
+        assert!(parse_obj_with("v 1 2 x\n", true).is_err());
+    }
+
+    #[test]
+    fn adding_a_bad_file_leaves_it_out_of_the_world() {
+        let mut world = World::default();
+
+        assert!(add_obj_file(&mut world, "v 0 0 0\nf 1 1 1 1 x\n").is_err());
+        assert!(world.objects.is_empty());
+
+        let loaded = ObjLoader::new()
+            .lenient()
+            .load(&mut world, "v 0 0 0\nf 1 1 1 1 x\n")
+            .unwrap();
+        assert!(world.get_children(loaded.mesh).is_empty());
+        assert_eq!(loaded.warnings.len(), 1);
+        assert_eq!(loaded.warnings[0].line, 2);
+    }
+
+    #[test]
+    fn loading_a_file_reads_its_material_libraries_next_to_it() {
+        let mut world = World::default();
+        let mesh = add_obj_file_from_path(&mut world, Path::new("src/scenes/files/materials.obj"))
+            .unwrap();
+        let triangle_in =
+            |group| world.get_shape(world.get_children(world.get_children(mesh)[group])[0]);
+
+        assert_eq!(triangle_in(0).material.color, color(1.0, 0.0, 0.0));
+        assert_eq!(triangle_in(1).material.refractive_index, 1.5);
+    }
+
+    #[test]
+    fn missing_files_are_errors() {
+        let error = add_obj_file_from_path(&mut World::default(), Path::new("nowhere.obj"))
+            .err()
+            .unwrap();
+
+        assert_eq!(error.line, 0);
+        assert!(matches!(error.reason, ObjErrorReason::Io(_)));
+    }
+
+    #[test]
+    fn lines_that_are_not_text_are_errors() {
+        let content: &[u8] = b"v 0 0 0\nv 1 \xff 0\n";
+        let error = ObjLoader::new()
+            .load_from(&mut World::default(), content)
+            .err()
+            .unwrap();
+
+        assert_eq!(error.line, 2);
+        assert!(matches!(error.reason, ObjErrorReason::Io(_)));
+    }
+
+    #[test]
+    fn loading_reports_progress() {
+        let mut content = "v 0 0 0\n".repeat(10_000);
+        content.push_str("v 1 0 0\nv 0 1 0\nf 1 10001 10002\n");
+
+        let reports = Arc::new(Mutex::new(vec![]));
+        let collected = reports.clone();
+        let mut world = World::default();
+        ObjLoader::new()
+            .with_progress(move |progress| collected.lock().unwrap().push(progress.clone()))
+            .load(&mut world, &content)
+            .unwrap();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            reports[0],
+            ObjProgress::Reading {
+                lines: 10_000,
+                vertices: 10_000,
+                triangles: 0
+            }
+        );
+        assert!(matches!(
+            reports[1],
+            ObjProgress::Done {
+                vertices: 10_002,
+                triangles: 1,
+                ..
+            }
+        ));
+    }
 }
 
 #[derive(Debug)]
 struct TriangleGroup {
     pub name: String,
     pub items: Vec<Shape>,
+    pub material: Option<String>,
 }
 
 impl TriangleGroup {
     pub fn default() -> TriangleGroup {
-        TriangleGroup {
-            name: "Default".to_owned(),
-            items: vec![],
-        }
+        TriangleGroup::with_name("Default")
     }
 
     pub fn with_name(name: &str) -> TriangleGroup {
         TriangleGroup {
             name: name.to_owned(),
             items: vec![],
+            material: None,
         }
     }
 }
 
 type Groups = Vec<TriangleGroup>;
 
+// groups with more triangles than this are split into a bounding volume hierarchy
+const MESH_GROUP_THRESHOLD: usize = 8;
+
 struct ParsedObj {
     pub vertices: Vec<Tuple>,
     pub groups: Groups,
     pub normals: Vec<Tuple>,
+    pub texture_coordinates: Vec<(f64, f64)>,
+    pub material_libraries: Vec<String>,
+    pub warnings: Vec<ObjError>,
+}
+
+impl ParsedObj {
+    fn triangle_count(&self) -> usize {
+        self.groups.iter().map(|group| group.items.len()).sum()
+    }
+}
+
+// one corner of a face, indices into the vertices, texture coordinates and normals
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FaceVertex {
+    vertex: usize,
+    texture: Option<usize>,
+    normal: Option<usize>,
+}
+
+/*
+ * A line of an OBJ file that could not be read, with its line number (from 1), the text
+ * of the line and what is wrong with it. Line 0 is the file itself, which could not be
+ * opened.
+ * */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjError {
+    pub line: usize,
+    pub text: String,
+    pub reason: ObjErrorReason,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjErrorReason {
+    BadNumber(String),
+    IndexOutOfRange(i64),
+    TooFewVertices(usize),
+    TooFewValues,
+    Io(String),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.reason),
+            line => write!(f, "line {}: {} in `{}`", line, self.reason, self.text),
+        }
+    }
+}
+
+impl fmt::Display for ObjErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjErrorReason::BadNumber(s) => write!(f, "`{}` is not a number", s),
+            ObjErrorReason::IndexOutOfRange(index) => write!(f, "index {} is out of range", index),
+            ObjErrorReason::TooFewVertices(count) => write!(
+                f,
+                "a face needs at least 3 vertices but this one has {}",
+                count
+            ),
+            ObjErrorReason::TooFewValues => write!(f, "too few values"),
+            ObjErrorReason::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+pub type ObjResult<T> = Result<T, ObjError>;
+
+// what `ObjLoader` added to the world, and what it had to skip on the way
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedObj {
+    pub mesh: usize,
+    pub warnings: Vec<ObjError>,
+}
+
+// how far loading has come, see `ObjLoader::with_progress`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjProgress {
+    Reading {
+        lines: usize,
+        vertices: usize,
+        triangles: usize,
+    },
+    Done {
+        vertices: usize,
+        triangles: usize,
+        bounds: Bounds,
+    },
+}
+
+// lines read between two `ObjProgress::Reading` reports
+const PROGRESS_INTERVAL: usize = 10_000;
+
+type ProgressFn = Arc<dyn Fn(&ObjProgress) + Send + Sync>;
+
+/*
+ * How to read an OBJ file into a world. By default any bad line fails the whole file. A
+ * lenient loader skips faces it can not read instead, and returns them as warnings in
+ * the `LoadedObj`. Bad `v`, `vn` and `vt` records still fail since skipping them would
+ * shift the indices of the rest.
+ * */
+#[derive(Clone, Default)]
+pub struct ObjLoader {
+    lenient: bool,
+    material_dir: Option<PathBuf>,
+    progress: Option<ProgressFn>,
+}
+
+impl fmt::Debug for ObjLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObjLoader")
+            .field("lenient", &self.lenient)
+            .field("material_dir", &self.material_dir)
+            .finish()
+    }
+}
+
+impl ObjLoader {
+    pub fn new() -> Self {
+        ObjLoader::default()
+    }
+
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    // reads the `mtllib` libraries relative to `base_dir`, see `add_obj_file_with_materials`
+    pub fn with_materials(mut self, base_dir: &Path) -> Self {
+        self.material_dir = Some(base_dir.to_owned());
+        self
+    }
+
+    /*
+     * Called every few thousand lines while the file is read, and once when the mesh is
+     * in the world. Nothing is reported without it.
+     * */
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(&ObjProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    pub fn load(&self, world: &mut World, content: &str) -> ObjResult<LoadedObj> {
+        self.load_from(world, content.as_bytes())
+    }
+
+    // reads the file line by line, without holding all of it in memory
+    pub fn load_from(&self, world: &mut World, reader: impl BufRead) -> ObjResult<LoadedObj> {
+        let report = |progress: &ObjProgress| {
+            if let Some(f) = &self.progress {
+                f(progress)
+            }
+        };
+
+        let mut parse_result = parse_obj_from(reader, self.lenient, &report)?;
+        let mut warnings = std::mem::take(&mut parse_result.warnings);
+
+        // a library that can't be read leaves its faces with the default material
+        let mut materials = HashMap::new();
+        if let Some(base_dir) = &self.material_dir {
+            for library in &parse_result.material_libraries {
+                let path = base_dir.join(library);
+                let library_warning = |message: String| ObjError {
+                    line: 0,
+                    text: library.clone(),
+                    reason: ObjErrorReason::Io(message),
+                };
+
+                match fs::read_to_string(&path) {
+                    Ok(mtl) => {
+                        let parsed = parse_mtl(&mtl, path.parent().unwrap_or(base_dir));
+                        materials.extend(parsed.materials);
+                        warnings.extend(parsed.warnings.into_iter().map(library_warning));
+                    }
+                    Err(e) => warnings.push(library_warning(format!(
+                        "could not read material library {}: {}",
+                        path.display(),
+                        e
+                    ))),
+                }
+            }
+        }
+
+        let vertices = parse_result.vertices.len() - 1;
+        let triangles = parse_result.triangle_count();
+        let mesh = add_parsed_obj(world, parse_result, &materials);
+        report(&ObjProgress::Done {
+            vertices,
+            triangles,
+            bounds: world.get_bounds(mesh),
+        });
+
+        Ok(LoadedObj { mesh, warnings })
+    }
+
+    /*
+     * Opens the file at `path` and streams it in. Unless `with_materials` says otherwise,
+     * `mtllib` paths are relative to the directory of the file.
+     * */
+    pub fn load_path(&self, world: &mut World, path: &Path) -> ObjResult<LoadedObj> {
+        let file = File::open(path).map_err(|e| ObjError {
+            line: 0,
+            text: String::new(),
+            reason: ObjErrorReason::Io(e.to_string()),
+        })?;
+
+        let mut loader = self.clone();
+        if loader.material_dir.is_none() {
+            loader.material_dir = Some(path.parent().unwrap_or(Path::new(".")).to_owned());
+        }
+
+        loader.load_from(world, BufReader::new(file))
+    }
 }
 
-pub fn add_obj_file(world: &mut World, content: &str) -> usize {
-    let parse_result = parse_obj(content);
+/*
+ * The `add_obj_file` functions give just the mesh, the warnings of a file they could read
+ * are dropped. Use an `ObjLoader` to get them.
+ * */
+pub fn add_obj_file(world: &mut World, content: &str) -> ObjResult<usize> {
+    ObjLoader::new()
+        .load(world, content)
+        .map(|loaded| loaded.mesh)
+}
 
+// reads the file and its material libraries, see `ObjLoader::load_path`
+pub fn add_obj_file_from_path(world: &mut World, path: &Path) -> ObjResult<usize> {
+    ObjLoader::new()
+        .load_path(world, path)
+        .map(|loaded| loaded.mesh)
+}
+
+/*
+ * Like `add_obj_file`, but also reads the `mtllib` libraries, relative to `base_dir`, and
+ * gives every face the material named by the `usemtl` before it. Faces without one, or
+ * with a name none of the libraries define, get the default material.
+ * */
+pub fn add_obj_file_with_materials(
+    world: &mut World,
+    content: &str,
+    base_dir: &Path,
+) -> ObjResult<usize> {
+    ObjLoader::new()
+        .with_materials(base_dir)
+        .load(world, content)
+        .map(|loaded| loaded.mesh)
+}
+
+fn add_parsed_obj(
+    world: &mut World,
+    parse_result: ParsedObj,
+    materials: &HashMap<String, Material>,
+) -> usize {
     let root_group = world.add_shape(Shape::group());
 
     for group in parse_result.groups {
+        let material = group
+            .material
+            .as_ref()
+            .and_then(|name| materials.get(name))
+            .cloned()
+            .unwrap_or_default();
+
         let group_id = world.add_shape(Shape::group());
         for triangle in group.items {
-            let triangle_id = world.add_shape(triangle);
+            let triangle_id = world.add_shape(triangle.with_material(material.clone()));
             world.add_shape_to_group(group_id, triangle_id);
         }
 
         world.add_shape_to_group(root_group, group_id);
     }
 
-    let bounds = world.calculate_bounds_for_group(root_group);
-    println!("parsed {} vertices", parse_result.vertices.len());
-    println!("bounds is {:#?}", bounds);
+    world.divide(root_group, MESH_GROUP_THRESHOLD);
 
     root_group
 }
 
-fn parse_obj(content: &str) -> ParsedObj {
-    let mut vertices = vec![point_i(6, 6, 6)]; // bogus point to make it 1 indexed
-    let mut normals = vec![point_i(6, 6, 6)]; // bogus point to make it 1 indexed
-    let mut groups = vec![];
+fn parse_obj_from(
+    reader: impl BufRead,
+    lenient: bool,
+    report: &dyn Fn(&ObjProgress),
+) -> ObjResult<ParsedObj> {
+    let mut parsed = ParsedObj {
+        vertices: vec![point_i(6, 6, 6)], // bogus point to make it 1 indexed
+        groups: vec![],
+        normals: vec![point_i(6, 6, 6)], // bogus point to make it 1 indexed
+        texture_coordinates: vec![(0.0, 0.0)], // bogus uv to make it 1 indexed
+        material_libraries: vec![],
+        warnings: vec![],
+    };
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| ObjError {
+            line: index + 1,
+            text: String::new(),
+            reason: ObjErrorReason::Io(e.to_string()),
+        })?;
+        let line = line.trim();
+
+        if index > 0 && index % PROGRESS_INTERVAL == 0 {
+            report(&ObjProgress::Reading {
+                lines: index,
+                vertices: parsed.vertices.len() - 1,
+                triangles: parsed.triangle_count(),
+            });
+        }
+
+        let error = |reason| ObjError {
+            line: index + 1,
+            text: line.to_owned(),
+            reason,
+        };
 
-    for line in content.lines().map(&str::trim) {
         if line.starts_with("v ") {
-            vertices.push(parse_vertex(line));
+            parsed.vertices.push(parse_vertex(line).map_err(error)?);
         }
 
         if line.starts_with("f ") {
-            if groups.is_empty() {
-                groups.push(TriangleGroup::default())
+            let face = parse_vertex_ids(line, &parsed).map(|ids| fan_triangulation(&parsed, ids));
+
+            match face {
+                Ok(mut face) => {
+                    if parsed.groups.is_empty() {
+                        parsed.groups.push(TriangleGroup::default())
+                    }
+                    parsed.groups.last_mut().unwrap().items.append(&mut face)
+                }
+                Err(reason) if lenient => parsed.warnings.push(error(reason)),
+                Err(reason) => return Err(error(reason)),
             }
-
-            let face = &mut fan_triangulation(&vertices, &normals, parse_vertex_ids(line));
-
-            groups.last_mut().unwrap().items.append(face)
         }
 
         if line.starts_with("g ") {
-            let name = line.split(" ").skip(1).next().unwrap();
-            groups.push(TriangleGroup::with_name(name))
+            let name = line.split_whitespace().nth(1).unwrap();
+            parsed.groups.push(TriangleGroup::with_name(name))
         }
 
         if line.starts_with("vn ") {
-            normals.push(parse_normal(line))
+            parsed.normals.push(parse_normal(line).map_err(error)?)
+        }
+
+        if line.starts_with("vt ") {
+            parsed
+                .texture_coordinates
+                .push(parse_texture_coordinate(line).map_err(error)?)
         }
-    }
 
-    ParsedObj {
-        vertices,
-        groups,
-        normals,
+        // library names may contain spaces, so they are the whole rest of the line
+        if let Some(library) = line.strip_prefix("mtllib ") {
+            parsed.material_libraries.push(library.trim().to_owned())
+        }
+
+        if let Some(name) = line.strip_prefix("usemtl ") {
+            use_material(&mut parsed.groups, name.trim())
+        }
     }
+
+    Ok(parsed)
+}
+
+// a material switch halfway through a group starts a new part of that group
+fn use_material(groups: &mut Groups, material: &str) {
+    let group = match groups.last_mut() {
+        Some(group) if group.items.is_empty() => group,
+        Some(group) => {
+            let name = group.name.clone();
+            groups.push(TriangleGroup::with_name(&name));
+            groups.last_mut().unwrap()
+        }
+        None => {
+            groups.push(TriangleGroup::default());
+            groups.last_mut().unwrap()
+        }
+    };
+
+    group.material = Some(material.to_owned())
 }
 
-fn fan_triangulation(
-    vertices: &Vec<Tuple>,
-    normals: &Vec<Tuple>,
-    vertex_ids: Vec<(usize, Option<usize>)>,
-) -> Vec<Shape> {
+/*
+ * Triangles get normals and texture coordinates when all three of their corners have
+ * them, and are flat and untextured otherwise. The indices of the face have already
+ * been checked by `parse_vertex_ids`.
+ * */
+fn fan_triangulation(parsed: &ParsedObj, face: Vec<FaceVertex>) -> Vec<Shape> {
     let mut triangles = vec![];
 
-    for index in 1..(vertex_ids.len() - 1) {
-        match (vertex_ids[0], vertex_ids[index], vertex_ids[index + 1]) {
-            ((p1, Some(n1)), (p2, Some(n2)), (p3, Some(n3))) => {
-                triangles.push(Shape::smooth_triangle(
-                    vertices[p1],
-                    vertices[p2],
-                    vertices[p3],
-                    normals[n1],
-                    normals[n2],
-                    normals[n3],
-                ))
-            }
-            ((p1, None), (p2, None), (p3, None)) => {
-                triangles.push(Shape::triangle(vertices[p1], vertices[p2], vertices[p3]))
-            }
-            _ => panic!("There was a mix of things"),
+    for index in 1..(face.len() - 1) {
+        let corners = [face[0], face[index], face[index + 1]];
+        let [p1, p2, p3] = corners.map(|corner| parsed.vertices[corner.vertex]);
+
+        let mut triangle = match corners.map(|corner| corner.normal) {
+            [Some(n1), Some(n2), Some(n3)] => Shape::smooth_triangle(
+                p1,
+                p2,
+                p3,
+                parsed.normals[n1],
+                parsed.normals[n2],
+                parsed.normals[n3],
+            ),
+            _ => Shape::triangle(p1, p2, p3),
+        };
+
+        if let [Some(t1), Some(t2), Some(t3)] = corners.map(|corner| corner.texture) {
+            triangle = triangle.with_texture_coordinates([
+                parsed.texture_coordinates[t1],
+                parsed.texture_coordinates[t2],
+                parsed.texture_coordinates[t3],
+            ]);
         }
+
+        triangles.push(triangle);
     }
 
     triangles
 }
 
-fn parse_vertex(line: &str) -> Tuple {
-    let mut iter = line.split(" ").skip(1).map(parse_float);
-    point(
-        iter.next().unwrap(),
-        iter.next().unwrap(),
-        iter.next().unwrap(),
-    )
+fn parse_vertex(line: &str) -> Result<Tuple, ObjErrorReason> {
+    let [x, y, z] = parse_floats(line)?;
+    Ok(point(x, y, z))
+}
+
+fn parse_normal(line: &str) -> Result<Tuple, ObjErrorReason> {
+    let [x, y, z] = parse_floats(line)?;
+    Ok(vector(x, y, z))
 }
 
-fn parse_normal(line: &str) -> Tuple {
-    let mut iter = line.split(" ").skip(1).map(parse_float);
+// `vt u v` with an optional w, which is ignored
+fn parse_texture_coordinate(line: &str) -> Result<(f64, f64), ObjErrorReason> {
+    let mut iter = line.split_whitespace().skip(1).map(parse_float);
+    let u = iter.next().ok_or(ObjErrorReason::TooFewValues)??;
+    let v = iter.next().unwrap_or(Ok(0.0))?;
 
-    vector(
-        iter.next().unwrap(),
-        iter.next().unwrap(),
-        iter.next().unwrap(),
-    )
+    Ok((u, v))
 }
 
-fn parse_vertex_ids(line: &str) -> Vec<(usize, Option<usize>)> {
-    line.split(" ")
+// the first `N` numbers after the keyword, anything after them is ignored
+fn parse_floats<const N: usize>(line: &str) -> Result<[f64; N], ObjErrorReason> {
+    let mut iter = line.split_whitespace().skip(1).map(parse_float);
+    let mut values = [0.0; N];
+    for value in values.iter_mut() {
+        *value = iter.next().ok_or(ObjErrorReason::TooFewValues)??;
+    }
+
+    Ok(values)
+}
+
+/*
+ * Corners are `v`, `v/vt`, `v//vn` or `v/vt/vn`. Negative indices count back from the
+ * last record read so far, -1 being the last one. Files without `vt` records sometimes
+ * still put numbers in the texture slot of faces, those are not checked.
+ * */
+fn parse_vertex_ids(line: &str, parsed: &ParsedObj) -> Result<Vec<FaceVertex>, ObjErrorReason> {
+    let has_textures = parsed.texture_coordinates.len() > 1;
+
+    let face = line
+        .split_whitespace()
         .skip(1)
         .map(|group| {
-            if group.contains("/") {
-                let mut iter = group.split("/");
+            let mut iter = group.split('/');
+            let v_id = iter.next().unwrap_or_default();
+            let texture_id = iter.next().filter(|id| has_textures && !id.is_empty());
+            let normal_id = iter.next().filter(|id| !id.is_empty());
+
+            Ok(FaceVertex {
+                vertex: parse_index(v_id, parsed.vertices.len())?,
+                texture: texture_id
+                    .map(|id| parse_index(id, parsed.texture_coordinates.len()))
+                    .transpose()?,
+                normal: normal_id
+                    .map(|id| parse_index(id, parsed.normals.len()))
+                    .transpose()?,
+            })
+        })
+        .collect::<Result<Vec<FaceVertex>, ObjErrorReason>>()?;
 
-                let v_id = iter.next().unwrap();
+    if face.len() < 3 {
+        return Err(ObjErrorReason::TooFewVertices(face.len()));
+    }
 
-                //
-                let v_normal_id = iter.skip(1).next().unwrap();
-                return (parse_integer(v_id), Some(parse_integer(v_normal_id)));
-            } else {
-                return (parse_integer(group), None);
-            }
-        })
-        .collect::<Vec<(usize, Option<usize>)>>()
+    Ok(face)
 }
 
-fn parse_integer(s: &str) -> usize {
-    if let Ok(i) = s.parse::<usize>() {
-        return i;
+// `count` includes the bogus first record, so -1 is `count - 1` and 0 is never valid
+fn parse_index(s: &str, count: usize) -> Result<usize, ObjErrorReason> {
+    let index = parse_integer(s)?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index
+    };
+
+    if resolved < 1 || resolved >= count as i64 {
+        return Err(ObjErrorReason::IndexOutOfRange(index));
     }
 
-    panic!("Could not parse {} to i32", s);
+    Ok(resolved as usize)
 }
 
-fn parse_float(s: &str) -> f64 {
-    if let Ok(f) = s.parse::<f64>() {
-        return f;
-    }
+fn parse_integer(s: &str) -> Result<i64, ObjErrorReason> {
+    s.parse::<i64>()
+        .map_err(|_| ObjErrorReason::BadNumber(s.to_owned()))
+}
 
-    panic!("Could not parse {} to f64", s)
+fn parse_float(s: &str) -> Result<f64, ObjErrorReason> {
+    s.parse::<f64>()
+        .map_err(|_| ObjErrorReason::BadNumber(s.to_owned()))
 }
@@ -0,0 +1,579 @@
+use std::f64::consts::{PI, TAU};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::matrix::Matrix;
+use crate::shape::{ShapeType, TextureCoordinates};
+use crate::tuple::{point, vector, Tuple};
+use crate::world::{ShapeId, World};
+
+#[cfg(test)]
+mod export_test {
+    use std::f64::consts::FRAC_PI_2;
+
+    use crate::{
+        matrix::Matrix,
+        obj_file::add_obj_file,
+        scenes::hexagon::hexagon_scene,
+        shape::Shape,
+        tuple::{point_i, vector, vector_i},
+        world::World,
+    };
+
+    use super::ObjExporter;
+
+    fn lines_starting_with(obj: &str, prefix: &str) -> usize {
+        obj.lines().filter(|line| line.starts_with(prefix)).count()
+    }
+
+    #[test]
+    fn exporting_an_empty_world() {
+        let obj = ObjExporter::new().write(&World::default());
+
+        assert_eq!(lines_starting_with(&obj, "v "), 0);
+        assert_eq!(lines_starting_with(&obj, "f "), 0);
+    }
+
+    #[test]
+    fn triangles_are_written_in_world_space() {
+        let mut world = World::default();
+        let group = world
+            .add_shape(Shape::group().with_transform(Matrix::identity().translate(1.0, 0.0, 0.0)));
+        let triangle = world.add_shape(Shape::triangle(
+            point_i(0, 1, 0),
+            point_i(-1, 0, 0),
+            point_i(1, 0, 0),
+        ));
+        world.add_shape_to_group(group, triangle);
+
+        let obj = ObjExporter::new().write(&world);
+
+        assert!(obj.contains("v 1 1 0\nv 0 0 0\nv 2 0 0\n"));
+        assert!(obj.contains("f 1 2 3\n"));
+    }
+
+    #[test]
+    fn smooth_triangles_keep_their_normals() {
+        let mut world = World::default();
+        world.add_shape(
+            Shape::smooth_triangle(
+                point_i(0, 1, 0),
+                point_i(-1, 0, 0),
+                point_i(1, 0, 0),
+                vector_i(0, 1, 0),
+                vector_i(-1, 0, 0),
+                vector_i(1, 0, 0),
+            )
+            .with_transform(Matrix::identity().rotate_z(FRAC_PI_2)),
+        );
+
+        let obj = ObjExporter::new().write(&world);
+
+        assert!(obj.contains("vn -1 0 0\nvn 0 -1 0\nvn 0 1 0\n"));
+        assert!(obj.contains("f 1//1 2//2 3//3\n"));
+    }
+
+    #[test]
+    fn textured_triangles_keep_their_texture_coordinates() {
+        let mut world = World::default();
+        world.add_shape(
+            Shape::triangle(point_i(0, 1, 0), point_i(-1, 0, 0), point_i(1, 0, 0))
+                .with_texture_coordinates([(0.5, 1.0), (0.0, 0.0), (1.0, 0.0)]),
+        );
+
+        let obj = ObjExporter::new().write(&world);
+
+        assert!(obj.contains("vt 0.5 1\nvt 0 0\nvt 1 0\n"));
+        assert!(obj.contains("f 1/1 2/2 3/3\n"));
+    }
+
+    #[test]
+    fn cubes_are_six_quads() {
+        let mut world = World::default();
+        world.add_shape(
+            Shape::cube_default().with_transform(Matrix::identity().scale(2.0, 2.0, 2.0)),
+        );
+
+        let obj = ObjExporter::new().write(&world);
+
+        assert_eq!(lines_starting_with(&obj, "v "), 24);
+        assert_eq!(lines_starting_with(&obj, "f "), 6);
+        assert!(obj.contains("v 2 2 2\n"));
+        assert!(obj.contains("v -2 -2 -2\n"));
+    }
+
+    #[test]
+    fn spheres_are_tessellated() {
+        let mut world = World::default();
+        world.add_shape(Shape::sphere_default());
+
+        let obj = ObjExporter::new().with_segments(8).write(&world);
+
+        // 8 segments around, 4 rings of faces from pole to pole
+        assert_eq!(lines_starting_with(&obj, "f "), 32);
+        for line in obj.lines().filter(|line| line.starts_with("v ")) {
+            let coordinates = line[2..]
+                .split(' ')
+                .map(|n| n.parse::<f64>().unwrap())
+                .collect::<Vec<f64>>();
+            let length = coordinates.iter().map(|c| c * c).sum::<f64>().sqrt();
+            assert!((length - 1.0).abs() < 1e-9);
+        }
+    }
+
+    // how many faces are wound counter clockwise around their normals, and how many not
+    fn windings(obj: &str) -> (usize, usize) {
+        let numbers = |line: &str| {
+            line.split(' ')
+                .skip(1)
+                .map(|n| n.parse::<f64>().unwrap())
+                .collect::<Vec<f64>>()
+        };
+        let records = |prefix: &str| {
+            obj.lines()
+                .filter(|line| line.starts_with(prefix))
+                .map(numbers)
+                .map(|n| vector(n[0], n[1], n[2]))
+                .collect::<Vec<_>>()
+        };
+        let (vertices, normals) = (records("v "), records("vn "));
+
+        obj.lines()
+            .filter(|line| line.starts_with("f "))
+            .fold((0, 0), |(agree, disagree), face| {
+                let corners = face
+                    .split(' ')
+                    .skip(1)
+                    .map(|corner| {
+                        let indices = corner.split('/').collect::<Vec<&str>>();
+                        let index = |i: usize| indices[i].parse::<usize>().unwrap() - 1;
+                        (vertices[index(0)], normals[index(2)])
+                    })
+                    .collect::<Vec<_>>();
+                let winding = (corners[1].0 - corners[0].0).cross(&(corners[2].0 - corners[0].0));
+                let normal = corners
+                    .iter()
+                    .fold(vector(0.0, 0.0, 0.0), |sum, c| sum + c.1);
+
+                match winding.dot(&normal) > 0.0 {
+                    true => (agree + 1, disagree),
+                    false => (agree, disagree + 1),
+                }
+            })
+    }
+
+    #[test]
+    fn faces_are_wound_counter_clockwise_around_their_normals() {
+        for shape in [
+            Shape::sphere_default(),
+            Shape::cube_default(),
+            Shape::cylinder(0.0, 1.0, true),
+        ] {
+            let mut world = World::default();
+            world.add_shape(shape);
+
+            let obj = ObjExporter::new().with_segments(8).write(&world);
+
+            assert_eq!(windings(&obj).1, 0, "{}", obj);
+        }
+    }
+
+    #[test]
+    fn closed_cylinders_have_caps() {
+        let mut world = World::default();
+        world.add_shape(Shape::cylinder(0.0, 1.0, false));
+        let open = ObjExporter::new().with_segments(8).write(&world);
+
+        let mut world = World::default();
+        world.add_shape(Shape::cylinder(0.0, 1.0, true));
+        let closed = ObjExporter::new().with_segments(8).write(&world);
+
+        assert_eq!(lines_starting_with(&open, "f "), 8);
+        assert_eq!(lines_starting_with(&closed, "f "), 24);
+    }
+
+    #[test]
+    fn double_cones_meet_at_the_tip() {
+        let mut world = World::default();
+        world.add_shape(Shape::cone(-1.0, 1.0, false));
+
+        let obj = ObjExporter::new().with_segments(8).write(&world);
+
+        assert_eq!(lines_starting_with(&obj, "f "), 16);
+        assert!(obj.contains("v 0 0 0\n"));
+    }
+
+    #[test]
+    fn infinite_shapes_are_skipped() {
+        let mut world = World::default();
+        world.add_shape(Shape::plane_default());
+        world.add_shape(Shape::cylinder(f64::NEG_INFINITY, f64::INFINITY, false));
+
+        let obj = ObjExporter::new().write(&world);
+
+        assert_eq!(lines_starting_with(&obj, "f "), 0);
+        assert_eq!(lines_starting_with(&obj, "# skipped"), 2);
+    }
+
+    #[test]
+    fn exported_scenes_can_be_read_back() {
+        let world = hexagon_scene(World::default());
+        let obj = ObjExporter::new().write(&world);
+
+        let mut imported = World::default();
+        let mesh = add_obj_file(&mut imported, &obj).unwrap();
+        let bounds = imported.get_bounds(mesh);
+
+        // the corners are spheres of radius 0.25 one unit from the middle
+        assert!(bounds.min.x > -1.3 && bounds.min.x < -1.1);
+        assert!(bounds.max.x > 1.1 && bounds.max.x < 1.3);
+        assert!(bounds.min.y > -0.5 && bounds.max.y < 0.5);
+        assert!(bounds.min.z < -1.1 && bounds.max.z > 1.1);
+    }
+}
+
+/*
+ * Writes the shapes of a world as one OBJ file, everything in world space, so scenes can
+ * be looked at in other programs. Spheres, cylinders, cones and cubes are tessellated,
+ * `segments` being the number of faces around them. Planes and shapes without ends
+ * can't be written and are left out with a comment. CSG can't be either, both of its
+ * sides are written as they are.
+ * */
+#[derive(Debug, Clone)]
+pub struct ObjExporter {
+    segments: usize,
+}
+
+impl Default for ObjExporter {
+    fn default() -> Self {
+        ObjExporter { segments: 24 }
+    }
+}
+
+impl ObjExporter {
+    pub fn new() -> Self {
+        ObjExporter::default()
+    }
+
+    pub fn with_segments(mut self, segments: usize) -> Self {
+        self.segments = segments.max(3);
+        self
+    }
+
+    pub fn write(&self, world: &World) -> String {
+        let mut writer = ObjWriter::default();
+        writeln!(writer.out, "# {} shapes", world.objects.len()).unwrap();
+
+        for (id, world_shape) in world.objects.iter().enumerate() {
+            if world_shape.shape.parent.is_none() {
+                self.write_shape(&mut writer, world, id, &Matrix::identity());
+            }
+        }
+
+        writer.out
+    }
+
+    pub fn save(&self, world: &World, path: &Path) -> io::Result<()> {
+        fs::write(path, self.write(world))
+    }
+
+    fn write_shape(&self, writer: &mut ObjWriter, world: &World, id: ShapeId, parent: &Matrix) {
+        let shape = world.get_shape(id);
+        let to_world = parent * &shape.transformation;
+        let transform = Transform::new(to_world);
+
+        match &shape.shape_type {
+            ShapeType::Group(children, _) => {
+                for child in children {
+                    self.write_shape(writer, world, *child, &to_world);
+                }
+            }
+            ShapeType::CSG(_, left, right) => {
+                self.write_shape(writer, world, *left, &to_world);
+                self.write_shape(writer, world, *right, &to_world);
+            }
+            ShapeType::Triangle(p1, p2, p3, _, _, _, uvs) => {
+                writer.group(&format!("group_{}", shape.parent.unwrap_or(id)));
+                writer.triangle(&transform, [*p1, *p2, *p3], None, uvs);
+            }
+            ShapeType::SmoothTriangle(p1, p2, p3, _, _, n1, n2, n3, uvs) => {
+                writer.group(&format!("group_{}", shape.parent.unwrap_or(id)));
+                writer.triangle(&transform, [*p1, *p2, *p3], Some([*n1, *n2, *n3]), uvs);
+            }
+            ShapeType::Sphere => {
+                writer.group(&format!("sphere_{}", id));
+                self.write_sphere(writer, &transform);
+            }
+            ShapeType::Cube => {
+                writer.group(&format!("cube_{}", id));
+                write_cube(writer, &transform);
+            }
+            ShapeType::Cylinder(min, max, _) | ShapeType::Cone(min, max, _)
+                if min.is_infinite() || max.is_infinite() =>
+            {
+                writeln!(writer.out, "# skipped shape {}, it has no ends", id).unwrap();
+            }
+            ShapeType::Cylinder(min, max, closed) => {
+                writer.group(&format!("cylinder_{}", id));
+                self.write_lathe(writer, &transform, &[*min, *max], *closed, |_| 1.0);
+            }
+            ShapeType::Cone(min, max, closed) => {
+                writer.group(&format!("cone_{}", id));
+                // a cone through y = 0 is two cones meeting at the tip
+                let heights = if *min < 0.0 && *max > 0.0 {
+                    vec![*min, 0.0, *max]
+                } else {
+                    vec![*min, *max]
+                };
+                self.write_lathe(writer, &transform, &heights, *closed, f64::abs);
+            }
+            ShapeType::Plane => {
+                writeln!(writer.out, "# skipped plane {}, it has no ends", id).unwrap();
+            }
+        }
+    }
+
+    // rings of latitude from pole to pole, the pole rings collapse into triangles
+    fn write_sphere(&self, writer: &mut ObjWriter, transform: &Transform) {
+        let rings = (self.segments / 2).max(2);
+
+        let corner = |ring: usize, segment: usize| {
+            let theta = PI * ring as f64 / rings as f64;
+            let phi = TAU * segment as f64 / self.segments as f64;
+            vector(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            )
+        };
+
+        for ring in 0..rings {
+            for segment in 0..self.segments {
+                // counter clockwise seen from outside, like the other shapes
+                let mut normals = vec![
+                    corner(ring, segment),
+                    corner(ring, segment + 1),
+                    corner(ring + 1, segment + 1),
+                    corner(ring + 1, segment),
+                ];
+                if ring == 0 {
+                    normals.remove(1);
+                } else if ring == rings - 1 {
+                    normals.remove(2);
+                }
+
+                let points = normals
+                    .iter()
+                    .map(|n| point(n.x, n.y, n.z))
+                    .collect::<Vec<Tuple>>();
+                writer.face(transform, &points, Some(&normals));
+            }
+        }
+    }
+
+    /*
+     * A surface turned around the y axis, with `radius_at` the distance from the axis at
+     * each of the `heights`. The sides between two heights are quads, the caps fans.
+     * */
+    fn write_lathe(
+        &self,
+        writer: &mut ObjWriter,
+        transform: &Transform,
+        heights: &[f64],
+        closed: bool,
+        radius_at: impl Fn(f64) -> f64,
+    ) {
+        let angle = |segment: usize| TAU * segment as f64 / self.segments as f64;
+        let at = |y: f64, segment: usize| {
+            let radius = radius_at(y);
+            point(
+                radius * angle(segment).cos(),
+                y,
+                radius * angle(segment).sin(),
+            )
+        };
+        // the slope of the side between two heights, flat for a cylinder
+        let normal = |bottom: f64, top: f64, segment: usize| {
+            let slope = (radius_at(bottom) - radius_at(top)) / (top - bottom);
+            vector(angle(segment).cos(), slope, angle(segment).sin())
+        };
+
+        for pair in heights.windows(2) {
+            let (bottom, top) = (pair[0], pair[1]);
+            for segment in 0..self.segments {
+                let mut corners = vec![
+                    (at(bottom, segment), normal(bottom, top, segment)),
+                    (at(top, segment), normal(bottom, top, segment)),
+                    (at(top, segment + 1), normal(bottom, top, segment + 1)),
+                    (at(bottom, segment + 1), normal(bottom, top, segment + 1)),
+                ];
+                // at the tip of a cone the side is a triangle
+                if radius_at(bottom) == 0.0 {
+                    corners.remove(3);
+                } else if radius_at(top) == 0.0 {
+                    corners.remove(2);
+                }
+
+                let (points, normals): (Vec<Tuple>, Vec<Tuple>) = corners.into_iter().unzip();
+                writer.face(transform, &points, Some(&normals));
+            }
+        }
+
+        if !closed {
+            return;
+        }
+
+        let ends = [(heights[0], -1.0), (heights[heights.len() - 1], 1.0)];
+        for (y, direction) in ends {
+            if radius_at(y) == 0.0 {
+                continue;
+            }
+            for segment in 0..self.segments {
+                let mut points = [point(0.0, y, 0.0), at(y, segment), at(y, segment + 1)];
+                if direction > 0.0 {
+                    points.swap(1, 2);
+                }
+                let normals = [vector(0.0, direction, 0.0); 3];
+                writer.face(transform, &points, Some(&normals));
+            }
+        }
+    }
+}
+
+fn write_cube(writer: &mut ObjWriter, transform: &Transform) {
+    for axis in 0..3 {
+        for side in [-1.0, 1.0] {
+            // the corners of the face, going around it
+            let points = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(a, b)| {
+                let mut coordinates = [0.0; 3];
+                coordinates[axis] = side;
+                coordinates[(axis + 1) % 3] = a * side;
+                coordinates[(axis + 2) % 3] = b;
+                point(coordinates[0], coordinates[1], coordinates[2])
+            });
+            let mut normal = [0.0; 3];
+            normal[axis] = side;
+            let normals = [vector(normal[0], normal[1], normal[2]); 4];
+
+            writer.face(transform, &points, Some(&normals));
+        }
+    }
+}
+
+// from object to world space, normals go through the transposed inverse
+struct Transform {
+    to_world: Matrix,
+    normal_to_world: Matrix,
+}
+
+impl Transform {
+    fn new(to_world: Matrix) -> Self {
+        let normal_to_world = to_world.inverse().transpose();
+        Transform {
+            to_world,
+            normal_to_world,
+        }
+    }
+
+    fn point(&self, p: &Tuple) -> Tuple {
+        p * &self.to_world
+    }
+
+    fn normal(&self, n: &Tuple) -> Tuple {
+        let mut normal = n * &self.normal_to_world;
+        normal.w = 0.0;
+        normal.normalize()
+    }
+}
+
+// the OBJ text so far, and how many of each record it has to number the next one
+#[derive(Default)]
+struct ObjWriter {
+    out: String,
+    group: Option<String>,
+    vertices: usize,
+    normals: usize,
+    texture_coordinates: usize,
+}
+
+impl ObjWriter {
+    fn group(&mut self, name: &str) {
+        if self.group.as_deref() != Some(name) {
+            writeln!(self.out, "g {}", name).unwrap();
+            self.group = Some(name.to_owned());
+        }
+    }
+
+    fn triangle(
+        &mut self,
+        transform: &Transform,
+        points: [Tuple; 3],
+        normals: Option<[Tuple; 3]>,
+        uvs: &Option<TextureCoordinates>,
+    ) {
+        let first_uv = self.texture_coordinates + 1;
+        if let Some(uvs) = uvs {
+            for (u, v) in uvs {
+                writeln!(self.out, "vt {} {}", u, v).unwrap();
+            }
+            self.texture_coordinates += 3;
+        }
+
+        self.face_with(transform, &points, normals.as_ref().map(|n| &n[..]), |i| {
+            uvs.map(|_| first_uv + i)
+        });
+    }
+
+    fn face(&mut self, transform: &Transform, points: &[Tuple], normals: Option<&[Tuple]>) {
+        self.face_with(transform, points, normals, |_| None)
+    }
+
+    fn face_with(
+        &mut self,
+        transform: &Transform,
+        points: &[Tuple],
+        normals: Option<&[Tuple]>,
+        uv_at: impl Fn(usize) -> Option<usize>,
+    ) {
+        let first_vertex = self.vertices + 1;
+        for p in points {
+            let p = transform.point(p);
+            writeln!(self.out, "v {} {} {}", clean(p.x), clean(p.y), clean(p.z)).unwrap();
+        }
+        self.vertices += points.len();
+
+        let first_normal = self.normals + 1;
+        if let Some(normals) = normals {
+            for n in normals {
+                let n = transform.normal(n);
+                writeln!(self.out, "vn {} {} {}", clean(n.x), clean(n.y), clean(n.z)).unwrap();
+            }
+            self.normals += normals.len();
+        }
+
+        let corners = (0..points.len())
+            .map(|i| {
+                let vertex = first_vertex + i;
+                match (uv_at(i), normals.is_some()) {
+                    (None, false) => vertex.to_string(),
+                    (Some(uv), false) => format!("{}/{}", vertex, uv),
+                    (None, true) => format!("{}//{}", vertex, first_normal + i),
+                    (Some(uv), true) => format!("{}/{}/{}", vertex, uv, first_normal + i),
+                }
+            })
+            .collect::<Vec<String>>();
+        writeln!(self.out, "f {}", corners.join(" ")).unwrap();
+    }
+}
+
+// rounds away the noise of the transformations so that 1e-17 is written as 0
+fn clean(n: f64) -> f64 {
+    let rounded = (n * 1e9).round() / 1e9;
+    if rounded == 0.0 {
+        0.0
+    } else {
+        rounded
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::canvas::Canvas;
+use crate::color::color;
+use crate::material::{Material, Microfacet};
+use crate::pattern::{Pattern, TextureFilter, UvMapping};
+
+#[cfg(test)]
+mod mtl_test {
+    use std::path::Path;
+
+    use crate::color::color;
+    use crate::material::{Material, Microfacet};
+
+    use super::parse_mtl;
+
+    #[test]
+    fn reading_materials() {
+        let content = "
+            # two materials
+            newmtl red
+            Kd 1 0 0
+            Ka 0.2 0.1 0.0
+            Ks 0.5 0.5 0.5
+            Ns 50
+
+            newmtl glass
+            Kd 1 1 1
+            Ni 1.5
+            d 0.25
+            ";
+
+        let materials = parse_mtl(content, Path::new(".")).materials;
+
+        let red = &materials["red"];
+        assert_eq!(red.color, color(1.0, 0.0, 0.0));
+        assert!((red.ambient - 0.1).abs() < 1e-9);
+        assert_eq!(red.specular, 0.5);
+        assert_eq!(red.shininess, 50.0);
+
+        let glass = &materials["glass"];
+        assert_eq!(glass.refractive_index, 1.5);
+        assert_eq!(glass.transparency, 0.75);
+    }
+
+    #[test]
+    fn tr_is_transparency() {
+        let materials = parse_mtl("newmtl a\nTr 0.4\n", Path::new(".")).materials;
+
+        assert_eq!(materials["a"].transparency, 0.4);
+    }
+
+    #[test]
+    fn pbr_properties_make_a_microfacet_material() {
+        let materials =
+            parse_mtl("newmtl gold\nKd 1 0.8 0.3\nPm 1\nPr 0.2\n", Path::new(".")).materials;
+
+        assert_eq!(
+            materials["gold"].microfacet,
+            Some(Microfacet::new(1.0, 0.2))
+        );
+    }
+
+    #[test]
+    fn unset_properties_keep_their_defaults() {
+        let materials = parse_mtl("newmtl plain\nKd 0 1 0\n", Path::new(".")).materials;
+
+        assert_eq!(
+            materials["plain"],
+            Material::from_color(color(0.0, 1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn diffuse_maps_are_loaded_relative_to_the_library() {
+        let content = "newmtl textured\nmap_Kd -s 1 1 1 checkers.ppm\n";
+        let materials = parse_mtl(content, Path::new("src/scenes/files")).materials;
+
+        let pattern = materials["textured"].pattern.as_ref().unwrap();
+        assert_eq!(
+            pattern.color_at(crate::tuple::point(0.0, 1.0, 0.0)),
+            color(1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn missing_diffuse_maps_are_skipped_with_a_warning() {
+        let library = parse_mtl("newmtl a\nmap_Kd nope.ppm\n", Path::new("."));
+
+        assert_eq!(library.materials["a"].pattern, None);
+        assert_eq!(library.warnings.len(), 1);
+        assert!(library.warnings[0].starts_with("could not load texture ./nope.ppm"));
+    }
+}
+
+// the materials of a library by name, and why any of their textures are missing
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlLibrary {
+    pub materials: HashMap<String, Material>,
+    pub warnings: Vec<String>,
+}
+
+/*
+ * Reads a material library. Kd is the color, Ka and Ks set ambient and specular to the
+ * average of their channels, Ns is the shininess, Ni the refractive index and d (opacity)
+ * or Tr (transparency) the transparency. map_Kd is an image, a PPM relative to the
+ * library, wrapped on with the texture coordinates of the mesh. The PBR extension's Pm
+ * (metallic) and Pr (roughness) make the material a `Microfacet` one. A texture that
+ * can't be loaded leaves the material without it, with a warning.
+ * */
+pub fn parse_mtl(content: &str, base_dir: &Path) -> MtlLibrary {
+    let mut materials = HashMap::new();
+    let mut warnings = vec![];
+    let mut current: Option<(String, Material)> = None;
+
+    for line in content.lines().map(&str::trim) {
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or_default();
+        let values = words.collect::<Vec<&str>>();
+
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((values.join(" "), Material::default()));
+            continue;
+        }
+
+        let Some((_, material)) = current.as_mut() else {
+            continue;
+        };
+        let numbers = values
+            .iter()
+            .filter_map(|value| value.parse::<f64>().ok())
+            .collect::<Vec<f64>>();
+        let average = numbers.iter().sum::<f64>() / numbers.len().max(1) as f64;
+
+        match (keyword, numbers.as_slice()) {
+            ("Kd", [r, g, b, ..]) => material.color = color(*r, *g, *b),
+            ("Ka", [_, ..]) => material.ambient = average,
+            ("Ks", [_, ..]) => material.specular = average,
+            ("Ns", [shininess, ..]) => material.shininess = *shininess,
+            ("Ni", [refractive_index, ..]) => material.refractive_index = *refractive_index,
+            ("d", [opacity, ..]) => material.transparency = 1.0 - opacity,
+            ("Tr", [transparency, ..]) => material.transparency = *transparency,
+            ("Pm", [metallic, ..]) => {
+                let roughness = material.microfacet.as_ref().map_or(0.5, |m| m.roughness);
+                material.microfacet = Some(Microfacet::new(*metallic, roughness));
+            }
+            ("Pr", [roughness, ..]) => {
+                let metallic = material.microfacet.as_ref().map_or(0.0, |m| m.metallic);
+                material.microfacet = Some(Microfacet::new(metallic, *roughness));
+            }
+            ("map_Kd", _) => {
+                // options like `-s 1 1 1` come first, the file name is last
+                if let Some(file) = values.last() {
+                    match load_texture(&base_dir.join(file)) {
+                        Ok(pattern) => material.pattern = Some(pattern),
+                        Err(e) => warnings.push(e),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+
+    MtlLibrary {
+        materials,
+        warnings,
+    }
+}
+
+fn load_texture(path: &Path) -> Result<Pattern, String> {
+    fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| Canvas::from_ppm(&bytes).map_err(|e| e.to_string()))
+        .map(|texture| Pattern::image(texture, UvMapping::Mesh, TextureFilter::Bilinear))
+        .map_err(|e| format!("could not load texture {}: {}", path.display(), e))
+}
@@ -0,0 +1,428 @@
+use std::f64::consts::TAU;
+
+use rand::Rng;
+
+use crate::color::{black, white, Color};
+use crate::intersection::Intersections;
+use crate::rays::Ray;
+use crate::tuple::{vector, Tuple};
+use crate::world::{glossy_direction, ShapeId, World};
+
+#[cfg(test)]
+mod path_tracing_test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::{
+        color::{black, color, white},
+        material::Material,
+        matrix::Matrix,
+        rays::Ray,
+        shape::Shape,
+        tuple::{point, vector},
+        world::World,
+    };
+
+    use super::cosine_direction;
+    use crate::world::glossy_direction;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(7)
+    }
+
+    fn glowing(emissive: crate::color::Color) -> Material {
+        Material {
+            diffuse: 0.0,
+            specular: 0.0,
+            ambient: 0.0,
+            emissive,
+            ..Material::default()
+        }
+    }
+
+    #[test]
+    fn rays_that_miss_everything_are_black() {
+        let world = World::with_lights(vec![]);
+        let ray = Ray::with(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(world.path_color(&ray, 5, &mut rng()), black());
+    }
+
+    #[test]
+    fn emissive_surfaces_are_seen_directly() {
+        let world = World::with_lights(vec![]).with_objects(vec![
+            Shape::sphere_default().with_material(glowing(color(1.0, 0.5, 0.0)))
+        ]);
+        let ray = Ray::with(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(world.path_color(&ray, 5, &mut rng()), color(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn ambient_is_not_used() {
+        let world =
+            World::default().with_objects(vec![Shape::sphere_default().with_material(Material {
+                ambient: 1.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                ..Material::default()
+            })]);
+        let ray = Ray::with(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(world.path_color(&ray, 5, &mut rng()), black());
+    }
+
+    #[test]
+    fn diffuse_surfaces_are_lit_by_what_is_around_them() {
+        // every bounce off the inner sphere ends on the glowing one around it
+        let inner = Shape::sphere_default().with_material(Material {
+            diffuse: 0.5,
+            specular: 0.0,
+            ambient: 0.0,
+            ..Material::default()
+        });
+        let outer = Shape::sphere_default()
+            .with_transform(Matrix::identity().scale(10.0, 10.0, 10.0))
+            .with_material(glowing(white()));
+        let world = World::with_lights(vec![]).with_objects(vec![inner, outer]);
+        let ray = Ray::with(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let mut rng = rng();
+        for _ in 0..10 {
+            assert_eq!(world.path_color(&ray, 5, &mut rng), color(0.5, 0.5, 0.5));
+        }
+    }
+
+    #[test]
+    fn light_bounces_off_mirrors() {
+        let mirror = Shape::plane_default()
+            .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0))
+            .with_material(Material {
+                reflective: 1.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                ambient: 0.0,
+                ..Material::default()
+            });
+        let lamp = Shape::sphere_default()
+            .with_transform(Matrix::identity().translate(0.0, 1.0, 4.0))
+            .with_material(glowing(color(0.0, 0.0, 1.0)));
+        let world = World::with_lights(vec![]).with_objects(vec![mirror, lamp]);
+        let ray = Ray::with(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 1.0).normalize());
+
+        assert_eq!(world.path_color(&ray, 5, &mut rng()), color(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn metals_mirror_their_color() {
+        let gold = Shape::plane_default()
+            .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0))
+            .with_material(Material::microfacet(color(1.0, 0.8, 0.3), 1.0, 0.0));
+        let lamp = Shape::sphere_default()
+            .with_transform(Matrix::identity().translate(0.0, 1.0, 4.0))
+            .with_material(glowing(white()));
+        let world = World::with_lights(vec![]).with_objects(vec![gold, lamp]);
+        let ray = Ray::with(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 1.0).normalize());
+
+        // a little more than the base color, Fresnel at 45 degrees
+        let mut rng = rng();
+        for _ in 0..10 {
+            assert_eq!(
+                world.path_color(&ray, 5, &mut rng),
+                color(1.0, 0.80043, 0.30151)
+            );
+        }
+    }
+
+    #[test]
+    fn paths_through_glass_lose_what_it_absorbs() {
+        let ball = Shape::sphere_default()
+            .with_transform(Matrix::identity().translate(0.0, 0.0, 3.0))
+            .with_material(Material {
+                transparency: 1.0,
+                absorption: color(0.5, 0.0, 0.0),
+                ..glowing(black())
+            });
+        let lamp = Shape::sphere_default()
+            .with_transform(Matrix::identity().translate(0.0, 0.0, 10.0))
+            .with_material(glowing(white()));
+        let world = World::with_lights(vec![]).with_objects(vec![ball, lamp]);
+        let ray = Ray::with(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            world.path_color(&ray, 5, &mut rng()),
+            color(0.36788, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn paths_bouncing_inside_an_object_keep_losing_what_it_absorbs() {
+        let ball = Shape::sphere_default()
+            .with_transform(Matrix::identity().scale(5.0, 5.0, 5.0))
+            .with_material(Material {
+                reflective: 1.0,
+                absorption: color(0.5, 0.0, 0.0),
+                ..glowing(black())
+            });
+        let lamp = Shape::sphere_default()
+            .with_transform(
+                Matrix::identity()
+                    .scale(0.5, 0.5, 0.5)
+                    .translate(0.0, 0.0, -2.0),
+            )
+            .with_material(glowing(white()));
+        let world = World::with_lights(vec![]).with_objects(vec![ball, lamp]);
+        let ray = Ray::with(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+
+        // mirrored at z = 5 and absorbed all the way back to the lamp at z = -1.5
+        assert_eq!(
+            world.path_color(&ray, 5, &mut rng()),
+            color(0.03877, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn paths_are_repeatable_with_the_same_seed() {
+        let world = World::test_world();
+        let ray = Ray::with(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            world.path_color(&ray, 5, &mut rng()),
+            world.path_color(&ray, 5, &mut rng())
+        );
+    }
+
+    #[test]
+    fn rough_mirrors_blur_what_they_reflect() {
+        let mirror = |roughness| {
+            Shape::plane_default()
+                .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0))
+                .with_material(Material {
+                    reflective: 1.0,
+                    diffuse: 0.0,
+                    specular: 0.0,
+                    ambient: 0.0,
+                    roughness,
+                    ..Material::default()
+                })
+        };
+        let lamp = Shape::sphere_default()
+            .with_transform(Matrix::identity().translate(0.0, 1.0, 4.0))
+            .with_material(glowing(white()));
+        let world = World::with_lights(vec![]).with_objects(vec![mirror(0.6), lamp]);
+        let ray = Ray::with(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 1.0).normalize());
+
+        let mut rng = rng();
+        let hits = (0..200)
+            .filter(|_| world.path_color(&ray, 5, &mut rng) == white())
+            .count();
+
+        assert!(hits > 20 && hits < 180);
+    }
+
+    #[test]
+    fn glossy_bounces_stay_on_their_side_of_the_surface() {
+        let normal = vector(0.0, 1.0, 0.0);
+        let reflected = vector(0.0, 0.1, 1.0).normalize();
+        let refracted = vector(0.0, -0.1, 1.0).normalize();
+        let mut rng = rng();
+
+        for _ in 0..100 {
+            assert!(glossy_direction(reflected, normal, 1.0, &mut rng).y > 0.0);
+            assert!(glossy_direction(refracted, normal, 1.0, &mut rng).y < 0.0);
+
+            let close = glossy_direction(reflected, normal, 0.1, &mut rng);
+            assert!(close.dot(&reflected) > 0.99);
+        }
+    }
+
+    #[test]
+    fn diffuse_bounces_stay_on_the_side_of_the_normal() {
+        let normal = vector(0.0, 0.6, 0.8);
+        let mut rng = rng();
+
+        for _ in 0..100 {
+            let direction = cosine_direction(normal, &mut rng);
+            assert!(direction.dot(&normal) >= 0.0);
+            assert!((direction.magnitude() - 1.0).abs() < 1e-9);
+        }
+    }
+}
+
+// bounces before a path may be cut short by russian roulette
+const MIN_BOUNCES: i32 = 3;
+
+// how a path leaves a surface
+enum Bounce {
+    Diffuse,
+    Mirror,
+    Refract,
+}
+
+impl World {
+    /*
+     * Monte Carlo path tracing, the alternative to `color_at`. Every hit adds what the
+     * surface emits and the direct light of the world's lights, then the path goes on in
+     * one direction picked at random: a cosine weighted diffuse bounce, a mirror
+     * reflection or a refraction, as likely as the material's `diffuse`, `reflective` and
+     * `transparency` make them, or for a microfacet its metallic and Fresnel reflectance.
+     * Reflections and refractions are spread by its roughness, and a path inside an object
+     * loses what it absorbs on the way to each hit, until it refracts out again. Light
+     * bouncing between surfaces takes the place of `ambient`, which is not used. After a
+     * few bounces dim paths are ended at random (russian roulette), and the ones that go
+     * on are made brighter to make up for it. One path is noisy, the camera averages many.
+     *
+     * Mesh lights are already in the direct light, so a diffuse bounce that lands on one
+     * doesn't count its emission a second time.
+     * */
+    pub fn path_color(&self, ray: &Ray, max_depth: i32, rng: &mut impl Rng) -> Color {
+        let mut ray = Ray::with(ray.origin, ray.direction);
+        let mut throughput = white();
+        let mut radiance = black();
+        let mut after_diffuse = false;
+        // the object the path is inside of, absorbing along the way
+        let mut medium: Option<ShapeId> = None;
+
+        for bounce in 0..=max_depth.max(0) {
+            let xs = self.intersect_world(&ray).xs;
+            let Some(hit) = xs.iter().find(|i| i.t > 0.0) else {
+                break;
+            };
+            let comps = hit.prepare_computations(self, &ray, &Intersections::from(xs.clone()));
+            let shape = self.get_shape(comps.object);
+            let material = &shape.material;
+
+            if let Some(medium) = medium {
+                let distance = hit.t * ray.direction.magnitude();
+                throughput = throughput * self.get_shape(medium).material.transmittance(distance);
+            }
+
+            let direct = self.lights.iter().fold(black(), |sum, light| {
+                sum + material.direct_lighting(
+                    shape,
+                    light,
+                    comps.over_point,
+                    comps.eye_vector,
+                    comps.normal_vector,
+                    self.intensity_at(light, comps.over_point),
+                    self,
+                )
+            });
+            let emitted = if after_diffuse && self.is_mesh_light(comps.object) {
+                black()
+            } else {
+                material.emissive
+            };
+            radiance = radiance + throughput * (emitted + direct);
+
+            // the same split between reflection and refraction as `shade_hit`
+            let (reflect, refract) = if shape.is_opaque() {
+                (material.reflective, 0.0)
+            } else if material.reflective > 0.0 {
+                let reflectance = comps.schlick();
+                (
+                    material.reflective * reflectance,
+                    material.transparency * (1.0 - reflectance),
+                )
+            } else {
+                (0.0, material.transparency)
+            };
+
+            // as in `reflected_color`, microfacets mirror by Fresnel, metals have no diffuse
+            let surface_color = material.color_at(shape, comps.over_point, self);
+            let (diffuse, mirrored) = match &material.microfacet {
+                Some(microfacet) => (
+                    1.0 - microfacet.metallic,
+                    microfacet
+                        .reflectance(surface_color, comps.eye_vector.dot(&comps.normal_vector)),
+                ),
+                None => (material.diffuse, white() * reflect),
+            };
+            let reflect = mirrored.r.max(mirrored.g).max(mirrored.b);
+
+            let total = diffuse + reflect + refract;
+            if total <= 0.0 {
+                break;
+            }
+
+            let pick = rng.gen::<f64>() * total;
+            let bounce_kind = if pick < diffuse {
+                Bounce::Diffuse
+            } else if pick < diffuse + reflect {
+                Bounce::Mirror
+            } else {
+                Bounce::Refract
+            };
+
+            // rough surfaces spread reflections and refractions around the perfect ones
+            let roughness = material.lobe_roughness();
+            let mut spread =
+                |direction| glossy_direction(direction, comps.normal_vector, roughness, rng);
+
+            // picking by weight means every bounce carries all of `total`
+            throughput = throughput * total;
+            after_diffuse = matches!(bounce_kind, Bounce::Diffuse);
+            // bounces off the inside of an object stay in it, only refractions cross over
+            medium = comps.inside.then_some(comps.object);
+            ray = match bounce_kind {
+                Bounce::Diffuse => {
+                    throughput = throughput * surface_color;
+                    Ray::with(comps.over_point, cosine_direction(comps.normal_vector, rng))
+                }
+                Bounce::Mirror => {
+                    // metals tint what they mirror
+                    throughput = throughput * (mirrored * (1.0 / reflect));
+                    Ray::with(comps.over_point, spread(comps.reflection_vector))
+                }
+                Bounce::Refract => match comps.refracted_direction() {
+                    Some(direction) => {
+                        medium = (!comps.inside).then_some(comps.object);
+                        Ray::with(comps.under_point, spread(direction))
+                    }
+                    None => Ray::with(comps.over_point, spread(comps.reflection_vector)),
+                },
+            };
+
+            if bounce >= MIN_BOUNCES {
+                let survival = throughput.r.max(throughput.g).max(throughput.b).min(1.0);
+                if rng.gen::<f64>() >= survival {
+                    break;
+                }
+                throughput = throughput * (1.0 / survival);
+            }
+        }
+
+        radiance
+    }
+
+    fn is_mesh_light(&self, object: ShapeId) -> bool {
+        self.lights
+            .iter()
+            .filter_map(|light| light.shape())
+            .any(|shape| self.includes(shape, object))
+    }
+}
+
+/*
+ * A random direction around `normal`, more likely the closer it is to the normal, the
+ * way a matte surface scatters light. Picks a point on the unit disk and lifts it onto
+ * the hemisphere.
+ * */
+fn cosine_direction(normal: Tuple, rng: &mut impl Rng) -> Tuple {
+    let (u, v) = (rng.gen::<f64>(), rng.gen::<f64>());
+    let radius = u.sqrt();
+    let angle = TAU * v;
+
+    let helper = if normal.x.abs() > 0.9 {
+        vector(0.0, 1.0, 0.0)
+    } else {
+        vector(1.0, 0.0, 0.0)
+    };
+    let tangent = helper.cross(&normal).normalize();
+    let bitangent = normal.cross(&tangent);
+
+    (tangent * (radius * angle.cos())
+        + bitangent * (radius * angle.sin())
+        + normal * (1.0 - u).sqrt())
+    .normalize()
+}
@@ -1,56 +1,162 @@
+pub mod jitter;
+pub mod ramp;
+pub mod texture;
+
+pub use self::jitter::Jitter;
+pub use self::ramp::ColorRamp;
+pub use self::texture::{TextureFilter, UvMapping};
+
+use std::f64::consts::TAU;
+use std::sync::Arc;
+
+use crate::canvas::Canvas;
 use crate::color::Color;
 use crate::matrix::{is_equal_float, Matrix};
-use crate::perlin_noise::noise3;
+use crate::perlin_noise::{Fractal, NoiseGenerator};
 use crate::shape::Shape;
 use crate::tuple::Tuple;
 use crate::{black, color, point, World};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Pattern {
-    a: Color,
-    b: Color,
     inverse_transformation: Matrix,
     pattern_type: PatternType,
     noise: f64,
+    noise_generator: NoiseGenerator,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/*
+ * The two sides of stripes, rings, checkers and gradients are patterns themselves, so
+ * they can be nested with transformations of their own. A plain color is a `Solid`.
+ * */
+#[derive(Debug, Clone, PartialEq)]
 enum PatternType {
-    Striped,
-    Gradient,
-    Ring,
-    Checkers,
+    Solid(Color),
+    Striped(Box<Pattern>, Box<Pattern>),
+    Gradient(Box<Pattern>, Box<Pattern>),
+    Ring(Box<Pattern>, Box<Pattern>),
+    Checkers(Box<Pattern>, Box<Pattern>),
     Test,
+    Image {
+        texture: Arc<Canvas>,
+        mapping: UvMapping,
+        filter: TextureFilter,
+    },
+    // weights add up to 1.0
+    Blended(Vec<(f64, Pattern)>),
+    Perturbed(Box<Pattern>, Jitter),
+    Marble {
+        ramp: ColorRamp,
+        fractal: Fractal,
+        turbulence: f64,
+    },
+    Wood {
+        ramp: ColorRamp,
+        fractal: Fractal,
+        turbulence: f64,
+    },
+    Clouds {
+        ramp: ColorRamp,
+        fractal: Fractal,
+    },
+}
+
+impl From<Color> for Pattern {
+    fn from(color: Color) -> Self {
+        Pattern::solid(color)
+    }
 }
 
 impl Pattern {
-    pub fn color_at(self, point: Tuple) -> Color {
-        match self.pattern_type {
-            PatternType::Striped => match point.x.floor() as i64 % 2 {
-                0 => self.a,
-                _ => self.b,
+    // `point` is in the space of this pattern
+    pub fn color_at(&self, point: Tuple) -> Color {
+        match &self.pattern_type {
+            PatternType::Solid(color) => *color,
+            PatternType::Striped(a, b) => match point.x.floor() as i64 % 2 {
+                0 => a.color_in(point),
+                _ => b.color_in(point),
             },
-            PatternType::Gradient => {
-                let distance = self.b - self.a;
+            PatternType::Gradient(a, b) => {
+                let (a, b) = (a.color_in(point), b.color_in(point));
+                let distance = b - a;
                 let fraction = point.x - point.x.floor();
-                self.a + distance * fraction
+                a + distance * fraction
             }
-            PatternType::Ring => {
+            PatternType::Ring(a, b) => {
                 match (point.x.powi(2) + point.z.powi(2)).sqrt().floor() as i64 % 2 {
-                    0 => self.a,
-                    _ => self.b,
+                    0 => a.color_in(point),
+                    _ => b.color_in(point),
                 }
             }
-            PatternType::Checkers => {
+            PatternType::Checkers(a, b) => {
                 match (point.x.floor() + point.y.floor() + point.z.floor()) as i64 % 2 {
-                    0 => self.a,
-                    _ => self.b,
+                    0 => a.color_in(point),
+                    _ => b.color_in(point),
                 }
             }
             PatternType::Test => color(point.x, point.y, point.z),
+            PatternType::Image {
+                texture,
+                mapping,
+                filter,
+            } => {
+                let (u, v) = mapping.map(point);
+                texture::sample(texture, u, v, *filter)
+            }
+            PatternType::Blended(parts) => parts.iter().fold(black(), |sum, (weight, part)| {
+                sum + part.color_in(point) * *weight
+            }),
+            PatternType::Perturbed(pattern, jitter) => pattern.color_in(jitter.apply(point)),
+            PatternType::Marble {
+                ramp,
+                fractal,
+                turbulence,
+            } => {
+                let twist = turbulence
+                    * self
+                        .noise_generator
+                        .turbulence3(point.x, point.y, point.z, *fractal);
+                ramp.at(0.5 + 0.5 * (TAU * (point.x + twist)).sin())
+            }
+            PatternType::Wood {
+                ramp,
+                fractal,
+                turbulence,
+            } => {
+                let distance = (point.x.powi(2) + point.z.powi(2)).sqrt()
+                    + turbulence
+                        * self
+                            .noise_generator
+                            .turbulence3(point.x, point.y, point.z, *fractal);
+                ramp.at(distance - distance.floor())
+            }
+            PatternType::Clouds { ramp, fractal } => ramp.at(0.5
+                + 0.5
+                    * self
+                        .noise_generator
+                        .fbm3(point.x, point.y, point.z, *fractal)),
         }
     }
 
+    // `point` is in the space of the parent, the object or an enclosing pattern
+    fn color_in(&self, p: Tuple) -> Color {
+        let mut pattern_space = &p * &self.inverse_transformation;
+
+        if self.has_noise() {
+            let factor = self.noise
+                * self
+                    .noise_generator
+                    .noise3(pattern_space.x, pattern_space.y, pattern_space.z);
+            pattern_space = point(
+                pattern_space.x + factor,
+                pattern_space.y + factor,
+                pattern_space.z + factor,
+            );
+        }
+
+        self.color_at(pattern_space)
+    }
+
     pub fn with_transformation(mut self, transformation: Matrix) -> Self {
         self.inverse_transformation = transformation.inverse();
         self
@@ -61,73 +167,142 @@ impl Pattern {
         self
     }
 
+    // the noise for `with_noise` and the marble, wood and cloud patterns
+    pub fn with_noise_generator(mut self, noise_generator: NoiseGenerator) -> Self {
+        self.noise_generator = noise_generator;
+        self
+    }
+
     pub fn has_noise(&self) -> bool {
         !is_equal_float(self.noise, 0.0)
     }
 
-    pub fn color_at_object(self, world: &World, object: &Shape, p: Tuple) -> Color {
+    pub fn color_at_object(&self, world: &World, object: &Shape, p: Tuple) -> Color {
         let object_space = object.world_to_object(world, p);
-        let mut pattern_space = &object_space * &self.inverse_transformation;
 
-        if self.has_noise() {
-            let factor = self.noise * noise3(pattern_space.x, pattern_space.y, pattern_space.z);
-            pattern_space = point(
-                pattern_space.x + factor,
-                pattern_space.y + factor,
-                pattern_space.z + factor,
-            );
+        if self.uses_texture_coordinates() {
+            if let Some((u, v)) = object.texture_coordinates_at(object_space) {
+                return self.color_in(point(u, v, 0.0));
+            }
         }
 
-        self.color_at(pattern_space)
+        self.color_in(object_space)
     }
 
-    pub fn striped(a: Color, b: Color) -> Self {
-        Pattern {
-            a,
-            b,
-            inverse_transformation: Matrix::identity().inverse(),
-            pattern_type: PatternType::Striped,
-            noise: 0.0,
-        }
+    fn uses_texture_coordinates(&self) -> bool {
+        matches!(
+            self.pattern_type,
+            PatternType::Image {
+                mapping: UvMapping::Mesh,
+                ..
+            }
+        )
     }
 
-    pub fn gradient(a: Color, b: Color) -> Self {
-        Pattern {
-            a,
-            b,
-            inverse_transformation: Matrix::identity().inverse(),
-            pattern_type: PatternType::Gradient,
-            noise: 0.0,
-        }
+    pub fn solid(color: Color) -> Self {
+        Pattern::with_type(PatternType::Solid(color))
     }
 
-    pub fn ring(a: Color, b: Color) -> Self {
-        Pattern {
-            a,
-            b,
-            inverse_transformation: Matrix::identity().inverse(),
-            pattern_type: PatternType::Ring,
-            noise: 0.0,
-        }
+    pub fn striped(a: impl Into<Pattern>, b: impl Into<Pattern>) -> Self {
+        Pattern::with_type(PatternType::Striped(Box::new(a.into()), Box::new(b.into())))
     }
 
-    pub fn checkers(a: Color, b: Color) -> Self {
-        Pattern {
-            a,
-            b,
-            inverse_transformation: Matrix::identity().inverse(),
-            pattern_type: PatternType::Checkers,
-            noise: 0.0,
-        }
+    pub fn gradient(a: impl Into<Pattern>, b: impl Into<Pattern>) -> Self {
+        Pattern::with_type(PatternType::Gradient(
+            Box::new(a.into()),
+            Box::new(b.into()),
+        ))
+    }
+
+    pub fn ring(a: impl Into<Pattern>, b: impl Into<Pattern>) -> Self {
+        Pattern::with_type(PatternType::Ring(Box::new(a.into()), Box::new(b.into())))
+    }
+
+    pub fn checkers(a: impl Into<Pattern>, b: impl Into<Pattern>) -> Self {
+        Pattern::with_type(PatternType::Checkers(
+            Box::new(a.into()),
+            Box::new(b.into()),
+        ))
+    }
+
+    // the average of the patterns, each counting the same
+    pub fn blended(patterns: Vec<Pattern>) -> Self {
+        Pattern::weighted(patterns.into_iter().map(|pattern| (1.0, pattern)).collect())
+    }
+
+    // each pattern counts in proportion to its weight
+    pub fn weighted(parts: Vec<(f64, Pattern)>) -> Self {
+        let total = parts.iter().map(|(weight, _)| weight).sum::<f64>();
+        assert!(
+            total > 0.0,
+            "a blend needs a pattern with a positive weight"
+        );
+
+        Pattern::with_type(PatternType::Blended(
+            parts
+                .into_iter()
+                .map(|(weight, pattern)| (weight / total, pattern))
+                .collect(),
+        ))
+    }
+
+    // moves the point looked up in `pattern` by `jitter`, to break up its straight lines
+    pub fn perturbed(pattern: Pattern, jitter: Jitter) -> Self {
+        Pattern::with_type(PatternType::Perturbed(Box::new(pattern), jitter))
+    }
+
+    /*
+     * Veins across x, one every unit, bent by turbulence. The ramp goes from the color
+     * between the veins at 0 to the middle of a vein at 1.
+     * */
+    pub fn marble(ramp: ColorRamp, fractal: Fractal, turbulence: f64) -> Self {
+        Pattern::with_type(PatternType::Marble {
+            ramp,
+            fractal,
+            turbulence,
+        })
+    }
+
+    // rings around the y axis, one per unit, the ramp going from the inside of a ring out
+    pub fn wood(ramp: ColorRamp, fractal: Fractal, turbulence: f64) -> Self {
+        Pattern::with_type(PatternType::Wood {
+            ramp,
+            fractal,
+            turbulence,
+        })
+    }
+
+    // fractal noise straight through the ramp, soft blobs at every scale
+    pub fn clouds(ramp: ColorRamp, fractal: Fractal) -> Self {
+        Pattern::with_type(PatternType::Clouds { ramp, fractal })
+    }
+
+    /*
+     * Wraps an image around the object. The texture is shared, so a loaded image can be
+     * used by many patterns without copying its pixels.
+     * */
+    pub fn image(
+        texture: impl Into<Arc<Canvas>>,
+        mapping: UvMapping,
+        filter: TextureFilter,
+    ) -> Self {
+        Pattern::with_type(PatternType::Image {
+            texture: texture.into(),
+            mapping,
+            filter,
+        })
     }
 
     pub fn test() -> Self {
+        Pattern::with_type(PatternType::Test)
+    }
+
+    fn with_type(pattern_type: PatternType) -> Self {
         Pattern {
-            a: black(),
-            b: black(),
             inverse_transformation: Matrix::identity().inverse(),
-            pattern_type: PatternType::Test,
+            pattern_type,
             noise: 0.0,
+            noise_generator: NoiseGenerator::default(),
         }
     }
 }
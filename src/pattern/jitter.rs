@@ -0,0 +1,109 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::perlin_noise::NoiseGenerator;
+use crate::tuple::{point, Tuple};
+
+#[cfg(test)]
+mod jitter_test {
+    use crate::tuple::point;
+
+    use super::Jitter;
+
+    #[test]
+    fn a_custom_jitter_moves_the_point() {
+        let jitter = Jitter::new(|p| point(p.x + 1.0, p.y, p.z * 2.0));
+
+        assert_eq!(jitter.apply(point(1.0, 2.0, 3.0)), point(2.0, 2.0, 6.0));
+    }
+
+    #[test]
+    fn perlin_jitter_moves_each_axis_by_at_most_the_scale() {
+        let jitter = Jitter::perlin(0.2);
+
+        for i in 0..50 {
+            let p = point(i as f64 * 0.37, i as f64 * 0.11, i as f64 * -0.53);
+            let moved = jitter.apply(p);
+
+            assert!((moved.x - p.x).abs() <= 0.2);
+            assert!((moved.y - p.y).abs() <= 0.2);
+            assert!((moved.z - p.z).abs() <= 0.2);
+        }
+    }
+
+    #[test]
+    fn perlin_jitter_moves_the_axes_independently() {
+        let moved = Jitter::perlin(1.0).apply(point(0.3, 0.6, 0.9));
+
+        assert_ne!(moved.x - 0.3, moved.y - 0.6);
+    }
+
+    #[test]
+    fn waves_follow_a_sine() {
+        let jitter = Jitter::waves(0.5, 1.0);
+
+        assert_eq!(jitter.apply(point(0.0, 0.0, 0.0)), point(0.0, 0.0, 0.0));
+        assert_eq!(jitter.apply(point(0.0, 0.25, 0.0)), point(0.5, 0.25, 0.0));
+    }
+
+    #[test]
+    fn jitters_are_equal_when_they_share_the_function() {
+        let jitter = Jitter::perlin(0.1);
+
+        assert_eq!(jitter, jitter.clone());
+        assert_ne!(jitter, Jitter::perlin(0.1));
+    }
+}
+
+/*
+ * Moves a point before a pattern looks it up. Any function from point to point will do,
+ * `perlin` and `waves` are the common ones. The function is shared between clones, and
+ * two jitters are only equal when they are clones of each other.
+ * */
+#[derive(Clone)]
+pub struct Jitter(Arc<dyn Fn(Tuple) -> Tuple + Send + Sync>);
+
+impl Jitter {
+    pub fn new(jitter: impl Fn(Tuple) -> Tuple + Send + Sync + 'static) -> Self {
+        Jitter(Arc::new(jitter))
+    }
+
+    pub fn perlin(scale: f64) -> Self {
+        Jitter::noise(NoiseGenerator::default(), scale)
+    }
+
+    // moves every axis by its own noise, scaled to at most `scale`
+    pub fn noise(noise: NoiseGenerator, scale: f64) -> Self {
+        Jitter::new(move |p| {
+            point(
+                p.x + noise.noise3(p.x, p.y, p.z) * scale,
+                p.y + noise.noise3(p.x, p.y, p.z + 1.0) * scale,
+                p.z + noise.noise3(p.x, p.y, p.z + 2.0) * scale,
+            )
+        })
+    }
+
+    // shifts x along a sine of y, `frequency` waves per unit
+    pub fn waves(amplitude: f64, frequency: f64) -> Self {
+        Jitter::new(move |p| {
+            let shift = amplitude * (p.y * frequency * std::f64::consts::TAU).sin();
+            point(p.x + shift, p.y, p.z)
+        })
+    }
+
+    pub fn apply(&self, p: Tuple) -> Tuple {
+        (self.0)(p)
+    }
+}
+
+impl fmt::Debug for Jitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Jitter")
+    }
+}
+
+impl PartialEq for Jitter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
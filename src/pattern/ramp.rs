@@ -0,0 +1,102 @@
+use crate::color::Color;
+
+#[cfg(test)]
+mod ramp_test {
+    use crate::color::{black, color, white};
+
+    use super::ColorRamp;
+
+    #[test]
+    fn ramp_between_two_colors() {
+        let ramp = ColorRamp::between(black(), white());
+
+        assert_eq!(ramp.at(0.0), black());
+        assert_eq!(ramp.at(0.25), color(0.25, 0.25, 0.25));
+        assert_eq!(ramp.at(1.0), white());
+    }
+
+    #[test]
+    fn ramp_blends_between_the_closest_stops() {
+        let ramp = ColorRamp::new(vec![
+            (1.0, color(0.0, 0.0, 1.0)),
+            (0.0, color(1.0, 0.0, 0.0)),
+            (0.5, color(0.0, 1.0, 0.0)),
+        ]);
+
+        assert_eq!(ramp.at(0.5), color(0.0, 1.0, 0.0));
+        assert_eq!(ramp.at(0.25), color(0.5, 0.5, 0.0));
+        assert_eq!(ramp.at(0.75), color(0.0, 0.5, 0.5));
+    }
+
+    #[test]
+    fn ramp_is_flat_outside_its_stops() {
+        let ramp = ColorRamp::new(vec![(0.2, black()), (0.8, white())]);
+
+        assert_eq!(ramp.at(0.0), black());
+        assert_eq!(ramp.at(-3.0), black());
+        assert_eq!(ramp.at(0.9), white());
+        assert_eq!(ramp.at(0.5), color(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn evenly_spread_colors() {
+        let ramp = ColorRamp::even(vec![black(), color(1.0, 0.0, 0.0), white()]);
+
+        assert_eq!(ramp.at(0.5), color(1.0, 0.0, 0.0));
+        assert_eq!(ramp.at(0.75), color(1.0, 0.5, 0.5));
+    }
+
+    #[test]
+    #[should_panic]
+    fn ramp_needs_a_color() {
+        ColorRamp::new(vec![]);
+    }
+}
+
+/*
+ * Turns a value from 0 to 1 into a color, blending between the colors of the stops on
+ * either side. Below the first stop and above the last the color stays the same.
+ * */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorRamp {
+    stops: Vec<(f64, Color)>,
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<(f64, Color)>) -> Self {
+        assert!(!stops.is_empty(), "a color ramp needs at least one color");
+        stops.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        ColorRamp { stops }
+    }
+
+    pub fn between(a: Color, b: Color) -> Self {
+        ColorRamp::new(vec![(0.0, a), (1.0, b)])
+    }
+
+    // the first color at 0, the last at 1 and the rest evenly in between
+    pub fn even(colors: Vec<Color>) -> Self {
+        let last = colors.len().saturating_sub(1).max(1) as f64;
+        ColorRamp::new(
+            colors
+                .into_iter()
+                .enumerate()
+                .map(|(i, color)| (i as f64 / last, color))
+                .collect(),
+        )
+    }
+
+    pub fn at(&self, t: f64) -> Color {
+        let after = self.stops.partition_point(|(position, _)| *position <= t);
+
+        if after == 0 {
+            return self.stops[0].1;
+        }
+        if after == self.stops.len() {
+            return self.stops[after - 1].1;
+        }
+
+        let (start, a) = self.stops[after - 1];
+        let (end, b) = self.stops[after];
+        a + (b - a) * ((t - start) / (end - start))
+    }
+}
@@ -0,0 +1,320 @@
+use std::f64::consts::PI;
+
+use crate::canvas::Canvas;
+use crate::color::Color;
+use crate::tuple::{vector, Tuple};
+
+#[cfg(test)]
+mod texture_test {
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    use rstest::rstest;
+
+    use crate::canvas::Canvas;
+    use crate::color::{black, color, white};
+    use crate::tuple::point;
+
+    use super::{
+        cube_face, cylindrical_map, planar_map, sample, spherical_map, CubeFace, TextureFilter,
+        UvMapping,
+    };
+
+    #[rstest]
+    #[case(0.0, 0.0, -1.0, 0.0, 0.5)]
+    #[case(1.0, 0.0, 0.0, 0.25, 0.5)]
+    #[case(0.0, 0.0, 1.0, 0.5, 0.5)]
+    #[case(-1.0, 0.0, 0.0, 0.75, 0.5)]
+    #[case(0.0, 1.0, 0.0, 0.5, 1.0)]
+    #[case(0.0, -1.0, 0.0, 0.5, 0.0)]
+    #[case(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0, 0.25, 0.75)]
+    fn spherical_mapping(
+        #[case] x: f64,
+        #[case] y: f64,
+        #[case] z: f64,
+        #[case] u: f64,
+        #[case] v: f64,
+    ) {
+        let (actual_u, actual_v) = spherical_map(point(x, y, z));
+
+        assert!((actual_u - u).abs() < 1e-5, "u was {}", actual_u);
+        assert!((actual_v - v).abs() < 1e-5, "v was {}", actual_v);
+    }
+
+    #[rstest]
+    #[case(0.25, 0.0, 0.5, 0.25, 0.5)]
+    #[case(0.25, 0.0, -0.25, 0.25, 0.75)]
+    #[case(0.25, 0.5, -0.25, 0.25, 0.75)]
+    #[case(1.25, 0.0, 0.5, 0.25, 0.5)]
+    #[case(0.25, 0.0, -1.75, 0.25, 0.25)]
+    #[case(1.0, 0.0, -1.0, 0.0, 0.0)]
+    #[case(0.0, 0.0, 0.0, 0.0, 0.0)]
+    fn planar_mapping(
+        #[case] x: f64,
+        #[case] y: f64,
+        #[case] z: f64,
+        #[case] u: f64,
+        #[case] v: f64,
+    ) {
+        assert_eq!(planar_map(point(x, y, z)), (u, v));
+    }
+
+    #[rstest]
+    #[case(0.0, 0.0, -1.0, 0.0, 0.0)]
+    #[case(0.0, 0.5, -1.0, 0.0, 0.5)]
+    #[case(0.0, 1.0, -1.0, 0.0, 0.0)]
+    #[case(FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2, 0.125, 0.5)]
+    #[case(1.0, 0.5, 0.0, 0.25, 0.5)]
+    #[case(FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2, 0.375, 0.5)]
+    #[case(0.0, -0.25, 1.0, 0.5, 0.75)]
+    #[case(-FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2, 0.625, 0.5)]
+    #[case(-1.0, 1.25, 0.0, 0.75, 0.25)]
+    #[case(-FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2, 0.875, 0.5)]
+    fn cylindrical_mapping(
+        #[case] x: f64,
+        #[case] y: f64,
+        #[case] z: f64,
+        #[case] u: f64,
+        #[case] v: f64,
+    ) {
+        let (actual_u, actual_v) = cylindrical_map(point(x, y, z));
+
+        assert!((actual_u - u).abs() < 1e-5, "u was {}", actual_u);
+        assert!((actual_v - v).abs() < 1e-5, "v was {}", actual_v);
+    }
+
+    #[test]
+    fn picking_the_face_of_a_cube() {
+        assert_eq!(cube_face(point(-1.0, 0.5, -0.25)).0, CubeFace::Left);
+        assert_eq!(cube_face(point(1.1, -0.75, 0.8)).0, CubeFace::Right);
+        assert_eq!(cube_face(point(0.1, 0.6, 0.9)).0, CubeFace::Front);
+        assert_eq!(cube_face(point(-0.7, 0.0, -2.0)).0, CubeFace::Back);
+        assert_eq!(cube_face(point(0.5, 1.0, 0.9)).0, CubeFace::Up);
+        assert_eq!(cube_face(point(-0.2, -1.3, 1.1)).0, CubeFace::Down);
+    }
+
+    #[test]
+    fn uv_on_the_faces_of_a_cube() {
+        assert_eq!(
+            cube_face(point(-0.5, 0.5, 1.0)),
+            (CubeFace::Front, 0.25, 0.75)
+        );
+        assert_eq!(
+            cube_face(point(0.5, -0.5, 1.0)),
+            (CubeFace::Front, 0.75, 0.25)
+        );
+        assert_eq!(
+            cube_face(point(0.5, 0.5, -1.0)),
+            (CubeFace::Back, 0.25, 0.75)
+        );
+        assert_eq!(
+            cube_face(point(-0.5, -0.5, -1.0)),
+            (CubeFace::Back, 0.75, 0.25)
+        );
+        assert_eq!(
+            cube_face(point(-1.0, 0.5, -0.5)),
+            (CubeFace::Left, 0.25, 0.75)
+        );
+        assert_eq!(
+            cube_face(point(-1.0, -0.5, 0.5)),
+            (CubeFace::Left, 0.75, 0.25)
+        );
+        assert_eq!(
+            cube_face(point(1.0, 0.5, 0.5)),
+            (CubeFace::Right, 0.25, 0.75)
+        );
+        assert_eq!(
+            cube_face(point(1.0, -0.5, -0.5)),
+            (CubeFace::Right, 0.75, 0.25)
+        );
+        assert_eq!(
+            cube_face(point(-0.5, 1.0, -0.5)),
+            (CubeFace::Up, 0.25, 0.75)
+        );
+        assert_eq!(cube_face(point(0.5, 1.0, 0.5)), (CubeFace::Up, 0.75, 0.25));
+        assert_eq!(
+            cube_face(point(-0.5, -1.0, 0.5)),
+            (CubeFace::Down, 0.25, 0.75)
+        );
+        assert_eq!(
+            cube_face(point(0.5, -1.0, -0.5)),
+            (CubeFace::Down, 0.75, 0.25)
+        );
+    }
+
+    #[test]
+    fn cube_mapping_uses_a_cross_layout() {
+        // the front face is the second cell of the middle row
+        let (u, v) = UvMapping::Cube.map(point(0.0, 0.0, 1.0));
+        assert_eq!((u, v), (0.375, 0.5));
+
+        // the top face sits above it
+        let (u, v) = UvMapping::Cube.map(point(0.0, 1.0, 0.0));
+        assert_eq!((u, v), (0.375, 5.0 / 6.0));
+    }
+
+    fn checkered_texture() -> Canvas {
+        Canvas::new(2, 2, black())
+            .write_pixel(0, 0, white())
+            .write_pixel(1, 1, white())
+    }
+
+    #[test]
+    fn nearest_filtering_picks_the_closest_pixel() {
+        let texture = checkered_texture();
+
+        // v goes up, so v = 1 is the top row of the image
+        assert_eq!(sample(&texture, 0.0, 1.0, TextureFilter::Nearest), white());
+        assert_eq!(sample(&texture, 0.4, 0.6, TextureFilter::Nearest), white());
+        assert_eq!(sample(&texture, 0.6, 0.6, TextureFilter::Nearest), black());
+        assert_eq!(sample(&texture, 1.0, 0.0, TextureFilter::Nearest), white());
+    }
+
+    #[test]
+    fn bilinear_filtering_blends_the_four_closest_pixels() {
+        let texture = checkered_texture();
+
+        assert_eq!(sample(&texture, 0.0, 1.0, TextureFilter::Bilinear), white());
+        assert_eq!(
+            sample(&texture, 0.5, 0.5, TextureFilter::Bilinear),
+            color(0.5, 0.5, 0.5)
+        );
+        assert_eq!(
+            sample(&texture, 0.25, 1.0, TextureFilter::Bilinear),
+            color(0.75, 0.75, 0.75)
+        );
+    }
+
+    #[test]
+    fn uv_outside_the_image_is_clamped() {
+        let texture = checkered_texture();
+
+        assert_eq!(sample(&texture, -1.0, 2.0, TextureFilter::Nearest), white());
+        assert_eq!(
+            sample(&texture, 2.0, -1.0, TextureFilter::Bilinear),
+            white()
+        );
+    }
+}
+
+/*
+ * How a point in pattern space is flattened to (u, v), both going from 0 to 1. Spherical
+ * and cylindrical wrap around the y axis, planar repeats every unit in x and z, and cube
+ * expects the image to be an unfolded cube: a cross four faces wide and three high. Mesh
+ * uses the texture coordinates of triangles from OBJ files, which the pattern gets as
+ * the x and y of the point.
+ * */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UvMapping {
+    Spherical,
+    Planar,
+    Cylindrical,
+    Cube,
+    Mesh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CubeFace {
+    Left,
+    Right,
+    Front,
+    Back,
+    Up,
+    Down,
+}
+
+impl UvMapping {
+    pub fn map(&self, point: Tuple) -> (f64, f64) {
+        match self {
+            UvMapping::Spherical => spherical_map(point),
+            UvMapping::Planar => planar_map(point),
+            UvMapping::Cylindrical => cylindrical_map(point),
+            UvMapping::Mesh => (point.x, point.y),
+            UvMapping::Cube => {
+                let (face, u, v) = cube_face(point);
+                // column and row of the face in the cross, counting rows from the bottom
+                let (column, row) = match face {
+                    CubeFace::Left => (0.0, 1.0),
+                    CubeFace::Front => (1.0, 1.0),
+                    CubeFace::Right => (2.0, 1.0),
+                    CubeFace::Back => (3.0, 1.0),
+                    CubeFace::Up => (1.0, 2.0),
+                    CubeFace::Down => (1.0, 0.0),
+                };
+                ((column + u) / 4.0, (row + v) / 3.0)
+            }
+        }
+    }
+}
+
+pub fn spherical_map(point: Tuple) -> (f64, f64) {
+    let theta = point.x.atan2(point.z);
+    let radius = vector(point.x, point.y, point.z).magnitude();
+    let phi = (point.y / radius).acos();
+
+    let raw_u = theta / (2.0 * PI);
+    (1.0 - (raw_u + 0.5), 1.0 - phi / PI)
+}
+
+pub fn planar_map(point: Tuple) -> (f64, f64) {
+    (point.x.rem_euclid(1.0), point.z.rem_euclid(1.0))
+}
+
+pub fn cylindrical_map(point: Tuple) -> (f64, f64) {
+    let theta = point.x.atan2(point.z);
+    let raw_u = theta / (2.0 * PI);
+
+    (1.0 - (raw_u + 0.5), point.y.rem_euclid(1.0))
+}
+
+// the face of the unit cube the point is on, and where on that face it is
+pub fn cube_face(point: Tuple) -> (CubeFace, f64, f64) {
+    let Tuple { x, y, z, .. } = point;
+    let largest = x.abs().max(y.abs()).max(z.abs());
+    let side = |a: f64| (a.rem_euclid(2.0)) / 2.0;
+
+    if largest == x {
+        (CubeFace::Right, side(1.0 - z), side(y + 1.0))
+    } else if largest == -x {
+        (CubeFace::Left, side(z + 1.0), side(y + 1.0))
+    } else if largest == y {
+        (CubeFace::Up, side(x + 1.0), side(1.0 - z))
+    } else if largest == -y {
+        (CubeFace::Down, side(x + 1.0), side(z + 1.0))
+    } else if largest == z {
+        (CubeFace::Front, side(x + 1.0), side(y + 1.0))
+    } else {
+        (CubeFace::Back, side(1.0 - x), side(y + 1.0))
+    }
+}
+
+/*
+ * The color of the texture at (u, v), with v = 0 the bottom of the image. Nearest takes
+ * the closest pixel, bilinear blends the four around the point so close ups don't show
+ * the pixels as blocks.
+ * */
+pub fn sample(texture: &Canvas, u: f64, v: f64, filter: TextureFilter) -> Color {
+    let last_x = (texture.width - 1) as f64;
+    let last_y = (texture.height - 1) as f64;
+    let x = u.clamp(0.0, 1.0) * last_x;
+    let y = (1.0 - v.clamp(0.0, 1.0)) * last_y;
+
+    match filter {
+        TextureFilter::Nearest => texture.pixel_at(x.round() as i32, y.round() as i32),
+        TextureFilter::Bilinear => {
+            let (x0, y0) = (x.floor(), y.floor());
+            let (x1, y1) = ((x0 + 1.0).min(last_x), (y0 + 1.0).min(last_y));
+            let (fx, fy) = (x - x0, y - y0);
+            let at = |x: f64, y: f64| texture.pixel_at(x as i32, y as i32);
+
+            let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+            let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+            top * (1.0 - fy) + bottom * fy
+        }
+    }
+}
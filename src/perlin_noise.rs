@@ -6,7 +6,160 @@ I looked a lot at this one as well:
     https://github.com/keijiro/PerlinNoise/blob/master/Assets/Perlin.cs
 */
 
-pub fn noise2(mut x: f64, mut y: f64) -> f64 {
+use std::fmt;
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+#[cfg(test)]
+mod perlin_noise_test {
+    use super::{noise3, Fractal, NoiseGenerator, NoiseKind};
+
+    fn points() -> impl Iterator<Item = (f64, f64, f64)> {
+        (0..200).map(|i| {
+            let i = i as f64;
+            (i * 0.173, i * -0.071, i * 0.319)
+        })
+    }
+
+    #[test]
+    fn one_octave_is_plain_noise() {
+        let fractal = Fractal {
+            octaves: 1,
+            ..Fractal::default()
+        };
+
+        for (x, y, z) in points() {
+            assert_eq!(
+                NoiseGenerator::default().fbm3(x, y, z, fractal),
+                noise3(x, y, z)
+            );
+        }
+    }
+
+    #[test]
+    fn fbm_stays_within_the_range_of_the_noise() {
+        for (x, y, z) in points() {
+            let value = NoiseGenerator::default().fbm3(x, y, z, Fractal::default());
+            assert!((-1.0..=1.0).contains(&value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn turbulence_is_between_zero_and_one() {
+        for (x, y, z) in points() {
+            let value = NoiseGenerator::default().turbulence3(x, y, z, Fractal::default());
+            assert!((0.0..=1.0).contains(&value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn more_octaves_add_detail() {
+        let smooth = Fractal {
+            octaves: 1,
+            ..Fractal::default()
+        };
+        let detailed = Fractal {
+            octaves: 6,
+            ..Fractal::default()
+        };
+
+        assert!(
+            points().any(|(x, y, z)| NoiseGenerator::default().fbm3(x, y, z, smooth)
+                != NoiseGenerator::default().fbm3(x, y, z, detailed))
+        );
+    }
+
+    #[test]
+    fn no_octaves_is_flat() {
+        let fractal = Fractal {
+            octaves: 0,
+            ..Fractal::default()
+        };
+
+        assert_eq!(NoiseGenerator::default().fbm3(0.3, 0.2, 0.1, fractal), 0.0);
+        assert_eq!(
+            NoiseGenerator::default().turbulence3(0.3, 0.2, 0.1, fractal),
+            0.0
+        );
+    }
+
+    #[test]
+    fn the_default_generator_is_perlins_noise() {
+        let noise = NoiseGenerator::default();
+
+        for (x, y, z) in points() {
+            assert_eq!(noise.noise3(x, y, z), noise3(x, y, z));
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_noise() {
+        let a = NoiseGenerator::new(42);
+        let b = NoiseGenerator::new(42);
+
+        assert_eq!(a, b);
+        for (x, y, z) in points() {
+            assert_eq!(a.noise3(x, y, z), b.noise3(x, y, z));
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_noise() {
+        let a = NoiseGenerator::new(1);
+        let b = NoiseGenerator::new(2);
+
+        assert_ne!(a, b);
+        assert!(points().any(|(x, y, z)| a.noise3(x, y, z) != b.noise3(x, y, z)));
+    }
+
+    #[test]
+    fn every_kind_of_noise_stays_between_minus_one_and_one() {
+        for kind in [NoiseKind::Perlin, NoiseKind::Simplex, NoiseKind::Value] {
+            let noise = NoiseGenerator::new(7).with_kind(kind);
+            let values = points()
+                .map(|(x, y, z)| noise.noise3(x, y, z))
+                .collect::<Vec<f64>>();
+
+            assert!(values.iter().all(|value| (-1.0..=1.0).contains(value)));
+            assert!(values.iter().any(|value| *value != values[0]), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn every_kind_of_noise_is_smooth() {
+        for kind in [NoiseKind::Perlin, NoiseKind::Simplex, NoiseKind::Value] {
+            let noise = NoiseGenerator::new(7).with_kind(kind);
+
+            for (x, y, z) in points() {
+                let step = noise.noise3(x, y, z) - noise.noise3(x + 1e-6, y, z);
+                assert!(step.abs() < 1e-3, "{:?} jumped by {}", kind, step);
+            }
+        }
+    }
+
+    #[test]
+    fn kinds_of_noise_differ() {
+        let perlin = NoiseGenerator::new(3);
+        let simplex = perlin.clone().with_kind(NoiseKind::Simplex);
+        let value = perlin.clone().with_kind(NoiseKind::Value);
+
+        assert!(points().any(|(x, y, z)| perlin.noise3(x, y, z) != simplex.noise3(x, y, z)));
+        assert!(points().any(|(x, y, z)| perlin.noise3(x, y, z) != value.noise3(x, y, z)));
+    }
+}
+
+pub fn noise2(x: f64, y: f64) -> f64 {
+    perlin2(&PERM, x, y)
+}
+
+pub fn noise3(x: f64, y: f64, z: f64) -> f64 {
+    perlin3(&PERM, x, y, z)
+}
+
+fn perlin2(perm: &Permutation, mut x: f64, mut y: f64) -> f64 {
     let x_ = x.floor() as i64 & 0xff;
     let y_ = y.floor() as i64 & 0xff;
 
@@ -16,21 +169,25 @@ pub fn noise2(mut x: f64, mut y: f64) -> f64 {
     let u = fade(x);
     let v = fade(y);
 
-    let a = (get_perm(x_) + y_) & 0xff;
-    let b = (get_perm(x_ + 1) + y_) & 0xff;
+    let a = (get_perm(perm, x_) + y_) & 0xff;
+    let b = (get_perm(perm, x_ + 1) + y_) & 0xff;
 
     lerp(
         v,
-        lerp(u, grad2(get_perm(a), x, y), grad2(get_perm(b), x - 1.0, y)),
         lerp(
             u,
-            grad2(get_perm(a + 1), x, y - 1.0),
-            grad2(get_perm(b + 1), x - 1.0, y - 1.0),
+            grad2(get_perm(perm, a), x, y),
+            grad2(get_perm(perm, b), x - 1.0, y),
+        ),
+        lerp(
+            u,
+            grad2(get_perm(perm, a + 1), x, y - 1.0),
+            grad2(get_perm(perm, b + 1), x - 1.0, y - 1.0),
         ),
     )
 }
 
-pub fn noise3(mut x: f64, mut y: f64, mut z: f64) -> f64 {
+fn perlin3(perm: &Permutation, mut x: f64, mut y: f64, mut z: f64) -> f64 {
     let x_ = x.floor() as i64 & 0xff;
     let y_ = y.floor() as i64 & 0xff;
     let z_ = z.floor() as i64 & 0xff;
@@ -42,12 +199,12 @@ pub fn noise3(mut x: f64, mut y: f64, mut z: f64) -> f64 {
     let v = fade(y);
     let w = fade(z);
 
-    let a = (get_perm(x_) + y_) & 0xff;
-    let b = (get_perm(x_ + 1) + y_) & 0xff;
-    let aa = (get_perm(a) + z_) & 0xff;
-    let ba = (get_perm(b) + z_) & 0xff;
-    let ab = (get_perm(a + 1) + z_) & 0xff;
-    let bb = (get_perm(b + 1) + z_) & 0xff;
+    let a = (get_perm(perm, x_) + y_) & 0xff;
+    let b = (get_perm(perm, x_ + 1) + y_) & 0xff;
+    let aa = (get_perm(perm, a) + z_) & 0xff;
+    let ba = (get_perm(perm, b) + z_) & 0xff;
+    let ab = (get_perm(perm, a + 1) + z_) & 0xff;
+    let bb = (get_perm(perm, b + 1) + z_) & 0xff;
 
     lerp(
         w,
@@ -55,36 +212,259 @@ pub fn noise3(mut x: f64, mut y: f64, mut z: f64) -> f64 {
             v,
             lerp(
                 u,
-                grad3(get_perm(aa), x, y, z),
-                grad3(get_perm(ba), x - 1.0, y, z),
+                grad3(get_perm(perm, aa), x, y, z),
+                grad3(get_perm(perm, ba), x - 1.0, y, z),
             ),
             lerp(
                 u,
-                grad3(get_perm(ab), x, y - 1.0, z),
-                grad3(get_perm(bb), x - 1.0, y - 1.0, z),
+                grad3(get_perm(perm, ab), x, y - 1.0, z),
+                grad3(get_perm(perm, bb), x - 1.0, y - 1.0, z),
             ),
         ),
         lerp(
             v,
             lerp(
                 u,
-                grad3(get_perm(aa + 1), x, y, z - 1.0),
-                grad3(get_perm(ba + 1), x - 1.0, y, z - 1.0),
+                grad3(get_perm(perm, aa + 1), x, y, z - 1.0),
+                grad3(get_perm(perm, ba + 1), x - 1.0, y, z - 1.0),
             ),
             lerp(
                 u,
-                grad3(get_perm(ab + 1), x, y - 1.0, z - 1.0),
-                grad3(get_perm(bb + 1), x - 1.0, y - 1.0, z - 1.0),
+                grad3(get_perm(perm, ab + 1), x, y - 1.0, z - 1.0),
+                grad3(get_perm(perm, bb + 1), x - 1.0, y - 1.0, z - 1.0),
             ),
         ),
     )
 }
 
-fn get_perm(i: i64) -> i64 {
-    PERM[i as usize]
+/*
+ * Fractal noise adds up `octaves` layers of noise. Every layer is `lacunarity` times the
+ * frequency and `gain` times the amplitude of the one before, so big shapes get smaller
+ * and smaller details on top.
+ * */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fractal {
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub gain: f64,
 }
 
-static PERM: [i64; 257] = [
+impl Default for Fractal {
+    fn default() -> Self {
+        Fractal {
+            octaves: 4,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Fractal {
+    fn sum(
+        &self,
+        noise: &NoiseGenerator,
+        (x, y, z): (f64, f64, f64),
+        layer: impl Fn(f64) -> f64,
+    ) -> f64 {
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+
+        for _ in 0..self.octaves {
+            sum += amplitude * layer(noise.noise3(x * frequency, y * frequency, z * frequency));
+            total_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        if total_amplitude == 0.0 {
+            0.0
+        } else {
+            sum / total_amplitude
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseKind {
+    Perlin,
+    Simplex,
+    Value,
+}
+
+/*
+ * A noise field of its own. Generators made from the same seed give the same noise, and
+ * different seeds give unrelated fields, so two objects don't have to share one. The
+ * default is Perlin's own table, the noise `noise3` gives.
+ * */
+#[derive(Clone, PartialEq)]
+pub struct NoiseGenerator {
+    kind: NoiseKind,
+    seed: Option<u64>,
+    permutation: Arc<Permutation>,
+}
+
+impl Default for NoiseGenerator {
+    fn default() -> Self {
+        NoiseGenerator {
+            kind: NoiseKind::Perlin,
+            seed: None,
+            permutation: Arc::new(PERM),
+        }
+    }
+}
+
+impl fmt::Debug for NoiseGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NoiseGenerator")
+            .field("kind", &self.kind)
+            .field("seed", &self.seed)
+            .finish()
+    }
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: u64) -> Self {
+        let mut permutation = [0; 257];
+        let mut values = (0..=255).collect::<Vec<u8>>();
+        values.shuffle(&mut StdRng::seed_from_u64(seed));
+        permutation[..256].copy_from_slice(&values);
+        permutation[256] = permutation[0];
+
+        NoiseGenerator {
+            kind: NoiseKind::Perlin,
+            seed: Some(seed),
+            permutation: Arc::new(permutation),
+        }
+    }
+
+    pub fn with_kind(mut self, kind: NoiseKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn kind(&self) -> NoiseKind {
+        self.kind
+    }
+
+    // between -1 and 1
+    pub fn noise3(&self, x: f64, y: f64, z: f64) -> f64 {
+        match self.kind {
+            NoiseKind::Perlin => perlin3(&self.permutation, x, y, z),
+            NoiseKind::Simplex => simplex3(&self.permutation, x, y, z),
+            NoiseKind::Value => value3(&self.permutation, x, y, z),
+        }
+    }
+
+    // fractal Brownian motion, between -1 and 1 like the noise itself
+    pub fn fbm3(&self, x: f64, y: f64, z: f64, fractal: Fractal) -> f64 {
+        fractal.sum(self, (x, y, z), |noise| noise)
+    }
+
+    // like fbm but of the absolute noise, between 0 and 1, which gives sharp creases
+    pub fn turbulence3(&self, x: f64, y: f64, z: f64, fractal: Fractal) -> f64 {
+        fractal.sum(self, (x, y, z), f64::abs)
+    }
+}
+
+/*
+ * Simplex noise, Perlin's later take: it sums the gradients of the four corners of the
+ * tetrahedron around the point instead of the eight of the cube, which is cheaper and
+ * has no grid aligned artifacts. Follows Stefan Gustavson's "Simplex noise demystified".
+ * */
+fn simplex3(perm: &Permutation, x: f64, y: f64, z: f64) -> f64 {
+    const F3: f64 = 1.0 / 3.0;
+    const G3: f64 = 1.0 / 6.0;
+
+    let skew = (x + y + z) * F3;
+    let (i, j, k) = ((x + skew).floor(), (y + skew).floor(), (z + skew).floor());
+    let unskew = (i + j + k) * G3;
+    let (x0, y0, z0) = (x - (i - unskew), y - (j - unskew), z - (k - unskew));
+
+    // which of the six tetrahedra in the cube the point is in
+    let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+        if y0 >= z0 {
+            ((1, 0, 0), (1, 1, 0))
+        } else if x0 >= z0 {
+            ((1, 0, 0), (1, 0, 1))
+        } else {
+            ((0, 0, 1), (1, 0, 1))
+        }
+    } else if y0 < z0 {
+        ((0, 0, 1), (0, 1, 1))
+    } else if x0 < z0 {
+        ((0, 1, 0), (0, 1, 1))
+    } else {
+        ((0, 1, 0), (1, 1, 0))
+    };
+
+    let corners = [
+        (0, 0, 0, 0.0),
+        (i1, j1, k1, G3),
+        (i2, j2, k2, 2.0 * G3),
+        (1, 1, 1, 3.0 * G3),
+    ];
+    let (i, j, k) = (i as i64, j as i64, k as i64);
+
+    let sum = corners
+        .iter()
+        .map(|&(di, dj, dk, offset)| {
+            let dx = x0 - di as f64 + offset;
+            let dy = y0 - dj as f64 + offset;
+            let dz = z0 - dk as f64 + offset;
+            let t = 0.6 - dx * dx - dy * dy - dz * dz;
+            if t < 0.0 {
+                0.0
+            } else {
+                let hash = hash3(perm, i + di, j + dj, k + dk);
+                t.powi(4) * grad3(hash, dx, dy, dz)
+            }
+        })
+        .sum::<f64>();
+
+    (32.0 * sum).clamp(-1.0, 1.0)
+}
+
+// a random value at every corner of the grid, smoothly blended in between
+fn value3(perm: &Permutation, mut x: f64, mut y: f64, mut z: f64) -> f64 {
+    let (i, j, k) = (x.floor() as i64, y.floor() as i64, z.floor() as i64);
+    x -= x.floor();
+    y -= y.floor();
+    z -= z.floor();
+
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+    let at = |di: i64, dj: i64, dk: i64| hash3(perm, i + di, j + dj, k + dk) as f64 / 127.5 - 1.0;
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, at(0, 0, 0), at(1, 0, 0)),
+            lerp(u, at(0, 1, 0), at(1, 1, 0)),
+        ),
+        lerp(
+            v,
+            lerp(u, at(0, 0, 1), at(1, 0, 1)),
+            lerp(u, at(0, 1, 1), at(1, 1, 1)),
+        ),
+    )
+}
+
+fn hash3(perm: &Permutation, i: i64, j: i64, k: i64) -> i64 {
+    let k = get_perm(perm, k & 0xff);
+    let j = get_perm(perm, (k + j) & 0xff);
+    get_perm(perm, (j + i) & 0xff)
+}
+
+fn get_perm(perm: &Permutation, i: i64) -> i64 {
+    perm[i as usize] as i64
+}
+
+// 0 to 255 shuffled, with the first repeated at the end so `i + 1` never needs wrapping
+type Permutation = [u8; 257];
+
+static PERM: Permutation = [
     151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
     142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
     203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
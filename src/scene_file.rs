@@ -0,0 +1,1507 @@
+pub mod yaml;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::{
+    camera::Camera,
+    canvas::Canvas,
+    color::{color, Color},
+    lights::{AreaLight, DirectionalLight, MeshLight, PointLight, SpotLight},
+    material::{Material, Microfacet},
+    matrix::Matrix,
+    obj_file::add_obj_file_from_path,
+    pattern::{ColorRamp, Jitter, Pattern, TextureFilter, UvMapping},
+    perlin_noise::{Fractal, NoiseGenerator, NoiseKind},
+    shape::{CsgType, Shape, ShapeType},
+    transformation::view_transformation,
+    tuple::{point, vector, Tuple},
+    world::{ShapeId, World},
+};
+
+use self::yaml::{parse_yaml, Node, Value};
+
+#[cfg(test)]
+mod scene_file_test {
+    use std::f64::consts::FRAC_PI_2;
+    use std::path::Path;
+
+    use crate::{
+        color::{color, white},
+        lights::{AreaLight, Light, PointLight},
+        material::Microfacet,
+        matrix::Matrix,
+        scenes::csg::csg,
+        shape::{CsgType, ShapeType},
+        transformation::view_transformation,
+        tuple::{point, point_i, vector},
+        world::World,
+    };
+
+    use super::{load_scene_file, parse_scene};
+
+    const CAMERA: &str = "
+- add: camera
+  width: 100
+  height: 50
+  field-of-view: 0.785
+  from: [ 0, 1.5, -5 ]
+  to: [ 0, 1, 0 ]
+  up: [ 0, 1, 0 ]
+";
+
+    fn scene(rest: &str) -> World {
+        parse_scene(&format!("{}{}", CAMERA, rest), Path::new("."))
+            .unwrap()
+            .0
+    }
+
+    fn error_for(content: &str) -> (Option<usize>, String) {
+        let error = parse_scene(content, Path::new(".")).err().unwrap();
+        (error.line, error.message)
+    }
+
+    #[test]
+    fn reading_the_camera() {
+        let (_, camera) = parse_scene(CAMERA, Path::new(".")).unwrap();
+
+        assert_eq!(camera.hsize, 100);
+        assert_eq!(camera.vsize, 50);
+        assert_eq!(camera.field_of_view, 0.785);
+        assert_eq!(
+            camera.transform,
+            view_transformation(
+                point(0.0, 1.5, -5.0),
+                point_i(0, 1, 0),
+                vector(0.0, 1.0, 0.0)
+            )
+        );
+    }
+
+    #[test]
+    fn reading_a_camera_with_depth_of_field() {
+        let with_aperture = |rest: &str| {
+            let content = format!("{}  aperture: 0.2\n{}", CAMERA.trim_start(), rest);
+            parse_scene(&content, Path::new(".")).unwrap().1
+        };
+
+        let focused_on_target = with_aperture("");
+        assert_eq!(focused_on_target.aperture, 0.2);
+        assert_eq!(focused_on_target.focal_distance, f64::sqrt(25.25));
+
+        let focused = with_aperture("  focal-distance: 3\n");
+        assert_eq!(focused.focal_distance, 3.0);
+    }
+
+    #[test]
+    fn reading_lights() {
+        let world = scene(
+            "
+- add: light
+  at: [ -10, 10, -10 ]
+  intensity: [ 1, 1, 1 ]
+- add: light
+  corner: [ -1, 2, 4 ]
+  uvec: [ 2, 0, 0 ]
+  usteps: 4
+  vvec: [ 0, 2, 0 ]
+  vsteps: 2
+  jitter: true
+  intensity: [ 1.5, 1.5, 1.5 ]
+- add: directional-light
+  direction: [ 0, -1, 0 ]
+  intensity: [ 0.5, 0.5, 0.5 ]
+- add: spot-light
+  at: [ 0, 10, 0 ]
+  direction: [ 0, -1, 0 ]
+  inner-angle: 0.3
+  outer-angle: 0.5
+  intensity: [ 1, 1, 1 ]
+",
+        );
+
+        assert_eq!(world.lights.len(), 4);
+        assert_eq!(
+            world.lights[0],
+            Light::from(PointLight::with(point_i(-10, 10, -10), white()))
+        );
+        assert_eq!(
+            world.lights[1],
+            Light::from(
+                AreaLight::with(
+                    point_i(-1, 2, 4),
+                    vector(2.0, 0.0, 0.0),
+                    4,
+                    vector(0.0, 2.0, 0.0),
+                    2,
+                    color(1.5, 1.5, 1.5)
+                )
+                .with_jitter(true)
+            )
+        );
+        assert!(matches!(world.lights[2], Light::Directional(_)));
+        assert!(matches!(world.lights[3], Light::Spot(_)));
+    }
+
+    #[test]
+    fn reading_primitives_with_material_and_transform() {
+        let world = scene(
+            "
+- add: sphere
+  material:
+    color: [ 1, 0.2, 1 ]
+    diffuse: 0.7
+    ambient: 0.2
+    specular: 0.3
+    shininess: 50
+    reflective: 0.4
+    transparency: 0.5
+    refractive-index: 1.5
+    roughness: 0.1
+    absorption: [ 0.2, 0.1, 0 ]
+    emissive: [ 0.5, 0.5, 0 ]
+  transform:
+    - [ scale, 2, 2, 2 ]
+    - [ rotate-y, 1.5707963267948966 ]
+    - [ translate, 1, 2, 3 ]
+- add: cylinder
+  min: 0
+  max: 2
+  closed: true
+",
+        );
+
+        let sphere = world.get_shape(0);
+        assert_eq!(sphere.shape_type, ShapeType::Sphere);
+        assert_eq!(sphere.material.color, color(1.0, 0.2, 1.0));
+        assert_eq!(sphere.material.diffuse, 0.7);
+        assert_eq!(sphere.material.ambient, 0.2);
+        assert_eq!(sphere.material.specular, 0.3);
+        assert_eq!(sphere.material.shininess, 50.0);
+        assert_eq!(sphere.material.reflective, 0.4);
+        assert_eq!(sphere.material.transparency, 0.5);
+        assert_eq!(sphere.material.refractive_index, 1.5);
+        assert_eq!(sphere.material.roughness, 0.1);
+        assert_eq!(sphere.material.absorption, color(0.2, 0.1, 0.0));
+        assert_eq!(sphere.material.emissive, color(0.5, 0.5, 0.0));
+        assert_eq!(
+            sphere.transformation,
+            Matrix::identity()
+                .scale(2.0, 2.0, 2.0)
+                .rotate_y(FRAC_PI_2)
+                .translate(1.0, 2.0, 3.0)
+        );
+
+        assert_eq!(
+            world.get_shape(1).shape_type,
+            ShapeType::Cylinder(0.0, 2.0, true)
+        );
+    }
+
+    #[test]
+    fn reading_microfacet_materials() {
+        let world = scene(
+            "
+- add: sphere
+  material:
+    color: [ 1, 0.8, 0.3 ]
+    microfacet:
+      metallic: 1
+      roughness: 0.25
+- add: sphere
+  material:
+    microfacet:
+      metallic: 0
+",
+        );
+
+        assert_eq!(
+            world.get_shape(0).material.microfacet,
+            Some(Microfacet::new(1.0, 0.25))
+        );
+        assert_eq!(
+            world.get_shape(1).material.microfacet,
+            Some(Microfacet::new(0.0, 0.5))
+        );
+    }
+
+    #[test]
+    fn emissive_shapes_can_be_lights() {
+        let world = scene(
+            "
+- add: group
+  transform:
+    - [ translate, 0, 5, 0 ]
+  children:
+    - add: cube
+      light-samples: 8
+      material:
+        emissive: [ 1, 1, 0.5 ]
+",
+        );
+
+        match &world.lights[..] {
+            [Light::Mesh(light)] => {
+                assert_eq!(light.shape, 1);
+                assert_eq!(light.positions.len(), 8);
+                assert_eq!(light.intensity, color(1.0, 1.0, 0.5));
+                assert!(light.positions.iter().all(|p| p.y >= 4.0 && p.y <= 6.0));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn only_some_shapes_can_be_lights() {
+        let content = format!("{}- add: plane\n  light-samples: 4\n", CAMERA);
+
+        assert_eq!(
+            error_for(&content),
+            (
+                Some(10),
+                "only triangles, cubes and evenly scaled spheres can be lights".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn defines_can_be_extended_and_referenced() {
+        let world = scene(
+            "
+- define: white-material
+  value:
+    color: [ 1, 1, 1 ]
+    diffuse: 0.7
+- define: blue-material
+  extend: white-material
+  value:
+    color: [ 0.5, 0.8, 0.9 ]
+- define: standard-transform
+  value:
+    - [ translate, 1, -1, 1 ]
+    - [ scale, 0.5, 0.5, 0.5 ]
+- define: large-object
+  value:
+    - standard-transform
+    - [ scale, 3.5, 3.5, 3.5 ]
+- add: cube
+  material: blue-material
+  transform:
+    - large-object
+",
+        );
+
+        let cube = world.get_shape(0);
+        assert_eq!(cube.material.color, color(0.5, 0.8, 0.9));
+        assert_eq!(cube.material.diffuse, 0.7);
+        assert_eq!(
+            cube.transformation,
+            Matrix::identity()
+                .translate(1.0, -1.0, 1.0)
+                .scale(0.5, 0.5, 0.5)
+                .scale(3.5, 3.5, 3.5)
+        );
+    }
+
+    #[test]
+    fn defined_shapes_can_be_added_by_name() {
+        let world = scene(
+            "
+- define: small-ball
+  value:
+    add: sphere
+    transform:
+      - [ scale, 0.5, 0.5, 0.5 ]
+- add: small-ball
+  transform:
+    - [ translate, 1, 0, 0 ]
+",
+        );
+
+        let ball = world.get_shape(0);
+        assert_eq!(ball.shape_type, ShapeType::Sphere);
+        assert_eq!(
+            ball.transformation,
+            Matrix::identity().translate(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn reading_patterns() {
+        let world = scene(
+            "
+- add: plane
+  material:
+    pattern:
+      type: checkers
+      colors:
+        - [ 1, 1, 1 ]
+        - [ 0, 0, 0 ]
+      transform:
+        - [ scale, 0.25, 0.25, 0.25 ]
+",
+        );
+
+        let plane = world.get_shape(0);
+        let pattern = plane.material.pattern.as_ref().unwrap();
+        assert_eq!(pattern.color_at(point(0.1, 0.0, 0.0)), white());
+        assert_eq!(pattern.color_at(point(1.1, 0.0, 0.0)), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn reading_nested_and_blended_patterns() {
+        let world = scene(
+            "
+- add: plane
+  material:
+    pattern:
+      type: checkers
+      colors:
+        - type: stripes
+          colors:
+            - [ 1, 0, 0 ]
+            - [ 0, 0, 1 ]
+          transform:
+            - [ scale, 0.5, 0.5, 0.5 ]
+        - type: blend
+          weights: [ 3, 1 ]
+          patterns:
+            - [ 1, 1, 1 ]
+            - [ 0, 0, 0 ]
+",
+        );
+
+        let pattern = world.get_shape(0).material.pattern.as_ref().unwrap();
+        assert_eq!(
+            pattern.color_at(point(0.25, 0.0, 0.0)),
+            color(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            pattern.color_at(point(0.75, 0.0, 0.0)),
+            color(0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            pattern.color_at(point(1.5, 0.0, 0.0)),
+            color(0.75, 0.75, 0.75)
+        );
+    }
+
+    #[test]
+    fn reading_noise_patterns() {
+        let world = scene(
+            "
+- add: sphere
+  material:
+    pattern:
+      type: marble
+      turbulence: 0
+      octaves: 2
+      ramp:
+        - [ 0, [ 0, 0, 0 ] ]
+        - [ 1, [ 1, 1, 1 ] ]
+- add: sphere
+  material:
+    pattern:
+      type: clouds
+      octaves: 0
+      colors:
+        - [ 0, 0, 1 ]
+        - [ 1, 1, 1 ]
+",
+        );
+
+        let marble = world.get_shape(0).material.pattern.as_ref().unwrap();
+        assert_eq!(marble.color_at(point(0.25, 0.0, 0.0)), white());
+
+        let clouds = world.get_shape(1).material.pattern.as_ref().unwrap();
+        assert_eq!(clouds.color_at(point(0.3, 0.2, 0.1)), color(0.5, 0.5, 1.0));
+    }
+
+    #[test]
+    fn reading_seeded_noise() {
+        let world = scene(
+            "
+- define: cloudy
+  value:
+    pattern:
+      type: clouds
+      seed: 7
+      noise-type: simplex
+      colors:
+        - [ 0, 0, 0 ]
+        - [ 1, 1, 1 ]
+- add: sphere
+  material: cloudy
+- add: sphere
+  material: cloudy
+- add: sphere
+  material:
+    pattern:
+      type: clouds
+      seed: 8
+      noise-type: simplex
+      colors:
+        - [ 0, 0, 0 ]
+        - [ 1, 1, 1 ]
+",
+        );
+
+        let pattern = |id| world.get_shape(id).material.pattern.as_ref().unwrap();
+        assert_eq!(pattern(0), pattern(1));
+        assert_ne!(pattern(0), pattern(2));
+    }
+
+    #[test]
+    fn unknown_noise_type() {
+        assert_eq!(
+            error_for(
+                "
+- add: sphere
+  material:
+    pattern:
+      type: clouds
+      noise-type: worley
+      colors: [ [ 0, 0, 0 ] ]
+"
+            ),
+            (Some(6), "unknown noise type `worley`".to_owned())
+        );
+    }
+
+    #[test]
+    fn reading_image_patterns() {
+        let content = format!(
+            "{}{}",
+            CAMERA,
+            "
+- add: plane
+  material:
+    pattern:
+      type: image
+      file: checkers.ppm
+      mapping: planar
+      filter: nearest
+"
+        );
+        let (world, _) = parse_scene(&content, Path::new("src/scenes/files")).unwrap();
+
+        let pattern = world.get_shape(0).material.pattern.as_ref().unwrap();
+        assert_eq!(pattern.color_at(point(0.1, 0.0, 0.9)), white());
+        assert_eq!(pattern.color_at(point(0.9, 0.0, 0.9)), color(0.0, 0.0, 0.0));
+        assert_eq!(pattern.color_at(point(0.9, 0.0, 0.1)), white());
+    }
+
+    #[test]
+    fn image_patterns_need_a_readable_file() {
+        let content = format!(
+            "{}{}",
+            CAMERA,
+            "
+- add: sphere
+  material:
+    pattern:
+      type: image
+      file: missing.ppm
+"
+        );
+        let error = parse_scene(&content, Path::new("src/scenes/files"))
+            .err()
+            .unwrap();
+
+        assert!(error.message.starts_with("could not read"), "{}", error);
+    }
+
+    #[test]
+    fn groups_pass_their_material_to_children() {
+        let world = scene(
+            "
+- add: group
+  material:
+    color: [ 1, 0, 0 ]
+  transform:
+    - [ translate, 0, 1, 0 ]
+  children:
+    - add: sphere
+    - add: cube
+      material:
+        color: [ 0, 0, 1 ]
+",
+        );
+
+        let group = world.get_shape(0);
+        assert!(group.is_group());
+        assert_eq!(
+            group.transformation,
+            Matrix::identity().translate(0.0, 1.0, 0.0)
+        );
+        assert_eq!(world.get_children(0), vec![1, 2]);
+        assert_eq!(world.get_shape(1).material.color, color(1.0, 0.0, 0.0));
+        assert_eq!(world.get_shape(2).material.color, color(0.0, 0.0, 1.0));
+        assert_eq!(world.get_bounds(0).max, point_i(1, 1, 1));
+    }
+
+    #[test]
+    fn reading_csg() {
+        let world = scene(
+            "
+- add: csg
+  operation: difference
+  left:
+    add: cube
+  right:
+    add: sphere
+    transform:
+      - [ scale, 1.3, 1.3, 1.3 ]
+",
+        );
+
+        let csg = world.get_shape(2);
+        assert_eq!(csg.shape_type, ShapeType::CSG(CsgType::DIFFERENCE, 0, 1));
+        assert_eq!(world.get_shape(0).parent, Some(2));
+        assert_eq!(world.get_shape(1).parent, Some(2));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_line() {
+        assert_eq!(
+            error_for("- add: sphere\n  transform:\n    - [ scale, 2, two, 2 ]\n"),
+            (Some(3), "expected a number, found `two`".to_owned())
+        );
+        assert_eq!(
+            error_for("- add: sphere\n- add: teacup\n"),
+            (Some(2), "unknown shape `teacup`".to_owned())
+        );
+        assert_eq!(
+            error_for("- add: cube\n  material: shiny\n"),
+            (Some(2), "`shiny` has not been defined".to_owned())
+        );
+        assert_eq!(
+            error_for("- add: cube\n  transform:\n    - [ rotate, 1 ]\n"),
+            (Some(3), "unknown transform `rotate`".to_owned())
+        );
+        assert_eq!(
+            error_for("- add: light\n  intensity: [ 1, 1, 1 ]\n"),
+            (Some(1), "missing `at`".to_owned())
+        );
+        assert_eq!(
+            error_for("- add: sphere\n"),
+            (None, "the scene has no camera".to_owned())
+        );
+    }
+
+    #[test]
+    fn bad_light_values_are_errors() {
+        assert_eq!(
+            error_for(
+                "- add: spot-light\n  at: [ 0, 0, 0 ]\n  direction: [ 0, -1, 0 ]\n  inner-angle: 0.5\n  outer-angle: 0.2\n  intensity: [ 1, 1, 1 ]\n"
+            ),
+            (
+                Some(5),
+                "`outer-angle` must not be smaller than `inner-angle`".to_owned()
+            )
+        );
+        assert_eq!(
+            error_for(
+                "- add: light\n  corner: [ 0, 0, 0 ]\n  uvec: [ 1, 0, 0 ]\n  usteps: 0\n  vvec: [ 0, 0, 1 ]\n  vsteps: 2\n  intensity: [ 1, 1, 1 ]\n"
+            ),
+            (Some(4), "expected at least 1, found `0`".to_owned())
+        );
+    }
+
+    #[test]
+    fn recursive_defines_are_errors() {
+        assert_eq!(
+            error_for("- define: t\n  value:\n    - t\n- add: sphere\n  transform:\n    - t\n"),
+            (Some(3), "recursive define `t`".to_owned())
+        );
+    }
+
+    #[test]
+    fn camera_sizes_must_be_positive() {
+        let camera = |width, aperture| {
+            format!(
+                "- add: camera\n  width: {}\n  height: 50\n  field-of-view: 0.785\n  from: [ 0, 1.5, -5 ]\n  to: [ 0, 1, 0 ]\n  up: [ 0, 1, 0 ]\n  aperture: {}\n",
+                width, aperture
+            )
+        };
+
+        assert_eq!(
+            error_for(&camera(0, 0.1)),
+            (Some(2), "the width must be positive".to_owned())
+        );
+        assert_eq!(
+            error_for(&camera(100, -0.1)),
+            (Some(8), "the aperture must not be negative".to_owned())
+        );
+    }
+
+    #[test]
+    fn example_scene_matches_scene_written_in_rust() {
+        let (world, camera) = load_scene_file("src/scenes/files/csg.yml").unwrap();
+
+        assert_eq!(camera.hsize, 1920);
+        assert_eq!(world.objects.len(), csg(World::default()).objects.len());
+        assert_eq!(world.lights.len(), 1);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SceneFileError {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl SceneFileError {
+    pub fn at(line: usize, message: &str) -> SceneFileError {
+        SceneFileError {
+            line: Some(line),
+            message: message.to_owned(),
+        }
+    }
+
+    pub fn anywhere(message: &str) -> SceneFileError {
+        SceneFileError {
+            line: None,
+            message: message.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+type SceneResult<T> = Result<T, SceneFileError>;
+
+pub fn load_scene_file(path: &str) -> SceneResult<(World, Camera)> {
+    let content = fs::read_to_string(path).map_err(|e| SceneFileError::anywhere(&e.to_string()))?;
+    let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+
+    parse_scene(&content, base_dir)
+}
+
+/*
+ * Reads a scene in the YAML format from the bonus chapters of the book. Files
+ * referenced by the scene, like OBJ meshes, are looked up relative to `base_dir`.
+ * */
+pub fn parse_scene(content: &str, base_dir: &Path) -> SceneResult<(World, Camera)> {
+    let document = parse_yaml(content)?;
+
+    let mut loader = SceneLoader {
+        world: World::with_lights(vec![]),
+        camera: None,
+        defines: HashMap::new(),
+        base_dir,
+        mesh_lights: vec![],
+    };
+
+    for item in as_list(&document)? {
+        let entries = as_map(item)?;
+
+        if let Some(name) = get(entries, "define") {
+            loader.define(as_str(name)?, entries, item.line)?;
+            continue;
+        }
+
+        let kind = as_str(required(entries, "add", item.line)?)?;
+        match kind {
+            "camera" => loader.camera = Some(read_camera(entries, item.line)?),
+            "light" | "directional-light" | "spot-light" => {
+                loader.add_light(kind, entries, item.line)?
+            }
+            _ => {
+                loader.add_shape(item, None)?;
+            }
+        }
+    }
+
+    // only now that every group has its transformation are the shapes where they will be
+    for (shape, samples, line) in loader.mesh_lights {
+        let light = MeshLight::from_shape(&loader.world, shape, samples).ok_or_else(|| {
+            SceneFileError::at(
+                line,
+                "only triangles, cubes and evenly scaled spheres can be lights",
+            )
+        })?;
+        loader.world.add_light(light);
+    }
+
+    match loader.camera {
+        Some(camera) => Ok((loader.world, camera)),
+        None => Err(SceneFileError::anywhere("the scene has no camera")),
+    }
+}
+
+struct SceneLoader<'a> {
+    world: World,
+    camera: Option<Camera>,
+    defines: HashMap<String, Node>,
+    base_dir: &'a Path,
+    // shapes that light the scene, with the number of samples and the line they are on
+    mesh_lights: Vec<(ShapeId, usize, usize)>,
+}
+
+impl<'a> SceneLoader<'a> {
+    fn define(&mut self, name: &str, entries: &[(String, Node)], line: usize) -> SceneResult<()> {
+        let mut value = required(entries, "value", line)?.clone();
+
+        if let Some(base) = get(entries, "extend") {
+            let base = self.lookup(base)?;
+            value = match (&base.value, &value.value) {
+                (Value::Map(base_entries), Value::Map(overrides)) => Node {
+                    value: Value::Map(merge(base_entries, overrides)),
+                    line: value.line,
+                },
+                _ => return Err(SceneFileError::at(line, "only mappings can be extended")),
+            };
+        }
+
+        self.defines.insert(name.to_owned(), value);
+        Ok(())
+    }
+
+    fn lookup(&self, name: &Node) -> SceneResult<Node> {
+        let name_str = as_str(name)?;
+        self.defines.get(name_str).cloned().ok_or_else(|| {
+            SceneFileError::at(name.line, &format!("`{}` has not been defined", name_str))
+        })
+    }
+
+    fn add_light(
+        &mut self,
+        kind: &str,
+        entries: &[(String, Node)],
+        line: usize,
+    ) -> SceneResult<()> {
+        let intensity = as_color(required(entries, "intensity", line)?)?;
+
+        match kind {
+            "directional-light" => {
+                let direction = as_vector(required(entries, "direction", line)?)?;
+                self.world
+                    .add_light(DirectionalLight::with(direction, intensity));
+            }
+            "spot-light" => {
+                let inner_angle = as_f64(required(entries, "inner-angle", line)?)?;
+                let outer = required(entries, "outer-angle", line)?;
+                let outer_angle = as_f64(outer)?;
+                if inner_angle > outer_angle {
+                    return Err(SceneFileError::at(
+                        outer.line,
+                        "`outer-angle` must not be smaller than `inner-angle`",
+                    ));
+                }
+
+                self.world.add_light(SpotLight::with(
+                    as_point(required(entries, "at", line)?)?,
+                    as_vector(required(entries, "direction", line)?)?,
+                    inner_angle,
+                    outer_angle,
+                    intensity,
+                ));
+            }
+            _ if get(entries, "corner").is_some() => {
+                let jitter = match get(entries, "jitter") {
+                    Some(jitter) => as_bool(jitter)?,
+                    None => false,
+                };
+                self.world.add_light(
+                    AreaLight::with(
+                        as_point(required(entries, "corner", line)?)?,
+                        as_vector(required(entries, "uvec", line)?)?,
+                        as_count(required(entries, "usteps", line)?)?,
+                        as_vector(required(entries, "vvec", line)?)?,
+                        as_count(required(entries, "vsteps", line)?)?,
+                        intensity,
+                    )
+                    .with_jitter(jitter),
+                );
+            }
+            _ => {
+                let position = as_point(required(entries, "at", line)?)?;
+                self.world.add_light(PointLight::with(position, intensity));
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Adds the shape and everything below it to the world. The material of a group
+     * (or csg, or mesh) is used by all children that don't have their own.
+     * */
+    fn add_shape(&mut self, item: &Node, inherited: Option<&Material>) -> SceneResult<ShapeId> {
+        let mut entries = as_map(item)?.clone();
+        let kind_node = required(&entries, "add", item.line)?.clone();
+        let mut kind = as_str(&kind_node)?.to_owned();
+
+        if !is_builtin_shape(&kind) {
+            let defined = self.defines.get(&kind).cloned().ok_or_else(|| {
+                SceneFileError::at(kind_node.line, &format!("unknown shape `{}`", kind))
+            })?;
+            let defined_entries = as_map(&defined)?;
+            let overrides = entries
+                .into_iter()
+                .filter(|(key, _)| key != "add")
+                .collect::<Vec<(String, Node)>>();
+            entries = merge(defined_entries, &overrides);
+            kind = as_str(required(&entries, "add", defined.line)?)?.to_owned();
+
+            if !is_builtin_shape(&kind) {
+                return Err(SceneFileError::at(
+                    defined.line,
+                    &format!("unknown shape `{}`", kind),
+                ));
+            }
+        }
+
+        let material = match get(&entries, "material") {
+            Some(node) => Some(self.read_material(node)?),
+            None => inherited.cloned(),
+        };
+        let transform = match get(&entries, "transform") {
+            Some(node) => self.read_transform(node)?,
+            None => Matrix::identity(),
+        };
+
+        let shape_id = match kind.as_str() {
+            "group" => {
+                let group = self
+                    .world
+                    .add_shape(Shape::group().with_transform(transform));
+                if let Some(children) = get(&entries, "children") {
+                    for child in as_list(children)? {
+                        let child_id = self.add_shape(child, material.as_ref())?;
+                        self.world.add_shape_to_group(group, child_id);
+                    }
+                }
+                self.world.calculate_bounds_for_group(group);
+                group
+            }
+            "csg" => {
+                let operation = required(&entries, "operation", item.line)?;
+                let csg_type = match as_str(operation)? {
+                    "union" => CsgType::UNION,
+                    "intersection" => CsgType::INTERSECTION,
+                    "difference" => CsgType::DIFFERENCE,
+                    other => {
+                        return Err(SceneFileError::at(
+                            operation.line,
+                            &format!("unknown csg operation `{}`", other),
+                        ))
+                    }
+                };
+                let left =
+                    self.add_shape(required(&entries, "left", item.line)?, material.as_ref())?;
+                let right =
+                    self.add_shape(required(&entries, "right", item.line)?, material.as_ref())?;
+                let csg = self.world.create_csg(csg_type, left, right);
+                self.world.set_transform(csg, transform);
+                csg
+            }
+            "obj" => {
+                let file = required(&entries, "file", item.line)?;
+                let path = self.base_dir.join(as_str(file)?);
+                let mesh = add_obj_file_from_path(&mut self.world, &path).map_err(|e| {
+                    SceneFileError::at(
+                        file.line,
+                        &format!("could not read {}: {}", path.display(), e),
+                    )
+                })?;
+                if let Some(material) = material {
+                    set_material(&mut self.world, mesh, &material);
+                }
+                self.world.set_transform(mesh, transform);
+                mesh
+            }
+            _ => {
+                let shape = self
+                    .read_primitive(&kind, &entries, item.line)?
+                    .with_transform(transform)
+                    .with_material(material.unwrap_or_default());
+                self.world.add_shape(shape)
+            }
+        };
+
+        if let Some(samples) = get(&entries, "light-samples") {
+            let count = as_usize(samples)?;
+            if count == 0 {
+                return Err(SceneFileError::at(
+                    samples.line,
+                    "a light needs at least one sample",
+                ));
+            }
+            self.mesh_lights.push((shape_id, count, samples.line));
+        }
+
+        Ok(shape_id)
+    }
+
+    fn read_primitive(
+        &self,
+        kind: &str,
+        entries: &[(String, Node)],
+        line: usize,
+    ) -> SceneResult<Shape> {
+        let limits = || -> SceneResult<(f64, f64, bool)> {
+            Ok((
+                match get(entries, "min") {
+                    Some(min) => as_f64(min)?,
+                    None => -f64::INFINITY,
+                },
+                match get(entries, "max") {
+                    Some(max) => as_f64(max)?,
+                    None => f64::INFINITY,
+                },
+                match get(entries, "closed") {
+                    Some(closed) => as_bool(closed)?,
+                    None => false,
+                },
+            ))
+        };
+
+        Ok(match kind {
+            "sphere" => Shape::sphere_default(),
+            "plane" => Shape::plane_default(),
+            "cube" => Shape::cube_default(),
+            "cylinder" => {
+                let (min, max, closed) = limits()?;
+                Shape::cylinder(min, max, closed)
+            }
+            "cone" => {
+                let (min, max, closed) = limits()?;
+                Shape::cone(min, max, closed)
+            }
+            "triangle" => Shape::triangle(
+                as_point(required(entries, "p1", line)?)?,
+                as_point(required(entries, "p2", line)?)?,
+                as_point(required(entries, "p3", line)?)?,
+            ),
+            _ => {
+                return Err(SceneFileError::at(
+                    line,
+                    &format!("unknown shape `{}`", kind),
+                ))
+            }
+        })
+    }
+
+    fn read_material(&self, node: &Node) -> SceneResult<Material> {
+        let node = match node.value {
+            Value::Scalar(_) => self.lookup(node)?,
+            _ => node.clone(),
+        };
+
+        let mut material = Material::default();
+
+        for (key, value) in as_map(&node)? {
+            match key.as_str() {
+                "color" => material.color = as_color(value)?,
+                "ambient" => material.ambient = as_f64(value)?,
+                "diffuse" => material.diffuse = as_f64(value)?,
+                "specular" => material.specular = as_f64(value)?,
+                "shininess" => material.shininess = as_f64(value)?,
+                "reflective" => material.reflective = as_f64(value)?,
+                "transparency" => material.transparency = as_f64(value)?,
+                "refractive-index" => material.refractive_index = as_f64(value)?,
+                "roughness" => material.roughness = as_f64(value)?,
+                "absorption" => material.absorption = as_color(value)?,
+                "emissive" => material.emissive = as_color(value)?,
+                "microfacet" => material.microfacet = Some(read_microfacet(value)?),
+                "pattern" => material.pattern = Some(self.read_pattern(value)?),
+                other => {
+                    return Err(SceneFileError::at(
+                        value.line,
+                        &format!("unknown material property `{}`", other),
+                    ))
+                }
+            }
+        }
+
+        Ok(material)
+    }
+
+    fn read_pattern(&self, node: &Node) -> SceneResult<Pattern> {
+        let entries = as_map(node)?;
+        let pattern_type = required(entries, "type", node.line)?;
+        let noise_generator = read_noise_generator(entries)?;
+
+        let mut pattern = match as_str(pattern_type)? {
+            "image" => self.read_image_pattern(entries, node.line)?,
+            "blend" => {
+                let patterns = as_list(required(entries, "patterns", node.line)?)?
+                    .iter()
+                    .map(|node| self.read_pattern_or_color(node))
+                    .collect::<SceneResult<Vec<Pattern>>>()?;
+
+                match get(entries, "weights") {
+                    None => Pattern::blended(patterns),
+                    Some(weights) => {
+                        let weights = as_list(weights)?
+                            .iter()
+                            .map(as_f64)
+                            .collect::<SceneResult<Vec<f64>>>()?;
+                        if weights.len() != patterns.len() || weights.iter().sum::<f64>() <= 0.0 {
+                            return Err(SceneFileError::at(
+                                node.line,
+                                "a blend needs one weight per pattern, adding up to more than 0",
+                            ));
+                        }
+                        Pattern::weighted(weights.into_iter().zip(patterns).collect())
+                    }
+                }
+            }
+            "perturb" => {
+                let inner = self.read_pattern_or_color(required(entries, "pattern", node.line)?)?;
+                let scale = get(entries, "scale").map_or(Ok(0.2), as_f64)?;
+                Pattern::perturbed(inner, Jitter::noise(noise_generator.clone(), scale))
+            }
+            kind @ ("marble" | "wood" | "clouds") => {
+                let ramp = self.read_color_ramp(entries, node.line)?;
+                let fractal = read_fractal(entries)?;
+                let turbulence = get(entries, "turbulence").map_or(Ok(1.0), as_f64)?;
+
+                match kind {
+                    "marble" => Pattern::marble(ramp, fractal, turbulence),
+                    "wood" => Pattern::wood(ramp, fractal, turbulence),
+                    _ => Pattern::clouds(ramp, fractal),
+                }
+            }
+            kind => {
+                let colors = as_list(required(entries, "colors", node.line)?)?;
+                if colors.len() != 2 {
+                    return Err(SceneFileError::at(
+                        node.line,
+                        "a pattern needs exactly two colors",
+                    ));
+                }
+                let a = self.read_pattern_or_color(&colors[0])?;
+                let b = self.read_pattern_or_color(&colors[1])?;
+
+                match kind {
+                    "stripes" => Pattern::striped(a, b),
+                    "gradient" => Pattern::gradient(a, b),
+                    "rings" => Pattern::ring(a, b),
+                    "checkers" => Pattern::checkers(a, b),
+                    other => {
+                        return Err(SceneFileError::at(
+                            pattern_type.line,
+                            &format!("unknown pattern `{}`", other),
+                        ))
+                    }
+                }
+            }
+        };
+
+        if let Some(transform) = get(entries, "transform") {
+            pattern = pattern.with_transformation(self.read_transform(transform)?);
+        }
+        if let Some(noise) = get(entries, "noise") {
+            pattern = pattern.with_noise(as_f64(noise)?);
+        }
+
+        Ok(pattern.with_noise_generator(noise_generator))
+    }
+
+    // either `ramp`, a list of [ position, [ r, g, b ] ], or `colors` spread evenly
+    fn read_color_ramp(&self, entries: &[(String, Node)], line: usize) -> SceneResult<ColorRamp> {
+        if let Some(ramp) = get(entries, "ramp") {
+            let stops = as_list(ramp)?
+                .iter()
+                .map(|stop| match as_list(stop)?.as_slice() {
+                    [position, color] => Ok((as_f64(position)?, as_color(color)?)),
+                    _ => Err(SceneFileError::at(
+                        stop.line,
+                        "a ramp stop is a position and a color",
+                    )),
+                })
+                .collect::<SceneResult<Vec<(f64, Color)>>>()?;
+            if stops.is_empty() {
+                return Err(SceneFileError::at(ramp.line, "a ramp needs a color"));
+            }
+            return Ok(ColorRamp::new(stops));
+        }
+
+        let colors = as_list(required(entries, "colors", line)?)?
+            .iter()
+            .map(as_color)
+            .collect::<SceneResult<Vec<Color>>>()?;
+        if colors.is_empty() {
+            return Err(SceneFileError::at(line, "a ramp needs a color"));
+        }
+        Ok(ColorRamp::even(colors))
+    }
+
+    // where a pattern takes a color it also takes a whole pattern, to nest them
+    fn read_pattern_or_color(&self, node: &Node) -> SceneResult<Pattern> {
+        match node.value {
+            Value::Map(_) => self.read_pattern(node),
+            _ => Ok(Pattern::solid(as_color(node)?)),
+        }
+    }
+
+    // the file is a PPM image, relative to the scene file like OBJ files
+    fn read_image_pattern(&self, entries: &[(String, Node)], line: usize) -> SceneResult<Pattern> {
+        let file = required(entries, "file", line)?;
+        let path = self.base_dir.join(as_str(file)?);
+        let bytes = fs::read(&path).map_err(|e| {
+            SceneFileError::at(
+                file.line,
+                &format!("could not read {}: {}", path.display(), e),
+            )
+        })?;
+        let texture = Canvas::from_ppm(&bytes)
+            .map_err(|e| SceneFileError::at(file.line, &format!("{}: {}", path.display(), e)))?;
+
+        let mapping = match get(entries, "mapping") {
+            None => UvMapping::Spherical,
+            Some(node) => match as_str(node)? {
+                "spherical" => UvMapping::Spherical,
+                "planar" => UvMapping::Planar,
+                "cylindrical" => UvMapping::Cylindrical,
+                "cube" => UvMapping::Cube,
+                "mesh" => UvMapping::Mesh,
+                other => {
+                    return Err(SceneFileError::at(
+                        node.line,
+                        &format!("unknown uv mapping `{}`", other),
+                    ))
+                }
+            },
+        };
+        let filter = match get(entries, "filter") {
+            None => TextureFilter::Bilinear,
+            Some(node) => match as_str(node)? {
+                "nearest" => TextureFilter::Nearest,
+                "bilinear" => TextureFilter::Bilinear,
+                other => {
+                    return Err(SceneFileError::at(
+                        node.line,
+                        &format!("unknown texture filter `{}`", other),
+                    ))
+                }
+            },
+        };
+
+        Ok(Pattern::image(texture, mapping, filter))
+    }
+
+    // transformations are applied in the order they are listed
+    fn read_transform(&self, node: &Node) -> SceneResult<Matrix> {
+        self.expand_transform(node, &mut HashSet::new())
+    }
+
+    // `expanding` holds the defines being read, so one that refers to itself is an error
+    fn expand_transform(
+        &self,
+        node: &Node,
+        expanding: &mut HashSet<String>,
+    ) -> SceneResult<Matrix> {
+        let mut transform = Matrix::identity();
+
+        for step in as_list(node)? {
+            if let Value::Scalar(_) = step.value {
+                let name = as_str(step)?;
+                if !expanding.insert(name.to_owned()) {
+                    return Err(SceneFileError::at(
+                        step.line,
+                        &format!("recursive define `{}`", name),
+                    ));
+                }
+                let defined = self.expand_transform(&self.lookup(step)?, expanding)?;
+                expanding.remove(name);
+
+                transform = transform.apply(&defined);
+                continue;
+            }
+
+            let step_items = as_list(step)?;
+            let (name, args) = step_items
+                .split_first()
+                .ok_or_else(|| SceneFileError::at(step.line, "empty transform"))?;
+            let args = args.iter().map(as_f64).collect::<SceneResult<Vec<f64>>>()?;
+            let name = as_str(name)?;
+
+            let expected_args = match name {
+                "translate" | "scale" => 3,
+                "rotate-x" | "rotate-y" | "rotate-z" => 1,
+                "shear" => 6,
+                _ => {
+                    return Err(SceneFileError::at(
+                        step.line,
+                        &format!("unknown transform `{}`", name),
+                    ))
+                }
+            };
+            if args.len() != expected_args {
+                return Err(SceneFileError::at(
+                    step.line,
+                    &format!(
+                        "`{}` takes {} numbers, found {}",
+                        name,
+                        expected_args,
+                        args.len()
+                    ),
+                ));
+            }
+
+            transform = match name {
+                "translate" => transform.translate(args[0], args[1], args[2]),
+                "scale" => transform.scale(args[0], args[1], args[2]),
+                "rotate-x" => transform.rotate_x(args[0]),
+                "rotate-y" => transform.rotate_y(args[0]),
+                "rotate-z" => transform.rotate_z(args[0]),
+                _ => transform.shear(args[0], args[1], args[2], args[3], args[4], args[5]),
+            };
+        }
+
+        Ok(transform)
+    }
+}
+
+// the focal distance defaults to the distance to the `to` point, which is then sharp
+fn read_camera(entries: &[(String, Node)], line: usize) -> SceneResult<Camera> {
+    let from = as_point(required(entries, "from", line)?)?;
+    let to = as_point(required(entries, "to", line)?)?;
+
+    let size = |key: &str| {
+        let node = required(entries, key, line)?;
+        match as_i32(node)? {
+            size if size > 0 => Ok(size),
+            _ => Err(SceneFileError::at(
+                node.line,
+                &format!("the {} must be positive", key),
+            )),
+        }
+    };
+
+    let mut camera = Camera::new(
+        size("width")?,
+        size("height")?,
+        as_f64(required(entries, "field-of-view", line)?)?,
+    )
+    .set_transform(view_transformation(
+        from,
+        to,
+        as_vector(required(entries, "up", line)?)?,
+    ));
+
+    let focal_distance = match get(entries, "focal-distance") {
+        Some(distance) => as_f64(distance)?,
+        None => (to - from).magnitude(),
+    };
+    if focal_distance <= 0.0 {
+        return Err(SceneFileError::at(
+            line,
+            "the focal distance must be positive",
+        ));
+    }
+    camera = camera.with_focal_distance(focal_distance);
+
+    if let Some(aperture) = get(entries, "aperture") {
+        let size = as_f64(aperture)?;
+        if size < 0.0 {
+            return Err(SceneFileError::at(
+                aperture.line,
+                "the aperture must not be negative",
+            ));
+        }
+        camera = camera.with_aperture(size);
+    }
+
+    Ok(camera)
+}
+
+fn is_builtin_shape(kind: &str) -> bool {
+    matches!(
+        kind,
+        "sphere" | "plane" | "cube" | "cylinder" | "cone" | "triangle" | "group" | "csg" | "obj"
+    )
+}
+
+fn set_material(world: &mut World, shape_id: ShapeId, material: &Material) {
+    match world.get_shape(shape_id).shape_type.clone() {
+        ShapeType::Group(children, _) => {
+            for child in children {
+                set_material(world, child, material);
+            }
+        }
+        ShapeType::CSG(_, left, right) => {
+            set_material(world, left, material);
+            set_material(world, right, material);
+        }
+        _ => world.objects[shape_id].shape.material = material.clone(),
+    }
+}
+
+// `seed` picks a noise field of its own, `noise-type` is perlin, simplex or value
+fn read_noise_generator(entries: &[(String, Node)]) -> SceneResult<NoiseGenerator> {
+    let mut noise_generator = match get(entries, "seed") {
+        Some(seed) => NoiseGenerator::new(as_usize(seed)? as u64),
+        None => NoiseGenerator::default(),
+    };
+
+    if let Some(kind) = get(entries, "noise-type") {
+        noise_generator = noise_generator.with_kind(match as_str(kind)? {
+            "perlin" => NoiseKind::Perlin,
+            "simplex" => NoiseKind::Simplex,
+            "value" => NoiseKind::Value,
+            other => {
+                return Err(SceneFileError::at(
+                    kind.line,
+                    &format!("unknown noise type `{}`", other),
+                ))
+            }
+        });
+    }
+
+    Ok(noise_generator)
+}
+
+fn read_fractal(entries: &[(String, Node)]) -> SceneResult<Fractal> {
+    let mut fractal = Fractal::default();
+
+    if let Some(octaves) = get(entries, "octaves") {
+        fractal.octaves = as_usize(octaves)? as u32;
+    }
+    if let Some(lacunarity) = get(entries, "lacunarity") {
+        fractal.lacunarity = as_f64(lacunarity)?;
+    }
+    if let Some(gain) = get(entries, "gain") {
+        fractal.gain = as_f64(gain)?;
+    }
+
+    Ok(fractal)
+}
+
+fn merge(base: &[(String, Node)], overrides: &[(String, Node)]) -> Vec<(String, Node)> {
+    let mut merged = base
+        .iter()
+        .filter(|(key, _)| !overrides.iter().any(|(other, _)| other == key))
+        .cloned()
+        .collect::<Vec<(String, Node)>>();
+    merged.extend(overrides.iter().cloned());
+    merged
+}
+
+fn get<'n>(entries: &'n [(String, Node)], key: &str) -> Option<&'n Node> {
+    entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn read_microfacet(node: &Node) -> SceneResult<Microfacet> {
+    let entries = as_map(node)?;
+    let number = |key, default| get(entries, key).map_or(Ok(default), as_f64);
+
+    Ok(Microfacet::new(
+        number("metallic", 0.0)?,
+        number("roughness", 0.5)?,
+    ))
+}
+
+fn required<'n>(entries: &'n [(String, Node)], key: &str, line: usize) -> SceneResult<&'n Node> {
+    get(entries, key).ok_or_else(|| SceneFileError::at(line, &format!("missing `{}`", key)))
+}
+
+fn as_map(node: &Node) -> SceneResult<&Vec<(String, Node)>> {
+    match &node.value {
+        Value::Map(entries) => Ok(entries),
+        _ => Err(SceneFileError::at(node.line, "expected `key: value` pairs")),
+    }
+}
+
+fn as_list(node: &Node) -> SceneResult<&Vec<Node>> {
+    match &node.value {
+        Value::List(items) => Ok(items),
+        _ => Err(SceneFileError::at(node.line, "expected a list")),
+    }
+}
+
+fn as_str(node: &Node) -> SceneResult<&str> {
+    match &node.value {
+        Value::Scalar(s) => Ok(s),
+        _ => Err(SceneFileError::at(node.line, "expected a single value")),
+    }
+}
+
+fn as_f64(node: &Node) -> SceneResult<f64> {
+    let s = as_str(node)?;
+    s.parse::<f64>()
+        .map_err(|_| SceneFileError::at(node.line, &format!("expected a number, found `{}`", s)))
+}
+
+fn as_usize(node: &Node) -> SceneResult<usize> {
+    let s = as_str(node)?;
+    s.parse::<usize>().map_err(|_| {
+        SceneFileError::at(
+            node.line,
+            &format!("expected a whole number, found `{}`", s),
+        )
+    })
+}
+
+// a whole number of at least one
+fn as_count(node: &Node) -> SceneResult<usize> {
+    match as_usize(node)? {
+        0 => Err(SceneFileError::at(
+            node.line,
+            "expected at least 1, found `0`",
+        )),
+        count => Ok(count),
+    }
+}
+
+fn as_i32(node: &Node) -> SceneResult<i32> {
+    let s = as_str(node)?;
+    s.parse::<i32>().map_err(|_| {
+        SceneFileError::at(
+            node.line,
+            &format!("expected a whole number, found `{}`", s),
+        )
+    })
+}
+
+fn as_bool(node: &Node) -> SceneResult<bool> {
+    match as_str(node)? {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        s => Err(SceneFileError::at(
+            node.line,
+            &format!("expected true or false, found `{}`", s),
+        )),
+    }
+}
+
+fn as_triple(node: &Node) -> SceneResult<(f64, f64, f64)> {
+    let items = as_list(node)?;
+    if items.len() != 3 {
+        return Err(SceneFileError::at(
+            node.line,
+            &format!("expected three numbers, found {}", items.len()),
+        ));
+    }
+    Ok((as_f64(&items[0])?, as_f64(&items[1])?, as_f64(&items[2])?))
+}
+
+fn as_point(node: &Node) -> SceneResult<Tuple> {
+    let (x, y, z) = as_triple(node)?;
+    Ok(point(x, y, z))
+}
+
+fn as_vector(node: &Node) -> SceneResult<Tuple> {
+    let (x, y, z) = as_triple(node)?;
+    Ok(vector(x, y, z))
+}
+
+fn as_color(node: &Node) -> SceneResult<Color> {
+    let (r, g, b) = as_triple(node)?;
+    Ok(color(r, g, b))
+}
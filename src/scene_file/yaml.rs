@@ -0,0 +1,424 @@
+/*
+ * Just enough YAML for scene files: block mappings, block sequences, flow sequences
+ * like `[ 1, 2, 3 ]`, plain or quoted scalars and `#` comments. Anchors, multi-line
+ * strings, flow mappings and the rest of the spec are not supported.
+ * */
+
+use super::SceneFileError;
+
+#[cfg(test)]
+mod yaml_test {
+    use super::{parse_yaml, Node, Value};
+
+    fn scalar(node: &Node) -> &str {
+        match &node.value {
+            Value::Scalar(s) => s,
+            other => panic!("{:?} was not a scalar", other),
+        }
+    }
+
+    fn list(node: &Node) -> &Vec<Node> {
+        match &node.value {
+            Value::List(items) => items,
+            other => panic!("{:?} was not a list", other),
+        }
+    }
+
+    fn map(node: &Node) -> &Vec<(String, Node)> {
+        match &node.value {
+            Value::Map(entries) => entries,
+            other => panic!("{:?} was not a map", other),
+        }
+    }
+
+    #[test]
+    fn parsing_a_mapping() {
+        let doc = parse_yaml("width: 100\nheight: 50\nname: \"my scene\"\n").unwrap();
+        let entries = map(&doc);
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].0, "width");
+        assert_eq!(scalar(&entries[0].1), "100");
+        assert_eq!(scalar(&entries[2].1), "my scene");
+        assert_eq!(entries[1].1.line, 2);
+    }
+
+    #[test]
+    fn parsing_a_sequence_of_mappings() {
+        let doc = parse_yaml(
+            "
+            # a comment
+            - add: sphere   # trailing comment
+              material:
+                color: [ 1, 0.5, 0 ]
+            - add: cube
+            ",
+        )
+        .unwrap();
+        let items = list(&doc);
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].line, 3);
+        assert_eq!(scalar(&map(&items[0])[0].1), "sphere");
+
+        let material = map(&map(&items[0])[1].1);
+        let color = list(&material[0].1);
+        assert_eq!(
+            color.iter().map(scalar).collect::<Vec<_>>(),
+            ["1", "0.5", "0"]
+        );
+        assert_eq!(scalar(&map(&items[1])[0].1), "cube");
+    }
+
+    #[test]
+    fn parsing_nested_flow_sequences() {
+        let doc =
+            parse_yaml("transform: [ [ translate, 1, 2, 3 ], [ rotate-x, 0.5 ], [] ]").unwrap();
+        let transform = list(&map(&doc)[0].1);
+
+        assert_eq!(transform.len(), 3);
+        assert_eq!(scalar(&list(&transform[0])[0]), "translate");
+        assert_eq!(scalar(&list(&transform[1])[1]), "0.5");
+        assert!(list(&transform[2]).is_empty());
+    }
+
+    #[test]
+    fn sequence_can_be_indented_at_same_level_as_its_key() {
+        let doc =
+            parse_yaml("transform:\n- [ scale, 2, 2, 2 ]\n- [ rotate-y, 1 ]\nnext: 1").unwrap();
+        let entries = map(&doc);
+
+        assert_eq!(list(&entries[0].1).len(), 2);
+        assert_eq!(scalar(&entries[1].1), "1");
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        let unclosed = parse_yaml("a: 1\nb: [ 1, 2\n").unwrap_err();
+        assert_eq!(unclosed.line, Some(2));
+
+        let indented = parse_yaml("a: 1\n    b: 2\n").unwrap_err();
+        assert_eq!(indented.line, Some(2));
+
+        let not_a_key = parse_yaml("a: 1\njust some text\n").unwrap_err();
+        assert_eq!(not_a_key.line, Some(2));
+
+        let duplicate = parse_yaml("a: 1\nb: 2\na: 3\n").unwrap_err();
+        assert_eq!(duplicate.line, Some(3));
+
+        let tab = parse_yaml("a:\n\tb: 2\n").unwrap_err();
+        assert_eq!(tab.line, Some(2));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Scalar(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub value: Value,
+    pub line: usize,
+}
+
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+pub fn parse_yaml(content: &str) -> Result<Node, SceneFileError> {
+    let mut lines = vec![];
+
+    for (i, raw) in content.lines().enumerate() {
+        let text = strip_comment(raw).trim_end();
+        let content = text.trim_start_matches(' ');
+        if content.is_empty() {
+            continue;
+        }
+        if content.starts_with('\t') {
+            return Err(SceneFileError::at(
+                i + 1,
+                "tabs can not be used for indentation",
+            ));
+        }
+
+        lines.push(Line {
+            number: i + 1,
+            indent: text.len() - content.len(),
+            text: content,
+        });
+    }
+
+    if lines.is_empty() {
+        return Ok(Node {
+            value: Value::List(vec![]),
+            line: 1,
+        });
+    }
+
+    let mut parser = Parser { lines, pos: 0 };
+    let indent = parser.lines[0].indent;
+    let document = parser.parse_block(indent)?;
+
+    if let Some(line) = parser.lines.get(parser.pos) {
+        return Err(SceneFileError::at(line.number, "unexpected indentation"));
+    }
+
+    Ok(document)
+}
+
+impl<'a> Parser<'a> {
+    fn parse_block(&mut self, indent: usize) -> Result<Node, SceneFileError> {
+        if is_sequence_item(self.lines[self.pos].text) {
+            self.parse_sequence(indent)
+        } else {
+            self.parse_mapping(indent)
+        }
+    }
+
+    fn parse_sequence(&mut self, indent: usize) -> Result<Node, SceneFileError> {
+        let first_line = self.lines[self.pos].number;
+        let mut items = vec![];
+
+        while let Some(&Line {
+            number,
+            indent: line_indent,
+            text,
+        }) = self.lines.get(self.pos)
+        {
+            if line_indent < indent || !is_sequence_item(text) {
+                break;
+            }
+            if line_indent > indent {
+                return Err(SceneFileError::at(number, "unexpected indentation"));
+            }
+
+            let rest = text[1..].trim_start();
+
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(self.parse_nested_or_empty(indent, number)?);
+            } else if is_sequence_item(rest) || split_key(rest).is_some() {
+                // the item starts on the same line as the dash, continue as if it was
+                // on its own line, indented to where it starts
+                let item_indent = line_indent + (text.len() - rest.len());
+                self.lines[self.pos] = Line {
+                    number,
+                    indent: item_indent,
+                    text: rest,
+                };
+                items.push(self.parse_block(item_indent)?);
+            } else {
+                self.pos += 1;
+                items.push(parse_inline(rest, number)?);
+            }
+        }
+
+        Ok(Node {
+            value: Value::List(items),
+            line: first_line,
+        })
+    }
+
+    fn parse_mapping(&mut self, indent: usize) -> Result<Node, SceneFileError> {
+        let first_line = self.lines[self.pos].number;
+        let mut entries: Vec<(String, Node)> = vec![];
+
+        while let Some(&Line {
+            number,
+            indent: line_indent,
+            text,
+        }) = self.lines.get(self.pos)
+        {
+            if line_indent < indent || is_sequence_item(text) {
+                break;
+            }
+            if line_indent > indent {
+                return Err(SceneFileError::at(number, "unexpected indentation"));
+            }
+
+            let (key, rest) = split_key(text).ok_or_else(|| {
+                SceneFileError::at(number, &format!("expected `key: value`, found `{}`", text))
+            })?;
+
+            if entries.iter().any(|(existing, _)| existing == key) {
+                return Err(SceneFileError::at(
+                    number,
+                    &format!("duplicate key `{}`", key),
+                ));
+            }
+
+            self.pos += 1;
+
+            let value = if !rest.is_empty() {
+                parse_inline(rest, number)?
+            } else if self
+                .lines
+                .get(self.pos)
+                .is_some_and(|next| next.indent == indent && is_sequence_item(next.text))
+            {
+                self.parse_sequence(indent)?
+            } else {
+                self.parse_nested_or_empty(indent, number)?
+            };
+
+            entries.push((key.to_owned(), value));
+        }
+
+        Ok(Node {
+            value: Value::Map(entries),
+            line: first_line,
+        })
+    }
+
+    fn parse_nested_or_empty(
+        &mut self,
+        indent: usize,
+        number: usize,
+    ) -> Result<Node, SceneFileError> {
+        match self.lines.get(self.pos) {
+            Some(next) if next.indent > indent => {
+                let nested_indent = next.indent;
+                self.parse_block(nested_indent)
+            }
+            _ => Ok(Node {
+                value: Value::Scalar(String::new()),
+                line: number,
+            }),
+        }
+    }
+}
+
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    if text.starts_with('[') || text.starts_with('"') || text.starts_with('\'') {
+        return None;
+    }
+
+    let colon = text
+        .char_indices()
+        .find(|(i, c)| *c == ':' && text[i + 1..].chars().next().is_none_or(char::is_whitespace))
+        .map(|(i, _)| i)?;
+
+    Some((text[..colon].trim(), text[colon + 1..].trim()))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_quotes = None;
+    let mut previous = ' ';
+
+    for (i, c) in line.char_indices() {
+        match (c, in_quotes) {
+            ('"' | '\'', None) => in_quotes = Some(c),
+            (_, Some(quote)) if c == quote => in_quotes = None,
+            ('#', None) if previous.is_whitespace() => return &line[..i],
+            _ => {}
+        }
+        previous = c;
+    }
+
+    line
+}
+
+fn parse_inline(text: &str, line: usize) -> Result<Node, SceneFileError> {
+    if !text.starts_with('[') {
+        return Ok(Node {
+            value: Value::Scalar(unquote(text).to_owned()),
+            line,
+        });
+    }
+
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut i = 0;
+    let node = parse_flow(&chars, &mut i, line)?;
+
+    if chars[i..].iter().any(|c| !c.is_whitespace()) {
+        return Err(SceneFileError::at(
+            line,
+            &format!(
+                "unexpected `{}` after `]`",
+                chars[i..].iter().collect::<String>().trim()
+            ),
+        ));
+    }
+
+    Ok(node)
+}
+
+fn parse_flow(chars: &[char], i: &mut usize, line: usize) -> Result<Node, SceneFileError> {
+    skip_whitespace(chars, i);
+
+    if chars.get(*i) != Some(&'[') {
+        let start = *i;
+        while *i < chars.len() && chars[*i] != ',' && chars[*i] != ']' {
+            *i += 1;
+        }
+        let text = chars[start..*i].iter().collect::<String>();
+        return Ok(Node {
+            value: Value::Scalar(unquote(text.trim()).to_owned()),
+            line,
+        });
+    }
+
+    *i += 1;
+    let mut items = vec![];
+
+    loop {
+        skip_whitespace(chars, i);
+        match chars.get(*i) {
+            None => return Err(SceneFileError::at(line, "unclosed `[`")),
+            Some(']') => {
+                *i += 1;
+                break;
+            }
+            _ => {}
+        }
+
+        items.push(parse_flow(chars, i, line)?);
+
+        skip_whitespace(chars, i);
+        match chars.get(*i) {
+            Some(',') => *i += 1,
+            Some(']') => {}
+            None => return Err(SceneFileError::at(line, "unclosed `[`")),
+            Some(c) => {
+                return Err(SceneFileError::at(
+                    line,
+                    &format!("expected `,` or `]`, found `{}`", c),
+                ))
+            }
+        }
+    }
+
+    Ok(Node {
+        value: Value::List(items),
+        line,
+    })
+}
+
+fn skip_whitespace(chars: &[char], i: &mut usize) {
+    while chars.get(*i).is_some_and(|c| c.is_whitespace()) {
+        *i += 1;
+    }
+}
+
+fn unquote(text: &str) -> &str {
+    for quote in ['"', '\''] {
+        if text.len() >= 2 && text.starts_with(quote) && text.ends_with(quote) {
+            return &text[1..text.len() - 1];
+        }
+    }
+    text
+}
@@ -1,4 +1,4 @@
-use crate::Shape;
+use crate::{shape::Shape, world::World};
 
 pub mod cubes;
 pub mod cylinder_and_cone;
@@ -11,3 +11,37 @@ pub mod astronaut;
 pub mod csg;
 
 type Scene = Vec<Shape>;
+
+pub const SCENE_NAMES: [&str; 9] = [
+    "csg",
+    "astronaut",
+    "hexagon",
+    "cubes",
+    "cylinder-and-cone",
+    "debug-glass",
+    "first-demo",
+    "maggie",
+    "shiny",
+];
+
+/*
+ * Builds one of the scenes above into `world` by the name used on the command line,
+ * or returns None if there is no scene with that name. Scenes that load files fail
+ * with the reason when the file can not be read.
+ * */
+pub fn scene_by_name(name: &str, world: World) -> Option<Result<World, String>> {
+    let world = match name {
+        "csg" => csg::csg(world),
+        "astronaut" => return Some(astronaut::astronaut(world)),
+        "hexagon" => hexagon::hexagon_scene(world),
+        "cubes" => world.with_objects(cubes::cubes_scene()),
+        "cylinder-and-cone" => world.with_objects(cylinder_and_cone::cylinder_and_cone_scene()),
+        "debug-glass" => world.with_objects(debug_glass::debug_glass()),
+        "first-demo" => world.with_objects(first_demo::maggies_nightmare()),
+        "maggie" => world.with_objects(maggie::maggies_world()),
+        "shiny" => world.with_objects(shiny::shiny_scene()),
+        _ => return None,
+    };
+
+    Some(Ok(world))
+}
@@ -1,12 +1,25 @@
-use std::fs;
+use std::path::Path;
 
-use crate::{obj_file::add_obj_file, world::World};
+use crate::{obj_file::{ObjLoader, ObjProgress}, world::World};
 
 
 
-pub fn astronaut(mut world: World) -> World {
-    let content = fs::read_to_string("src/scenes/files/astronaut.obj").unwrap();
-    let _ = add_obj_file(&mut world, &content);
+pub fn astronaut(mut world: World) -> Result<World, String> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/scenes/files/astronaut.obj");
+    let loaded = ObjLoader::new()
+        .with_progress(|progress| match progress {
+            ObjProgress::Reading { lines, .. } => println!("read {} lines", lines),
+            ObjProgress::Done {
+                vertices,
+                triangles,
+                ..
+            } => println!("loaded {} vertices and {} triangles", vertices, triangles),
+        })
+        .load_path(&mut world, &path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    for warning in loaded.warnings {
+        println!("{}: {}", path.display(), warning);
+    }
 
-    world
+    Ok(world)
 }
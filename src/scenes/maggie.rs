@@ -22,7 +22,7 @@ fn daisy() -> Vec<Shape> {
     stem_material.diffuse = 0.8;
     stem_material.specular = 0.3;
     stem_material.shininess = 300.0;
-    let stem = Shape::sphere_from_material(stem_material).with_transform(
+    let stem = Shape::sphere_from_material(stem_material.clone()).with_transform(
         Matrix::identity()
             .scale(0.05, 0.7, 0.05)
             .translate(0.0, 0.7, 0.0)
@@ -37,7 +37,7 @@ fn daisy() -> Vec<Shape> {
     let mut petals = Vec::new();
 
     for i in 0..8 {
-        let petal = Shape::sphere_from_material(petal_material).with_transform(
+        let petal = Shape::sphere_from_material(petal_material.clone()).with_transform(
             Matrix::identity()
                 .scale(0.2, 0.05, 0.3)
                 .rotate_x(FRAC_PI_2)
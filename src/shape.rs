@@ -1,4 +1,5 @@
 pub mod bounds;
+pub mod bvh;
 pub mod cone;
 pub mod csg;
 pub mod cube;
@@ -42,13 +43,36 @@ pub enum ShapeType {
     Cylinder(f64, f64, bool),    // Cylinder(min_y, max_y, closed)
     Cone(f64, f64, bool),        // Cone(min_y, max_y, closed)
     Group(Vec<ShapeId>, Bounds), // Group(children)
-    Triangle(Tuple, Tuple, Tuple, Tuple, Tuple, Tuple), // Triangle(p1, p2, p3, e1, e2, normal)
-    SmoothTriangle(Tuple, Tuple, Tuple, Tuple, Tuple, Tuple, Tuple, Tuple), // SmoothTriangle (p1, p2, p3, e1, e2, n1, n2, n3)
+    // Triangle(p1, p2, p3, e1, e2, normal, uvs)
+    Triangle(
+        Tuple,
+        Tuple,
+        Tuple,
+        Tuple,
+        Tuple,
+        Tuple,
+        Option<TextureCoordinates>,
+    ),
+    // SmoothTriangle (p1, p2, p3, e1, e2, n1, n2, n3, uvs)
+    SmoothTriangle(
+        Tuple,
+        Tuple,
+        Tuple,
+        Tuple,
+        Tuple,
+        Tuple,
+        Tuple,
+        Tuple,
+        Option<TextureCoordinates>,
+    ),
     CSG(CsgType, ShapeId, ShapeId), //CSG(operation, left, right)
 }
 
 pub type ShapeId = usize;
 
+// the (u, v) of each corner of a triangle, from the `vt` records of an OBJ file
+pub type TextureCoordinates = [(f64, f64); 3];
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct Shape {
     pub inverse_transformation: Matrix,
@@ -109,7 +133,7 @@ impl Shape {
         let e2 = p3 - p1;
         let normal = e2.cross(&e1).normalize();
 
-        Shape::default(ShapeType::Triangle(p1, p2, p3, e1, e2, normal))
+        Shape::default(ShapeType::Triangle(p1, p2, p3, e1, e2, normal, None))
     }
 
     pub fn smooth_triangle(
@@ -122,7 +146,46 @@ impl Shape {
     ) -> Self {
         let e1 = p2 - p1;
         let e2 = p3 - p1;
-        Shape::default(ShapeType::SmoothTriangle(p1, p2, p3, e1, e2, n1, n2, n3))
+        Shape::default(ShapeType::SmoothTriangle(
+            p1, p2, p3, e1, e2, n1, n2, n3, None,
+        ))
+    }
+
+    pub fn with_texture_coordinates(mut self, uvs: TextureCoordinates) -> Self {
+        match &mut self.shape_type {
+            ShapeType::Triangle(_, _, _, _, _, _, texture_coordinates)
+            | ShapeType::SmoothTriangle(_, _, _, _, _, _, _, _, texture_coordinates) => {
+                *texture_coordinates = Some(uvs)
+            }
+            _ => panic!("only triangles have texture coordinates"),
+        }
+        self
+    }
+
+    /*
+     * The texture coordinates at a point on a triangle, blended from the corners by the
+     * barycentric coordinates of the point. Shapes without them give None.
+     * */
+    pub fn texture_coordinates_at(&self, object_point: Tuple) -> Option<(f64, f64)> {
+        let (p1, e1, e2, [t1, t2, t3]) = match &self.shape_type {
+            ShapeType::Triangle(p1, _, _, e1, e2, _, Some(uvs))
+            | ShapeType::SmoothTriangle(p1, _, _, e1, e2, _, _, _, Some(uvs)) => (p1, e1, e2, uvs),
+            _ => return None,
+        };
+
+        let to_point = object_point - *p1;
+        let (d11, d12, d22) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
+        let (d1p, d2p) = (e1.dot(&to_point), e2.dot(&to_point));
+        let denominator = d11 * d22 - d12 * d12;
+
+        let u = (d22 * d1p - d12 * d2p) / denominator;
+        let v = (d11 * d2p - d12 * d1p) / denominator;
+        let w = 1.0 - u - v;
+
+        Some((
+            w * t1.0 + u * t2.0 + v * t3.0,
+            w * t1.1 + u * t2.1 + v * t3.1,
+        ))
     }
 
     pub fn is_group(&self) -> bool {
@@ -168,8 +231,8 @@ impl Shape {
             ShapeType::Cube => cube_normal_at(object_point),
             ShapeType::Cylinder(y_min, y_max, _) => cylinder_normal_at(object_point, y_min, y_max),
             ShapeType::Cone(y_min, y_max, _) => cone_normal_at(object_point, y_min, y_max),
-            ShapeType::Triangle(_, _, _, _, _, normal) => normal,
-            ShapeType::SmoothTriangle(_, _, _, _, _, n1, n2, n3) => {
+            ShapeType::Triangle(_, _, _, _, _, normal, _) => normal,
+            ShapeType::SmoothTriangle(_, _, _, _, _, n1, n2, n3, _) => {
                 smooth_triangle_normal_at(n1, n2, n3, hit)
             }
             ShapeType::Group(_, _) => {
@@ -197,10 +260,10 @@ impl Shape {
             ShapeType::Cone(y_min, y_max, closed) => {
                 cone_intersects(&transformed_ray, *y_min, *y_max, *closed, id)
             }
-            ShapeType::Triangle(p1, _, _, e1, e2, _) => {
+            ShapeType::Triangle(p1, _, _, e1, e2, _, _) => {
                 triangle_intersect(p1, e1, e2, &transformed_ray, id)
             }
-            ShapeType::SmoothTriangle(p1, _, _, e1, e2, _, _, _) => {
+            ShapeType::SmoothTriangle(p1, _, _, e1, e2, _, _, _, _) => {
                 triangle_intersect(p1, e1, e2, &transformed_ray, id)
             }
             ShapeType::CSG(_, left, right) => {
@@ -1,7 +1,4 @@
-use std::{
-    f64::{INFINITY, NEG_INFINITY},
-    ops::Mul,
-};
+use std::ops::Mul;
 
 use crate::{
     matrix::Matrix,
@@ -328,8 +325,8 @@ pub const CUBE_BOUNDS: Bounds = Bounds {
 };
 
 pub const NO_BOUNDS: Bounds = Bounds {
-    min: point(INFINITY, INFINITY, INFINITY),
-    max: point(NEG_INFINITY, NEG_INFINITY, NEG_INFINITY),
+    min: point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+    max: point(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
 };
 
 fn bounds_of_transformed_corners(bounds: &Bounds, transformation: &Matrix) -> Bounds {
@@ -367,22 +364,36 @@ pub fn bounds(world: &World, shape_id: usize) -> Bounds {
                 max: point(limit, *y_max, limit),
             }
         }
-        ShapeType::Triangle(p1, p2, p3, _, _, _) => vec![p1, p2, p3]
+        ShapeType::Triangle(p1, p2, p3, _, _, _, _) => vec![p1, p2, p3]
             .into_iter()
-            .fold(NO_BOUNDS, |b, p| add_point_to_bounds(&b, p.clone())),
-        ShapeType::SmoothTriangle(p1, p2, p3, _, _, _, _, _) => vec![p1, p2, p3]
+            .fold(NO_BOUNDS, |b, p| add_point_to_bounds(&b, *p)),
+        ShapeType::SmoothTriangle(p1, p2, p3, _, _, _, _, _, _) => vec![p1, p2, p3]
             .into_iter()
-            .fold(NO_BOUNDS, |b, p| add_point_to_bounds(&b, p.clone())),
+            .fold(NO_BOUNDS, |b, p| add_point_to_bounds(&b, *p)),
         ShapeType::CSG(_, left, right) => {
             combine_bounds(bounds(world, *left), bounds(world, *right))
         }
         ShapeType::Group(children, _) => children
-            .into_iter()
-            .map(|child: &usize| parent_space_bounds_of(&world, *child))
+            .iter()
+            .map(|child: &usize| parent_space_bounds_of(world, *child))
             .fold(NO_BOUNDS, combine_bounds),
     }
 }
 
+pub fn is_bounded(bounds: &Bounds) -> bool {
+    [bounds.min, bounds.max]
+        .iter()
+        .all(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+}
+
+pub fn centroid(bounds: &Bounds) -> Tuple {
+    point(
+        (bounds.min.x + bounds.max.x) / 2.0,
+        (bounds.min.y + bounds.max.y) / 2.0,
+        (bounds.min.z + bounds.max.z) / 2.0,
+    )
+}
+
 fn bounds_to_corners(bounds: &Bounds) -> Corners {
     let min = bounds.min;
     let max = bounds.max;
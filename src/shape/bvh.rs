@@ -0,0 +1,222 @@
+use crate::{
+    tuple::Tuple,
+    world::{ShapeId, World},
+};
+
+use super::bounds::{centroid, is_bounded, parent_space_bounds_of};
+
+#[cfg(test)]
+mod bvh_test {
+    use crate::{
+        matrix::Matrix,
+        obj_file::add_obj_file,
+        rays::Ray,
+        shape::{bounds::NO_BOUNDS, CsgType, Shape, ShapeType},
+        tuple::{point, point_i, vector_i},
+        world::World,
+    };
+
+    use super::partition_children;
+
+    fn sphere_at(world: &mut World, x: f64, y: f64, z: f64) -> usize {
+        world.add_shape(
+            Shape::sphere_default().with_transform(Matrix::identity().translate(x, y, z)),
+        )
+    }
+
+    fn row_of_spheres(world: &mut World, n: i32) -> usize {
+        let group = world.add_shape(Shape::group());
+        for i in 0..n {
+            let sphere = sphere_at(world, f64::from(i) * 3.0, 0.0, 0.0);
+            world.add_shape_to_group(group, sphere);
+        }
+        group
+    }
+
+    #[test]
+    fn partitioning_children_along_longest_axis() {
+        let mut world = World::default();
+        let s1 = sphere_at(&mut world, 0.0, 3.0, 0.0);
+        let s2 = sphere_at(&mut world, 0.0, -3.0, 0.0);
+        let s3 = sphere_at(&mut world, 0.0, -2.0, 0.0);
+        let s4 = sphere_at(&mut world, 1.0, 2.0, 0.0);
+
+        let (left, right) = partition_children(&world, &[s1, s2, s3, s4]);
+
+        assert_eq!(left, vec![s2, s3]);
+        assert_eq!(right, vec![s4, s1]);
+    }
+
+    #[test]
+    fn unbounded_children_are_not_partitioned() {
+        let mut world = World::default();
+        let plane = world.add_shape(Shape::plane_default());
+        let s1 = sphere_at(&mut world, -3.0, 0.0, 0.0);
+        let s2 = sphere_at(&mut world, 3.0, 0.0, 0.0);
+
+        let (left, right) = partition_children(&world, &[plane, s1, s2]);
+
+        assert_eq!(left, vec![s1]);
+        assert_eq!(right, vec![s2]);
+    }
+
+    #[test]
+    fn making_a_subgroup() {
+        let mut world = World::default();
+        let group = world.add_shape(Shape::group());
+        let s1 = sphere_at(&mut world, 0.0, 0.0, 0.0);
+        let s2 = sphere_at(&mut world, 0.0, 0.0, -4.0);
+        world.add_shape_to_group(group, s1);
+        world.add_shape_to_group(group, s2);
+
+        let subgroup = world.make_subgroup(group, vec![s1, s2]);
+
+        assert_eq!(world.get_children(group), vec![subgroup]);
+        assert_eq!(world.get_children(subgroup), vec![s1, s2]);
+        assert_eq!(world.get_shape(s1).parent, Some(subgroup));
+        assert_eq!(world.get_shape(subgroup).parent, Some(group));
+        assert_eq!(world.get_bounds(subgroup).min, point_i(-1, -1, -5));
+        assert_eq!(world.get_bounds(subgroup).max, point_i(1, 1, 1));
+    }
+
+    #[test]
+    fn dividing_a_group_below_threshold_does_nothing() {
+        let mut world = World::default();
+        let group = row_of_spheres(&mut world, 3);
+        let children = world.get_children(group);
+
+        world.divide(group, 3);
+
+        assert_eq!(world.get_children(group), children);
+    }
+
+    #[test]
+    fn dividing_a_group_builds_a_bounded_hierarchy() {
+        let mut world = World::default();
+        let group = row_of_spheres(&mut world, 8);
+
+        world.divide(group, 2);
+
+        let subgroups = world.get_children(group);
+        assert_eq!(subgroups.len(), 2);
+        for subgroup in subgroups {
+            assert!(world.get_shape(subgroup).is_group());
+            assert_ne!(world.get_bounds(subgroup), NO_BOUNDS);
+
+            let leaves = world.get_children(subgroup);
+            assert_eq!(leaves.len(), 2);
+            for leaf in leaves {
+                assert_eq!(world.get_children(leaf).len(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn dividing_a_group_does_not_change_intersections() {
+        let mut world = World::default();
+        let group = row_of_spheres(&mut world, 10);
+        let ray = Ray::with(point(-5.0, 0.0, 0.0), vector_i(1, 0, 0));
+        let before = world.intersect_world(&ray).xs;
+
+        world.divide(group, 1);
+        let after = world.intersect_world(&ray).xs;
+
+        assert_eq!(after.len(), 20);
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn dividing_a_csg_divides_its_operands() {
+        let mut world = World::default();
+        let left = row_of_spheres(&mut world, 4);
+        let right = sphere_at(&mut world, 0.0, 0.0, 0.0);
+        world.create_csg(CsgType::DIFFERENCE, left, right);
+
+        world.divide(world.current_index(), 2);
+
+        assert_eq!(world.get_children(left).len(), 2);
+    }
+
+    #[test]
+    fn imported_meshes_are_divided() {
+        let mut content = String::new();
+        for i in 0..16 {
+            let x = i * 2;
+            content.push_str(&format!("v {} 0 0\nv {} 1 0\nv {} 0 0\n", x, x, x + 1));
+        }
+        for i in 0..16 {
+            content.push_str(&format!("f {} {} {}\n", i * 3 + 1, i * 3 + 2, i * 3 + 3));
+        }
+
+        let mut world = World::default();
+        let root = add_obj_file(&mut world, &content).unwrap();
+        let mesh = world.get_children(root)[0];
+
+        let children = world.get_children(mesh);
+        assert_eq!(children.len(), 2);
+        assert!(children
+            .iter()
+            .all(|child| world.get_shape(*child).is_group()));
+
+        let ray = Ray::with(point(10.2, 0.2, -2.0), vector_i(0, 0, 1));
+        let xs = world.intersect_world(&ray);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs.get(0).t, 2.0);
+        assert!(matches!(
+            world.get_shape(xs.get(0).object_id).shape_type,
+            ShapeType::Triangle(_, _, _, _, _, _, _)
+        ));
+    }
+}
+
+/*
+ * Median split: the bounded children are sorted by the centre of their bounding box
+ * along the axis where those centres are spread out the most, and cut in half.
+ * Children without finite bounds (planes, infinite cylinders) can't be placed in a
+ * box, so they are left out of both halves and stay in the parent group.
+ * */
+pub fn partition_children(world: &World, children: &[ShapeId]) -> (Vec<ShapeId>, Vec<ShapeId>) {
+    let mut bounded = children
+        .iter()
+        .map(|child| (*child, parent_space_bounds_of(world, *child)))
+        .filter(|(_, bounds)| is_bounded(bounds))
+        .map(|(child, bounds)| (child, centroid(&bounds)))
+        .collect::<Vec<(ShapeId, Tuple)>>();
+
+    if bounded.len() < 2 {
+        return (vec![], vec![]);
+    }
+
+    let axis = longest_axis(bounded.iter().map(|(_, c)| *c));
+    bounded.sort_by(|(_, a), (_, b)| axis(a).partial_cmp(&axis(b)).unwrap());
+
+    let right = bounded.split_off(bounded.len() / 2);
+
+    (
+        bounded.into_iter().map(|(child, _)| child).collect(),
+        right.into_iter().map(|(child, _)| child).collect(),
+    )
+}
+
+fn longest_axis(points: impl Iterator<Item = Tuple>) -> fn(&Tuple) -> f64 {
+    let (min, max) = points.fold(
+        ([f64::INFINITY; 3], [f64::NEG_INFINITY; 3]),
+        |(mut min, mut max), p| {
+            for (i, v) in [p.x, p.y, p.z].into_iter().enumerate() {
+                min[i] = min[i].min(v);
+                max[i] = max[i].max(v);
+            }
+            (min, max)
+        },
+    );
+
+    let (dx, dy, dz) = (max[0] - min[0], max[1] - min[1], max[2] - min[2]);
+
+    if dx >= dy && dx >= dz {
+        |p| p.x
+    } else if dy >= dz {
+        |p| p.y
+    } else {
+        |p| p.z
+    }
+}
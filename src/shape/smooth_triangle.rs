@@ -25,7 +25,7 @@ mod smooth_triangle_test {
     #[test]
     fn constructing_smooth_triangle() {
         let tri = tri();
-        if let ShapeType::SmoothTriangle(p1, p2, p3, e1, e2, n1, n2, n3) = &tri.shape_type {
+        if let ShapeType::SmoothTriangle(p1, p2, p3, e1, e2, n1, n2, n3, _) = &tri.shape_type {
             assert!(is_point(p1));
             assert!(is_point(p2));
             assert!(is_point(p3));
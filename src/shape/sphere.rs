@@ -242,7 +242,7 @@ mod sphere_test {
         let mut m = Material::default();
 
         m.ambient = 1.0;
-        s = s.with_material(m);
+        s = s.with_material(m.clone());
 
         assert_eq!(s.material, m);
     }
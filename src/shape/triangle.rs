@@ -18,7 +18,7 @@ mod triangle_test {
         let triangle = Shape::triangle(point_i(0, 1, 0), point_i(-1, 0, 0), point_i(1, 0, 0));
 
         let (e1, e2, normal) = match triangle.shape_type {
-            ShapeType::Triangle(_, _, _, e1, e2, normal) => (e1, e2, normal),
+            ShapeType::Triangle(_, _, _, e1, e2, normal, _) => (e1, e2, normal),
             _ => panic!("wtf"),
         };
 
@@ -46,7 +46,7 @@ mod triangle_test {
         let n3 = triangle_shape.normal_at(&world, point(0.5, 0.25, 0.0), irrelevant_i);
 
         let triangle_normal = match triangle_shape.shape_type {
-            ShapeType::Triangle(_, _, _, _, _, normal) => normal,
+            ShapeType::Triangle(_, _, _, _, _, normal, _) => normal,
             _ => panic!("wtf"),
         };
 
@@ -62,7 +62,7 @@ mod triangle_test {
         let ray = Ray::with(point_i(0, -1, -2), vector_i(0, 1, 0));
 
         let (p1, e1, e2) = match &triangle.shape_type {
-            ShapeType::Triangle(p1, _, _, e1, e2, _) => (p1, e1, e2),
+            ShapeType::Triangle(p1, _, _, e1, e2, _, _) => (p1, e1, e2),
             _ => panic!("wtf"),
         };
 
@@ -76,7 +76,7 @@ mod triangle_test {
         let ray = Ray::with(point_i(1, 1, -2), vector_i(0, 0, 1));
 
         let (p1, e1, e2) = match &triangle.shape_type {
-            ShapeType::Triangle(p1, _, _, e1, e2, _) => (p1, e1, e2),
+            ShapeType::Triangle(p1, _, _, e1, e2, _, _) => (p1, e1, e2),
             _ => panic!("wtf"),
         };
 
@@ -90,7 +90,7 @@ mod triangle_test {
         let ray = Ray::with(point_i(-1, 1, -2), vector_i(0, 0, 1));
 
         let (p1, e1, e2) = match &triangle.shape_type {
-            ShapeType::Triangle(p1, _, _, e1, e2, _) => (p1, e1, e2),
+            ShapeType::Triangle(p1, _, _, e1, e2, _, _) => (p1, e1, e2),
             _ => panic!("wtf"),
         };
 
@@ -104,7 +104,7 @@ mod triangle_test {
         let ray = Ray::with(point_i(0, -1, -2), vector_i(0, 0, 1));
 
         let (p1, e1, e2) = match &triangle.shape_type {
-            ShapeType::Triangle(p1, _, _, e1, e2, _) => (p1, e1, e2),
+            ShapeType::Triangle(p1, _, _, e1, e2, _, _) => (p1, e1, e2),
             _ => panic!("wtf"),
         };
 
@@ -118,7 +118,7 @@ mod triangle_test {
         let ray = Ray::with(point(0.0, 0.5, -2.0), vector_i(0, 0, 1));
 
         let (p1, e1, e2) = match &triangle.shape_type {
-            ShapeType::Triangle(p1, _, _, e1, e2, _) => (p1, e1, e2),
+            ShapeType::Triangle(p1, _, _, e1, e2, _, _) => (p1, e1, e2),
             _ => panic!("wtf"),
         };
 
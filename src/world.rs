@@ -1,17 +1,29 @@
 use crate::color::{black, color, white, Color};
 use crate::intersection::{Intersection, Intersections, PreparedComputation};
-use crate::lights::PointLight;
+use crate::lights::{Light, PointLight};
 use crate::material::Material;
 use crate::matrix::{is_equal_float, Matrix};
 use crate::rays::Ray;
 use crate::shape::bounds::{bounds, Bounds};
+use crate::shape::bvh::partition_children;
 use crate::shape::{CsgType, Shape, ShapeType};
-use crate::tuple::{point, Tuple};
+use crate::tuple::{point, vector, Tuple};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::Cell;
 use std::f64::consts::FRAC_PI_2;
 use std::vec;
 
 pub type ShapeId = usize;
 
+// rays traced through a blurred reflection or refraction at the first rough surface
+const GLOSSY_SAMPLES: usize = 8;
+
+thread_local! {
+    // how many rough surfaces the ray being traced has gone through
+    static GLOSSY_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct WorldShape {
     pub shape: Shape,
@@ -21,21 +33,32 @@ pub struct WorldShape {
 #[derive(Debug, Clone)]
 pub struct World {
     pub objects: Vec<WorldShape>,
-    pub light_source: PointLight,
+    pub lights: Vec<Light>,
 }
 
-impl World {
-    pub fn default() -> World {
+impl Default for World {
+    fn default() -> World {
         Self::with_light(PointLight::default())
     }
+}
 
-    pub fn with_light(light_source: PointLight) -> World {
+impl World {
+    pub fn with_light(light_source: impl Into<Light>) -> World {
+        Self::with_lights(vec![light_source.into()])
+    }
+
+    pub fn with_lights(lights: Vec<Light>) -> World {
         World {
             objects: vec![],
-            light_source,
+            lights,
         }
     }
 
+    pub fn add_light(&mut self, light: impl Into<Light>) -> usize {
+        self.lights.push(light.into());
+        self.lights.len() - 1
+    }
+
     pub fn with_objects(mut self, objects: Vec<Shape>) -> World {
         for obj in objects {
             self.add_shape(obj);
@@ -60,8 +83,7 @@ impl World {
     }
 
     pub fn calculate_bounds_for_group(&mut self, group_id: ShapeId) -> Bounds {
-        let new_bounds = bounds(&self, group_id);
-        println!("{:?}", new_bounds);
+        let new_bounds = bounds(self, group_id);
 
         match self.objects.get_mut(group_id).unwrap().shape.shape_type {
             ShapeType::Group(_, ref mut bo) => {
@@ -73,6 +95,56 @@ impl World {
         }
     }
 
+    /*
+     * Builds a bounding volume hierarchy below the given shape: every group with more
+     * than `threshold` children is split into two subgroups, recursively, so that a ray
+     * only has to test the children whose bounding box it actually passes through.
+     * */
+    pub fn divide(&mut self, shape_id: ShapeId, threshold: usize) {
+        match self.get_shape(shape_id).shape_type.clone() {
+            ShapeType::Group(children, _) => {
+                if children.len() > threshold {
+                    let (left, right) = partition_children(self, &children);
+                    if !left.is_empty() {
+                        self.make_subgroup(shape_id, left);
+                    }
+                    if !right.is_empty() {
+                        self.make_subgroup(shape_id, right);
+                    }
+                }
+
+                for child in self.get_children(shape_id) {
+                    self.divide(child, threshold);
+                }
+
+                self.calculate_bounds_for_group(shape_id);
+            }
+            ShapeType::CSG(_, left, right) => {
+                self.divide(left, threshold);
+                self.divide(right, threshold);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn make_subgroup(&mut self, group_id: ShapeId, children: Vec<ShapeId>) -> ShapeId {
+        match self.objects.get_mut(group_id).unwrap().shape.shape_type {
+            ShapeType::Group(ref mut group_children, _) => {
+                group_children.retain(|child| !children.contains(child));
+            }
+            _ => panic!("{} is not a group!", group_id),
+        }
+
+        let subgroup = self.add_shape(Shape::group());
+        for child in children {
+            self.add_shape_to_group(subgroup, child);
+        }
+        self.add_shape_to_group(group_id, subgroup);
+        self.calculate_bounds_for_group(subgroup);
+
+        subgroup
+    }
+
     pub fn add_shape(&mut self, mut shape: Shape) -> usize {
         let shape_id = self.next_index();
         shape.id = Some(shape_id);
@@ -85,6 +157,11 @@ impl World {
         shape_id
     }
 
+    pub fn set_transform(&mut self, shape_id: ShapeId, transformation: Matrix) {
+        let shape = &mut self.objects[shape_id].shape;
+        *shape = shape.clone().with_transform(transformation);
+    }
+
     pub fn get_children(&self, group_id: usize) -> Vec<usize> {
         match &self.get_shape(group_id).shape_type {
             ShapeType::Group(children, _) => children.clone(),
@@ -169,22 +246,24 @@ impl World {
     }
 
     pub fn shade_hit(&self, computations: &PreparedComputation, remaining: i32) -> Color {
-        let is_in_shadow = self.is_shadowed(computations.over_point);
         let shape = self.get_shape(computations.object);
-        let surface_color = shape.material.lighting(
-            shape,
-            &self.light_source,
-            computations.over_point,
-            computations.eye_vector,
-            computations.normal_vector,
-            is_in_shadow,
-            self,
-        );
+        let surface_color = self.lights.iter().fold(black(), |sum, light| {
+            sum + shape.material.lighting(
+                shape,
+                light,
+                computations.over_point,
+                computations.eye_vector,
+                computations.normal_vector,
+                self.intensity_at(light, computations.over_point),
+                self,
+            )
+        });
 
         let reflected = self.reflected_color(computations, remaining);
         let refracted = self.refracted_color(computations, remaining);
 
-        let material = shape.material;
+        let material = &shape.material;
+        let surface_color = surface_color + material.emissive;
 
         if material.reflective > 0.0 && material.transparency > 0.0 {
             let reflectance = computations.schlick();
@@ -196,33 +275,69 @@ impl World {
     }
 
     pub fn color_at(&self, ray: &Ray, remaining: i32) -> Color {
+        self.hit_color(ray, remaining)
+            .map_or(black(), |(color, _)| color)
+    }
+
+    // the color where the ray hits and how far from its origin that is
+    fn hit_color(&self, ray: &Ray, remaining: i32) -> Option<(Color, f64)> {
         let intersections = self.intersect_world(ray).xs;
-        let positive_intersection = intersections.iter().find(|i| i.t > 0.0);
-
-        match positive_intersection {
-            Some(intersection) => self.shade_hit(
-                &intersection.prepare_computations(
-                    self,
-                    ray,
-                    &Intersections::from(intersections.clone()),
-                ),
-                remaining,
+        let positive_intersection = intersections.iter().find(|i| i.t > 0.0)?;
+
+        let color = self.shade_hit(
+            &positive_intersection.prepare_computations(
+                self,
+                ray,
+                &Intersections::from(intersections.clone()),
             ),
-            None => black(),
-        }
+            remaining,
+        );
+
+        Some((color, positive_intersection.t * ray.direction.magnitude()))
     }
 
-    pub fn is_shadowed(&self, point: Tuple) -> bool {
-        let v = self.light_source.position - point;
-        let direction = v.normalize();
-        let distance = v.magnitude();
+    /*
+     * The fraction of the light's samples that are visible from the point. A mesh light
+     * doesn't block its own samples, they are on its surface.
+     * */
+    pub fn intensity_at(&self, light: &Light, point: Tuple) -> f64 {
+        let samples = light.samples_at(point);
+        let visible = samples
+            .iter()
+            .filter(|sample| {
+                !self.is_occluded_except(point, sample.direction, sample.distance, light.shape())
+            })
+            .count();
+
+        visible as f64 / samples.len() as f64
+    }
+
+    pub fn is_shadowed(&self, light_position: Tuple, point: Tuple) -> bool {
+        let v = light_position - point;
+
+        self.is_occluded(point, v.normalize(), v.magnitude())
+    }
 
+    // is anything in the way within `distance` when going from `point` in `direction`
+    pub fn is_occluded(&self, point: Tuple, direction: Tuple, distance: f64) -> bool {
+        self.is_occluded_except(point, direction, distance, None)
+    }
+
+    // like `is_occluded`, but `except` and the shapes in it are not in the way
+    pub fn is_occluded_except(
+        &self,
+        point: Tuple,
+        direction: Tuple,
+        distance: f64,
+        except: Option<ShapeId>,
+    ) -> bool {
         let shadow_ray = Ray::with(point, direction);
 
         match self
             .intersect_world(&shadow_ray)
             .xs
             .into_iter()
+            .filter(|i| except.is_none_or(|shape| !self.includes(shape, i.object_id)))
             .find(|i| i.t > 0.0)
         {
             Some(hit) => hit.t < distance,
@@ -230,39 +345,112 @@ impl World {
         }
     }
 
+    /*
+     * What the surface mirrors, as much of it as `reflective` says, or for a microfacet
+     * material as much as Fresnel says at the angle it is seen from.
+     * */
     pub fn reflected_color(&self, comps: &PreparedComputation, remaining: i32) -> Color {
         let shape = self.get_shape(comps.object);
-        if is_equal_float(shape.material.reflective, 0.0) || remaining <= 0 {
+        let material = &shape.material;
+        let reflectance = match &material.microfacet {
+            Some(microfacet) => microfacet.reflectance(
+                material.color_at(shape, comps.over_point, self),
+                comps.eye_vector.dot(&comps.normal_vector),
+            ),
+            None if is_equal_float(material.reflective, 0.0) => black(),
+            None => white() * material.reflective,
+        };
+        if reflectance == black() || remaining <= 0 {
             return black();
         }
 
-        let reflect_ray = Ray::with(comps.over_point, comps.reflection_vector);
+        // a reflection inside an object stays in it, absorbed on the way like a refraction
+        let inside = comps.inside;
+        let reflected = |ray: &Ray| match self.hit_color(ray, remaining - 1) {
+            Some((color, distance)) if inside => color * material.transmittance(distance),
+            Some((color, _)) => color,
+            None => black(),
+        };
 
-        self.color_at(&reflect_ray, remaining - 1) * shape.material.reflective
+        let roughness = material.lobe_roughness();
+        self.lobe_color(
+            comps.over_point,
+            comps.reflection_vector,
+            comps.normal_vector,
+            roughness,
+            reflected,
+        ) * reflectance
     }
 
     pub fn refracted_color(&self, comps: &PreparedComputation, remaining: i32) -> Color {
-        let n_ratio = comps.n1 / comps.n2;
-        let cos_i = comps.eye_vector.dot(&comps.normal_vector);
-        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
         let shape = self.get_shape(comps.object);
 
-        let is_total_internal_reflection = sin2_t > 1.0;
-
-        if shape.is_opaque() || remaining == 0 || is_total_internal_reflection {
+        if shape.is_opaque() || remaining == 0 {
             return black();
         }
 
-        // cos(theta_t) via trig identity
-        let cos_t = f64::sqrt(1.0 - sin2_t);
+        // total internal reflection
+        let Some(direction) = comps.refracted_direction() else {
+            return black();
+        };
+
+        /*
+         * Going into the object, what is seen through it is dimmed by how far the ray goes
+         * before it hits something, usually the way out. Coming out there is nothing more
+         * to absorb.
+         * */
+        let material = &shape.material;
+        let absorbing = !comps.inside;
+        let transmitted = |ray: &Ray| match self.hit_color(ray, remaining - 1) {
+            Some((color, distance)) if absorbing => color * material.transmittance(distance),
+            Some((color, _)) => color,
+            None => black(),
+        };
+
+        self.lobe_color(
+            comps.under_point,
+            direction,
+            comps.normal_vector,
+            material.lobe_roughness(),
+            transmitted,
+        ) * material.transparency
+    }
+
+    /*
+     * The color `trace` sees from `origin` in `direction`, or for a rough surface the
+     * average of the colors seen in directions spread around it. Only the first rough
+     * surface a ray meets takes several samples, the ones behind it take one each, or
+     * frosted glass would multiply them at every bounce. Seeded from the point like area
+     * lights, so renders stay reproducible.
+     * */
+    fn lobe_color(
+        &self,
+        origin: Tuple,
+        direction: Tuple,
+        normal: Tuple,
+        roughness: f64,
+        trace: impl Fn(&Ray) -> Color,
+    ) -> Color {
+        if is_equal_float(roughness, 0.0) {
+            return trace(&Ray::with(origin, direction));
+        }
 
-        // direction of refracted ray
-        let direction =
-            comps.normal_vector * (n_ratio * cos_i - cos_t) - comps.eye_vector * n_ratio;
+        let depth = GLOSSY_DEPTH.get();
+        let samples = if depth == 0 { GLOSSY_SAMPLES } else { 1 };
+        let mut rng = StdRng::seed_from_u64(
+            origin.x.to_bits()
+                ^ origin.y.to_bits().rotate_left(21)
+                ^ origin.z.to_bits().rotate_left(42),
+        );
 
-        let refract_ray = Ray::with(comps.under_point, direction);
+        GLOSSY_DEPTH.set(depth + 1);
+        let sum = (0..samples).fold(black(), |sum, _| {
+            let jittered = glossy_direction(direction, normal, roughness, &mut rng);
+            sum + trace(&Ray::with(origin, jittered))
+        });
+        GLOSSY_DEPTH.set(depth);
 
-        self.color_at(&refract_ray, remaining - 1) * shape.material.transparency
+        sum * (1.0 / samples as f64)
     }
 
     pub fn has_object(&self, object_id: ShapeId) -> bool {
@@ -283,3 +471,35 @@ impl World {
         &self.objects.get(id).unwrap().shape
     }
 }
+
+/*
+ * A direction near `direction`, moved by up to `roughness` at random, on the same side of
+ * the surface so reflections don't go into it and refractions don't come out of it.
+ * */
+pub(crate) fn glossy_direction(
+    direction: Tuple,
+    normal: Tuple,
+    roughness: f64,
+    rng: &mut impl Rng,
+) -> Tuple {
+    let side = direction.dot(&normal).signum();
+
+    for _ in 0..8 {
+        let offset = loop {
+            let offset = vector(
+                rng.gen::<f64>() * 2.0 - 1.0,
+                rng.gen::<f64>() * 2.0 - 1.0,
+                rng.gen::<f64>() * 2.0 - 1.0,
+            );
+            if offset.magnitude() <= 1.0 {
+                break offset;
+            }
+        };
+        let jittered = (direction + offset * roughness).normalize();
+        if jittered.dot(&normal) * side > 0.0 {
+            return jittered;
+        }
+    }
+
+    direction
+}
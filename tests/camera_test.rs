@@ -1,11 +1,13 @@
 #[cfg(test)]
 mod camera_test {
-    use ray_tracer_challenge::camera::Camera;
+    use ray_tracer_challenge::camera::{Camera, Integrator, Sampling};
+    use ray_tracer_challenge::canvas::Canvas;
     use ray_tracer_challenge::color::{color, white};
     use ray_tracer_challenge::lights::PointLight;
     use ray_tracer_challenge::material::Material;
     use ray_tracer_challenge::matrix::{is_equal_float, Matrix};
     use ray_tracer_challenge::pattern::Pattern;
+    use ray_tracer_challenge::rays::Ray;
     use ray_tracer_challenge::shape::Shape;
     use ray_tracer_challenge::transformation::view_transformation;
     use ray_tracer_challenge::tuple::{point, vector};
@@ -37,7 +39,7 @@ mod camera_test {
     fn constructing_ray_through_center_of_canvas() {
         let camera = Camera::new(201, 101, PI / 2.0);
 
-        let ray = camera.ray_for_pixel(100, 50);
+        let ray = camera.ray_for_pixel(100, 50, (0.5, 0.5));
 
         assert_eq!(ray.origin, point(0.0, 0.0, 0.0));
         assert_eq!(ray.direction, vector(0.0, 0.0, -1.0));
@@ -47,7 +49,7 @@ mod camera_test {
     fn constructing_ray_through_corner_of_canvas() {
         let camera = Camera::new(201, 101, PI / 2.0);
 
-        let ray = camera.ray_for_pixel(0, 0);
+        let ray = camera.ray_for_pixel(0, 0, (0.5, 0.5));
 
         assert_eq!(ray.origin, point(0.0, 0.0, 0.0));
         assert_eq!(ray.direction, vector(0.66519, 0.33259, -0.66851));
@@ -62,7 +64,7 @@ mod camera_test {
                 .rotate_y(PI / 4.0),
         );
 
-        let ray = camera.ray_for_pixel(100, 50);
+        let ray = camera.ray_for_pixel(100, 50, (0.5, 0.5));
 
         assert_eq!(ray.origin, point(0.0, 2.0, -5.0));
         assert_eq!(
@@ -84,16 +86,210 @@ mod camera_test {
         assert_eq!(image.pixel_at(5, 5), color(0.38066, 0.47583, 0.2855));
     }
 
+    #[test]
+    fn camera_defaults_to_at_least_one_thread() {
+        let camera = Camera::new(160, 120, PI / 2.0);
+        assert!(camera.threads >= 1);
+        assert_eq!(camera.with_threads(3).threads, 3);
+    }
+
+    #[test]
+    fn camera_defaults_to_one_sample_and_depth_five() {
+        let camera = Camera::new(160, 120, PI / 2.0);
+        assert_eq!(camera.sampling, Sampling::Grid(1));
+        assert_eq!(camera.integrator, Integrator::Whitted);
+        assert_eq!(camera.max_depth, 5);
+    }
+
+    #[test]
+    fn constructing_ray_through_corner_of_pixel() {
+        let camera = Camera::new(201, 101, PI / 2.0);
+
+        let ray = camera.ray_for_pixel(100, 50, (0.0, 0.0));
+
+        assert_eq!(ray.origin, point(0.0, 0.0, 0.0));
+        assert_eq!(
+            ray.direction,
+            vector(1.0 / 201.0, 1.0 / 201.0, -1.0).normalize()
+        );
+    }
+
+    #[test]
+    fn pinhole_camera_by_default() {
+        let camera = Camera::new(201, 101, PI / 2.0);
+
+        assert_eq!(camera.aperture, 0.0);
+        let through_lens = camera.ray_through_lens(10, 20, (0.5, 0.5), (0.9, 0.1));
+        let through_pinhole = camera.ray_for_pixel(10, 20, (0.5, 0.5));
+        assert_eq!(through_lens.origin, through_pinhole.origin);
+        assert_eq!(through_lens.direction, through_pinhole.direction);
+    }
+
+    #[test]
+    fn rays_through_the_lens_meet_on_the_focal_plane() {
+        let camera = Camera::new(201, 101, PI / 2.0)
+            .with_aperture(0.5)
+            .with_focal_distance(4.0);
+
+        let center = camera.ray_for_pixel(30, 20, (0.5, 0.5));
+        let edge = camera.ray_through_lens(30, 20, (0.5, 0.5), (1.0, 0.5));
+        let corner = camera.ray_through_lens(30, 20, (0.5, 0.5), (0.0, 0.0));
+
+        assert_eq!(center.origin, point(0.0, 0.0, 0.0));
+        assert_eq!(edge.origin, point(0.25, 0.0, 0.0));
+        assert!((corner.origin - center.origin).magnitude() <= 0.25 + 1e-9);
+
+        let on_focal_plane = |ray: &Ray| ray.position((-4.0 - ray.origin.z) / ray.direction.z);
+        assert_eq!(on_focal_plane(&edge), on_focal_plane(&center));
+        assert_eq!(on_focal_plane(&corner), on_focal_plane(&center));
+    }
+
+    #[test]
+    fn depth_of_field_blurs_what_is_out_of_focus() {
+        let render = |focal_distance: f64| {
+            sampling_test_camera(Sampling::Grid(4))
+                .with_aperture(0.5)
+                .with_focal_distance(focal_distance)
+                .with_threads(2)
+                .render(World::test_world())
+        };
+        let sharp = sampling_test_camera(Sampling::Grid(4)).render(World::test_world());
+
+        // the edge of the sphere is about 4 away, so it only stays sharp when in focus
+        let in_focus = render(4.0);
+        let out_of_focus = render(1.0);
+
+        let difference = |image: &Canvas| {
+            (0..11)
+                .map(|x| (image.pixel_at(x, 3).r - sharp.pixel_at(x, 3).r).abs())
+                .sum::<f64>()
+        };
+        assert!(difference(&in_focus) < difference(&out_of_focus));
+        assert_eq!(
+            format!("{:?}", render(1.0).pixels),
+            format!("{:?}", out_of_focus.pixels)
+        );
+    }
+
+    fn sampling_test_camera(sampling: Sampling) -> Camera {
+        Camera::new(11, 11, PI / 2.0)
+            .set_transform(view_transformation(
+                point(0.0, 0.0, -5.0),
+                point(0.0, 0.0, 0.0),
+                vector(0.0, 1.0, 0.0),
+            ))
+            .with_sampling(sampling)
+    }
+
+    #[test]
+    fn grid_sampling_averages_rays_across_the_pixel() {
+        let image = sampling_test_camera(Sampling::Grid(2)).render(World::test_world());
+
+        // the four rays hit the sphere a little off center, where it is darker
+        assert_eq!(image.pixel_at(5, 5), color(0.36971, 0.46214, 0.27729));
+        assert_eq!(image.pixel_at(0, 0), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn jittered_sampling_is_repeatable() {
+        let first = sampling_test_camera(Sampling::Jittered(2)).render(World::test_world());
+        let second = sampling_test_camera(Sampling::Jittered(2))
+            .with_threads(3)
+            .render(World::test_world());
+        let grid = sampling_test_camera(Sampling::Grid(2)).render(World::test_world());
+
+        assert_eq!(
+            format!("{:?}", first.pixels),
+            format!("{:?}", second.pixels)
+        );
+        assert_ne!(
+            format!("{:?}", first.pixel_at(5, 5)),
+            format!("{:?}", grid.pixel_at(5, 5))
+        );
+        assert!((first.pixel_at(5, 5).r - grid.pixel_at(5, 5).r).abs() < 0.02);
+    }
+
+    #[test]
+    fn adaptive_sampling_only_refines_edges() {
+        let corners_only = Sampling::Adaptive {
+            threshold: 1.0,
+            max_depth: 3,
+        };
+        let refined = Sampling::Adaptive {
+            threshold: 0.01,
+            max_depth: 3,
+        };
+        let coarse = sampling_test_camera(corners_only).render(World::test_world());
+        let fine = sampling_test_camera(refined).render(World::test_world());
+        let dense = sampling_test_camera(Sampling::Grid(8)).render(World::test_world());
+
+        // empty space is black at every corner, so nothing gets refined
+        assert_eq!(fine.pixel_at(0, 0), color(0.0, 0.0, 0.0));
+        // the sphere is shaded differently at each corner of the center pixel
+        assert_ne!(
+            format!("{:?}", coarse.pixel_at(5, 5)),
+            format!("{:?}", fine.pixel_at(5, 5))
+        );
+        assert!((fine.pixel_at(5, 5).r - dense.pixel_at(5, 5).r).abs() < 0.001);
+    }
+
+    #[test]
+    fn path_tracing_is_repeatable() {
+        let path_traced = |threads| {
+            sampling_test_camera(Sampling::Grid(1))
+                .with_integrator(Integrator::PathTracing { samples: 4 })
+                .with_threads(threads)
+                .render(World::test_world())
+        };
+        let first = path_traced(1);
+        let whitted = sampling_test_camera(Sampling::Grid(1)).render(World::test_world());
+
+        assert_eq!(
+            format!("{:?}", first.pixels),
+            format!("{:?}", path_traced(3).pixels)
+        );
+        assert_eq!(first.pixel_at(0, 0), color(0.0, 0.0, 0.0));
+        // without ambient the sphere is lit by the light and the light bouncing off the
+        // inner sphere, not by a constant
+        assert_ne!(
+            format!("{:?}", first.pixel_at(5, 5)),
+            format!("{:?}", whitted.pixel_at(5, 5))
+        );
+    }
+
+    #[test]
+    fn parallel_rendering_matches_serial_rendering() {
+        let transform = view_transformation(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        );
+        let serial = Camera::new(21, 13, PI / 2.0)
+            .set_transform(transform)
+            .with_threads(1)
+            .render(World::test_world());
+        let parallel = Camera::new(21, 13, PI / 2.0)
+            .set_transform(transform)
+            .with_threads(4)
+            .render(World::test_world());
+
+        // compare the exact bits, Color equality is only within EPSILON
+        assert_eq!(
+            format!("{:?}", serial.pixels),
+            format!("{:?}", parallel.pixels)
+        );
+    }
+
     #[test]
     fn render_a_scene() {
         // floor
         let mut wall_material = Material::from_color(color(1.0, 0.9, 0.9));
         wall_material.specular = 0.0;
-        let floor = Shape::sphere_from_material(wall_material)
+        let floor = Shape::sphere_from_material(wall_material.clone())
             .with_transform(Matrix::identity().scale(10.0, 0.01, 10.0));
 
         // left wall
-        let left_wall = Shape::sphere_from_material(wall_material).with_transform(
+        let left_wall = Shape::sphere_from_material(wall_material.clone()).with_transform(
             Matrix::identity()
                 .scale(10.0, 0.01, 10.0)
                 .rotate_x(FRAC_PI_2)
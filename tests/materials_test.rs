@@ -1,12 +1,13 @@
 #[cfg(test)]
 mod material_test {
     use ray_tracer_challenge::color::{black, color, white};
-    use ray_tracer_challenge::lights::PointLight;
+    use ray_tracer_challenge::lights::{AreaLight, Light, PointLight, SpotLight};
     use ray_tracer_challenge::material::Material;
     use ray_tracer_challenge::pattern::Pattern;
     use ray_tracer_challenge::shape::Shape;
     use ray_tracer_challenge::tuple::{point, vector, Tuple};
     use ray_tracer_challenge::world::World;
+    use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_4, FRAC_PI_8};
 
     #[test]
     fn default_material() {
@@ -32,11 +33,11 @@ mod material_test {
 
         let result = m.lighting(
             &Shape::sphere_default(),
-            &light,
+            &light.into(),
             position,
             eye_v,
             normal_v,
-            false,
+            1.0,
             &World::default(),
         );
 
@@ -52,11 +53,11 @@ mod material_test {
 
         let result = m.lighting(
             &Shape::sphere_default(),
-            &light,
+            &light.into(),
             position,
             eye_v,
             normal_v,
-            false,
+            1.0,
             &World::default(),
         );
 
@@ -72,11 +73,11 @@ mod material_test {
 
         let result = m.lighting(
             &Shape::sphere_default(),
-            &light,
+            &light.into(),
             position,
             eye_v,
             normal_v,
-            false,
+            1.0,
             &World::default(),
         );
 
@@ -92,11 +93,11 @@ mod material_test {
 
         let result = m.lighting(
             &Shape::sphere_default(),
-            &light,
+            &light.into(),
             position,
             eye_v,
             normal_v,
-            false,
+            1.0,
             &World::default(),
         );
 
@@ -112,11 +113,11 @@ mod material_test {
 
         let result = m.lighting(
             &Shape::sphere_default(),
-            &light,
+            &light.into(),
             position,
             eye_v,
             normal_v,
-            false,
+            1.0,
             &World::default(),
         );
 
@@ -129,15 +130,15 @@ mod material_test {
         let eyev = vector(0.0, 0.0, -1.0);
         let normalv = vector(0.0, 0.0, -1.0);
         let light = PointLight::with(point(0.0, 0.0, -10.0), white());
-        let in_shadow = true;
+        let light_intensity = 0.0;
 
         let result = m.lighting(
             &Shape::sphere_default(),
-            &light,
+            &light.into(),
             position,
             eyev,
             normalv,
-            in_shadow,
+            light_intensity,
             &World::default(),
         );
 
@@ -153,7 +154,7 @@ mod material_test {
         material.diffuse = 0.0;
         let eyev = vector(0.0, 0.0, -1.0);
         let normalv = vector(0.0, 0.0, -1.0);
-        let light = PointLight::with(point(0.0, 0.0, -10.0), white());
+        let light: Light = PointLight::with(point(0.0, 0.0, -10.0), white()).into();
 
         let c1 = material.lighting(
             &Shape::sphere_default(),
@@ -161,7 +162,7 @@ mod material_test {
             point(0.9, 0.0, 0.0),
             eyev,
             normalv,
-            false,
+            1.0,
             &World::default(),
         );
         let c2 = material.lighting(
@@ -170,7 +171,7 @@ mod material_test {
             point(1.1, 0.0, 0.0),
             eyev,
             normalv,
-            false,
+            1.0,
             &World::default(),
         );
 
@@ -178,6 +179,157 @@ mod material_test {
         assert_eq!(c2, black());
     }
 
+    #[test]
+    fn lighting_uses_light_intensity_to_attenuate_color() {
+        let world = World::test_world();
+        let light = PointLight::with(point(0.0, 0.0, -10.0), white());
+        let shape = world.get_shape(0);
+        let mut material = shape.material.clone();
+        material.ambient = 0.1;
+        material.diffuse = 0.9;
+        material.specular = 0.0;
+        material.color = white();
+        let p = point(0.0, 0.0, -1.0);
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+
+        for (intensity, expected) in [
+            (1.0, white()),
+            (0.5, color(0.55, 0.55, 0.55)),
+            (0.0, color(0.1, 0.1, 0.1)),
+        ] {
+            let result = material.lighting(
+                shape,
+                &light.clone().into(),
+                p,
+                eyev,
+                normalv,
+                intensity,
+                &world,
+            );
+
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn lighting_samples_the_area_light() {
+        let light: Light = AreaLight::with(
+            point(-0.5, -0.5, -5.0),
+            vector(1.0, 0.0, 0.0),
+            2,
+            vector(0.0, 1.0, 0.0),
+            2,
+            white(),
+        )
+        .into();
+        let shape = Shape::sphere_default();
+        let mut material = Material::from_color(white());
+        material.ambient = 0.1;
+        material.diffuse = 0.9;
+        material.specular = 0.0;
+        let eye = point(0.0, 0.0, -5.0);
+
+        let cases = [
+            (point(0.0, 0.0, -1.0), color(0.9965, 0.9965, 0.9965)),
+            (
+                point(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+                color(0.62318, 0.62318, 0.62318),
+            ),
+        ];
+
+        for (p, expected) in cases {
+            let eyev = (eye - p).normalize();
+            let normalv = vector(p.x, p.y, p.z);
+            let result =
+                material.lighting(&shape, &light, p, eyev, normalv, 1.0, &World::default());
+
+            assert_eq!(result.de_normalized(), expected.de_normalized());
+        }
+    }
+
+    #[test]
+    fn lighting_with_spot_light_only_inside_the_cone() {
+        let (m, position) = setup();
+        let eye_v = vector(0.0, 0.0, -1.0);
+        let normal_v = vector(0.0, 0.0, -1.0);
+        let towards = SpotLight::with(
+            point(0.0, 0.0, -10.0),
+            vector(0.0, 0.0, 1.0),
+            FRAC_PI_8,
+            FRAC_PI_4,
+            white(),
+        );
+        let away = SpotLight::with(
+            point(0.0, 0.0, -10.0),
+            vector(0.0, 1.0, 0.0),
+            FRAC_PI_8,
+            FRAC_PI_4,
+            white(),
+        );
+
+        let lit = m.lighting(
+            &Shape::sphere_default(),
+            &towards.into(),
+            position,
+            eye_v,
+            normal_v,
+            1.0,
+            &World::default(),
+        );
+        let unlit = m.lighting(
+            &Shape::sphere_default(),
+            &away.into(),
+            position,
+            eye_v,
+            normal_v,
+            1.0,
+            &World::default(),
+        );
+
+        assert_eq!(lit, color(1.9, 1.9, 1.9));
+        // not even ambient outside the cone
+        assert_eq!(unlit, black());
+    }
+
+    #[test]
+    fn lighting_a_microfacet_material_ignores_phong() {
+        let position = point(0.0, 0.0, 0.0);
+        let eye_v = vector(0.0, 0.0, -1.0);
+        let normal_v = vector(0.0, 0.0, -1.0);
+        let light = PointLight::with(point(0.0, 0.0, -10.0), white());
+        let matte = Material {
+            specular: 100.0,
+            ..Material::microfacet(white(), 0.0, 1.0)
+        };
+
+        let result = matte.lighting(
+            &Shape::sphere_default(),
+            &light.into(),
+            position,
+            eye_v,
+            normal_v,
+            1.0,
+            &World::default(),
+        );
+
+        // ambient, diffuse of what Fresnel doesn't reflect, and a faint broad highlight
+        assert_eq!(result, color(1.02, 1.02, 1.02));
+    }
+
+    #[test]
+    fn transmittance_falls_off_with_distance() {
+        let clear = Material::default();
+        let tinted = Material {
+            absorption: color(1.0, 0.5, 0.0),
+            ..Material::default()
+        };
+
+        assert_eq!(clear.transmittance(10.0), white());
+        assert_eq!(tinted.transmittance(0.0), white());
+        assert_eq!(tinted.transmittance(2.0), color(0.13534, 0.36788, 1.0));
+    }
+
     #[test]
     fn reflectivity_for_default_material() {
         assert_eq!(Material::default().reflective, 0.0)
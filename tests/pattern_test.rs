@@ -1,8 +1,10 @@
 #[cfg(test)]
 mod pattern_test {
+    use ray_tracer_challenge::canvas::Canvas;
     use ray_tracer_challenge::color::{black, color, white};
     use ray_tracer_challenge::matrix::Matrix;
-    use ray_tracer_challenge::pattern::Pattern;
+    use ray_tracer_challenge::pattern::{ColorRamp, Jitter, Pattern, TextureFilter, UvMapping};
+    use ray_tracer_challenge::perlin_noise::{Fractal, NoiseGenerator, NoiseKind};
     use ray_tracer_challenge::shape::Shape;
     use ray_tracer_challenge::tuple::point;
     use ray_tracer_challenge::world::World;
@@ -112,4 +114,258 @@ mod pattern_test {
         assert_eq!(p.color_at(point(0.0, 0.0, 0.99)), white());
         assert_eq!(p.color_at(point(0.0, 0.0, 1.01)), black());
     }
+
+    #[test]
+    fn image_pattern_wraps_a_texture_around_a_sphere() {
+        // left half red, right half blue
+        let texture = Canvas::new(4, 2, color(1.0, 0.0, 0.0))
+            .write_pixel(2, 0, color(0.0, 0.0, 1.0))
+            .write_pixel(3, 0, color(0.0, 0.0, 1.0))
+            .write_pixel(2, 1, color(0.0, 0.0, 1.0))
+            .write_pixel(3, 1, color(0.0, 0.0, 1.0));
+        let p = Pattern::image(texture, UvMapping::Spherical, TextureFilter::Nearest);
+        let o = Shape::sphere_from_transform(Matrix::identity().scale(2.0, 2.0, 2.0));
+
+        let facing_camera = p.color_at_object(&World::default(), &o, point(0.0, 0.0, -2.0));
+        let facing_away = p.color_at_object(&World::default(), &o, point(0.0, 0.0, 2.0));
+
+        assert_eq!(facing_camera, color(1.0, 0.0, 0.0));
+        assert_eq!(facing_away, color(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn image_pattern_can_be_shared() {
+        let texture = std::sync::Arc::new(Canvas::new(1, 1, white()));
+        let nearest = Pattern::image(texture.clone(), UvMapping::Planar, TextureFilter::Nearest);
+        let bilinear = Pattern::image(texture, UvMapping::Planar, TextureFilter::Bilinear);
+
+        assert_eq!(nearest.color_at(point(0.3, 0.0, 0.7)), white());
+        assert_eq!(bilinear.color_at(point(0.3, 0.0, 0.7)), white());
+    }
+
+    #[test]
+    fn nested_patterns_pick_a_sub_pattern() {
+        let stripes = Pattern::striped(white(), black());
+        let p = Pattern::checkers(stripes, color(1.0, 0.0, 0.0));
+
+        assert_eq!(p.color_at(point(0.5, 0.0, 0.0)), white());
+        assert_eq!(p.color_at(point(0.5, 1.5, 0.0)), color(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn sub_patterns_have_their_own_transformation() {
+        let stripes = Pattern::striped(white(), black())
+            .with_transformation(Matrix::identity().scale(0.25, 1.0, 1.0));
+        let p = Pattern::checkers(stripes, color(1.0, 0.0, 0.0))
+            .with_transformation(Matrix::identity().scale(2.0, 2.0, 2.0));
+        let o = Shape::sphere_default();
+
+        // 0.3 in object space is 0.15 in the checkers and 0.6 in the stripes
+        let c = p.color_at_object(&World::default(), &o, point(0.3, 0.0, 0.0));
+        assert_eq!(c, white());
+
+        // 0.6 in object space is 0.3 in the checkers and 1.2 in the stripes
+        let c = p.color_at_object(&World::default(), &o, point(0.6, 0.0, 0.0));
+        assert_eq!(c, black());
+    }
+
+    #[test]
+    fn gradient_between_patterns() {
+        let p = Pattern::gradient(Pattern::striped(white(), black()), black());
+
+        assert_eq!(p.color_at(point(0.25, 0.0, 0.0)), color(0.75, 0.75, 0.75));
+        assert_eq!(p.color_at(point(1.25, 0.0, 0.0)), black());
+    }
+
+    #[test]
+    fn blended_pattern_averages_its_patterns() {
+        let p = Pattern::blended(vec![
+            Pattern::solid(white()),
+            Pattern::solid(color(1.0, 0.0, 0.0)),
+        ]);
+
+        assert_eq!(p.color_at(point(0.0, 0.0, 0.0)), color(1.0, 0.5, 0.5));
+    }
+
+    #[test]
+    fn blending_crossed_stripes() {
+        let along_x = Pattern::striped(white(), black());
+        let along_z = Pattern::striped(white(), black())
+            .with_transformation(Matrix::identity().rotate_y(std::f64::consts::FRAC_PI_2));
+        let p = Pattern::blended(vec![along_x, along_z]);
+
+        assert_eq!(p.color_at(point(0.5, 0.0, -0.5)), white());
+        assert_eq!(p.color_at(point(1.5, 0.0, -0.5)), color(0.5, 0.5, 0.5));
+        assert_eq!(p.color_at(point(1.5, 0.0, -1.5)), black());
+    }
+
+    #[test]
+    fn weighted_pattern_normalizes_the_weights() {
+        let p = Pattern::weighted(vec![(3.0, white().into()), (1.0, black().into())]);
+
+        assert_eq!(p.color_at(point(0.0, 0.0, 0.0)), color(0.75, 0.75, 0.75));
+    }
+
+    #[test]
+    #[should_panic]
+    fn weighted_pattern_needs_a_positive_weight() {
+        Pattern::weighted(vec![(0.0, white().into())]);
+    }
+
+    #[test]
+    fn perturbed_pattern_moves_the_point_before_looking_it_up() {
+        let shift = Jitter::new(|p| point(p.x + 1.0, p.y, p.z));
+        let p = Pattern::perturbed(Pattern::striped(white(), black()), shift);
+
+        assert_eq!(p.color_at(point(0.5, 0.0, 0.0)), black());
+        assert_eq!(p.color_at(point(1.5, 0.0, 0.0)), white());
+    }
+
+    #[test]
+    fn perlin_perturbation_only_bends_the_stripes() {
+        let p = Pattern::perturbed(Pattern::striped(white(), black()), Jitter::perlin(0.1));
+
+        // far enough from the edges that the jitter can't cross one
+        assert_eq!(p.color_at(point(0.5, 0.3, 0.7)), white());
+        assert_eq!(p.color_at(point(1.5, 0.3, 0.7)), black());
+    }
+
+    #[test]
+    fn marble_without_turbulence_is_straight_veins() {
+        let p = Pattern::marble(
+            ColorRamp::between(black(), white()),
+            Fractal::default(),
+            0.0,
+        );
+
+        assert_eq!(p.color_at(point(0.25, 0.0, 0.0)), white());
+        assert_eq!(p.color_at(point(0.75, 3.0, 1.0)), black());
+        assert_eq!(p.color_at(point(1.0, 0.0, 5.0)), color(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn turbulence_bends_the_marble_veins() {
+        let ramp = ColorRamp::between(black(), white());
+        let straight = Pattern::marble(ramp.clone(), Fractal::default(), 0.0);
+        let bent = Pattern::marble(ramp, Fractal::default(), 1.0);
+
+        let differs = (0..20)
+            .map(|i| point(i as f64 * 0.13, i as f64 * 0.29, i as f64 * 0.07))
+            .any(|p| straight.color_at(p) != bent.color_at(p));
+        assert!(differs);
+    }
+
+    #[test]
+    fn wood_rings_go_around_y() {
+        let p = Pattern::wood(
+            ColorRamp::between(black(), white()),
+            Fractal::default(),
+            0.0,
+        );
+
+        assert_eq!(p.color_at(point(0.25, 0.0, 0.0)), color(0.25, 0.25, 0.25));
+        assert_eq!(p.color_at(point(0.0, 7.0, 1.5)), color(0.5, 0.5, 0.5));
+        assert_eq!(p.color_at(point(0.6, 0.0, 0.8)), black());
+    }
+
+    #[test]
+    fn clouds_stay_within_the_ramp() {
+        let blue = color(0.2, 0.4, 1.0);
+        let p = Pattern::clouds(ColorRamp::between(blue, white()), Fractal::default());
+
+        for i in 0..50 {
+            let c = p.color_at(point(i as f64 * 0.31, i as f64 * 0.17, i as f64 * -0.23));
+            assert!(c.r >= 0.2 - 1e-9 && c.r <= 1.0 + 1e-9);
+            assert!(c.g >= 0.4 - 1e-9 && c.g <= 1.0 + 1e-9);
+            assert_eq!(c.b, 1.0);
+        }
+    }
+
+    #[test]
+    fn clouds_without_octaves_are_the_middle_of_the_ramp() {
+        let fractal = Fractal {
+            octaves: 0,
+            ..Fractal::default()
+        };
+        let p = Pattern::clouds(ColorRamp::between(black(), white()), fractal);
+
+        assert_eq!(p.color_at(point(0.3, 0.1, 0.4)), color(0.5, 0.5, 0.5));
+    }
+
+    fn points() -> Vec<ray_tracer_challenge::tuple::Tuple> {
+        (0..50)
+            .map(|i| point(i as f64 * 0.31, i as f64 * 0.17, i as f64 * -0.23))
+            .collect()
+    }
+
+    #[test]
+    fn patterns_with_different_seeds_have_independent_noise() {
+        let clouds = |seed| {
+            Pattern::clouds(ColorRamp::between(black(), white()), Fractal::default())
+                .with_noise_generator(NoiseGenerator::new(seed))
+        };
+        let (a, b) = (clouds(1), clouds(2));
+
+        assert!(points().into_iter().any(|p| a.color_at(p) != b.color_at(p)));
+    }
+
+    #[test]
+    fn patterns_with_the_same_seed_are_reproducible() {
+        let noisy = || {
+            Pattern::striped(white(), black())
+                .with_noise(0.5)
+                .with_noise_generator(NoiseGenerator::new(9).with_kind(NoiseKind::Value))
+        };
+        let (a, b) = (noisy(), noisy());
+        let o = Shape::sphere_default();
+
+        for p in points() {
+            assert_eq!(
+                a.color_at_object(&World::default(), &o, p),
+                b.color_at_object(&World::default(), &o, p)
+            );
+        }
+    }
+
+    #[test]
+    fn the_noise_generator_moves_the_noise() {
+        let o = Shape::sphere_default();
+        let plain = Pattern::gradient(white(), black()).with_noise(0.5);
+        let seeded = plain
+            .clone()
+            .with_noise_generator(NoiseGenerator::new(5).with_kind(NoiseKind::Simplex));
+
+        assert!(points().into_iter().any(|p| {
+            plain.color_at_object(&World::default(), &o, p)
+                != seeded.color_at_object(&World::default(), &o, p)
+        }));
+    }
+
+    #[test]
+    fn mesh_mapping_uses_the_texture_coordinates_of_triangles() {
+        // top row red, bottom row blue
+        let texture = Canvas::new(2, 2, color(0.0, 0.0, 1.0))
+            .write_pixel(0, 0, color(1.0, 0.0, 0.0))
+            .write_pixel(1, 0, color(1.0, 0.0, 0.0));
+        let p = Pattern::image(texture, UvMapping::Mesh, TextureFilter::Nearest);
+        let triangle = Shape::triangle(
+            point(0.0, 1.0, 0.0),
+            point(-1.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+        );
+
+        // upside down, the top corner is at the bottom of the image
+        let textured =
+            triangle
+                .clone()
+                .with_texture_coordinates([(0.5, 0.0), (0.0, 1.0), (1.0, 1.0)]);
+        let c = p.color_at_object(&World::default(), &textured, point(0.0, 0.9, 0.0));
+        assert_eq!(c, color(0.0, 0.0, 1.0));
+        let c = p.color_at_object(&World::default(), &textured, point(0.0, 0.1, 0.0));
+        assert_eq!(c, color(1.0, 0.0, 0.0));
+
+        // without texture coordinates the x and y of the point are used
+        let c = p.color_at_object(&World::default(), &triangle, point(0.0, 0.9, 0.0));
+        assert_eq!(c, color(1.0, 0.0, 0.0));
+    }
 }
@@ -2,7 +2,7 @@
 mod world_test {
     use ray_tracer_challenge::color::{black, color, white};
     use ray_tracer_challenge::intersection::{Intersection, Intersections};
-    use ray_tracer_challenge::lights::PointLight;
+    use ray_tracer_challenge::lights::{AreaLight, DirectionalLight, MeshLight, PointLight};
     use ray_tracer_challenge::material::Material;
     use ray_tracer_challenge::matrix::Matrix;
     use ray_tracer_challenge::rays::Ray;
@@ -28,7 +28,7 @@ mod world_test {
 
         let default_world = World::test_world();
 
-        assert_eq!(&default_world.light_source, &light);
+        assert_eq!(default_world.lights, vec![light.into()]);
         assert_eq!(default_world.get_shape(0), &s1);
         assert_eq!(default_world.get_shape(1), &s2);
     }
@@ -63,7 +63,7 @@ mod world_test {
     #[test]
     fn shading_an_intersection_from_the_inside() {
         let mut w = World::test_world();
-        w.light_source = PointLight::with(point(0.0, 0.25, 0.0), white());
+        w.lights = vec![PointLight::with(point(0.0, 0.25, 0.0), white()).into()];
         let r = Ray::with(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
         let shapes = w.objects.get(1).unwrap();
         let i = Intersection::new(0.5, shapes.shape.id.unwrap());
@@ -119,7 +119,7 @@ mod world_test {
         material.specular = 0.2;
         material.ambient = 1.0;
 
-        let outer = Shape::sphere_from_material(material);
+        let outer = Shape::sphere_from_material(material.clone());
         let inner = Shape::sphere_from_transform(Matrix::identity().scale(0.5, 0.5, 0.5))
             .with_material(material);
         let inner_color = inner.material.color;
@@ -139,7 +139,7 @@ mod world_test {
         let world = World::test_world();
         let p = point(0.0, 10.0, 0.0);
 
-        assert!(!world.is_shadowed(p));
+        assert!(!world.is_shadowed(point(-10.0, 10.0, -10.0), p));
     }
 
     #[test]
@@ -147,7 +147,7 @@ mod world_test {
         let world = World::test_world();
         let p = point(10.0, -10.0, 10.0);
 
-        assert!(world.is_shadowed(p));
+        assert!(world.is_shadowed(point(-10.0, 10.0, -10.0), p));
     }
 
     #[test]
@@ -155,7 +155,7 @@ mod world_test {
         let world = World::test_world();
         let p = point(-20.0, 20.0, -20.0);
 
-        assert!(!world.is_shadowed(p));
+        assert!(!world.is_shadowed(point(-10.0, 10.0, -10.0), p));
     }
 
     #[test]
@@ -163,7 +163,301 @@ mod world_test {
         let world = World::test_world();
         let p = point(-2.0, 2.0, -2.0);
 
-        assert!(!world.is_shadowed(p));
+        assert!(!world.is_shadowed(point(-10.0, 10.0, -10.0), p));
+    }
+
+    #[test]
+    fn world_without_lights_is_black() {
+        let mut w = World::test_world();
+        w.lights = vec![];
+        let r = Ray::with(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(w.color_at(&r, 5), black());
+    }
+
+    #[test]
+    fn shading_sums_contribution_of_each_light() {
+        let mut w = World::test_world();
+        w.add_light(PointLight::with(point(-10.0, 10.0, -10.0), white()));
+        let r = Ray::with(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        let c = w.color_at(&r, 5);
+
+        assert_eq!(c, color(0.38066, 0.47583, 0.2855) * 2.0);
+    }
+
+    #[test]
+    fn each_light_has_its_own_shadow_test() {
+        let w = World::with_lights(vec![
+            PointLight::with(point(0.0, 0.0, -10.0), white()).into(),
+            PointLight::with(point(0.0, 0.0, 10.0), white()).into(),
+        ])
+        .with_objects(vec![Shape::sphere_default()]);
+
+        let p = point(0.0, 0.0, 5.0);
+
+        assert_eq!(w.intensity_at(&w.lights[0], p), 0.0);
+        assert_eq!(w.intensity_at(&w.lights[1], p), 1.0);
+    }
+
+    #[test]
+    fn point_lights_evaluate_light_intensity_at_given_point() {
+        let world = World::test_world();
+        let light = &world.lights[0];
+
+        let cases = [
+            (point(0.0, 1.0001, 0.0), 1.0),
+            (point(-1.0001, 0.0, 0.0), 1.0),
+            (point(0.0, 0.0, -1.0001), 1.0),
+            (point(0.0, 0.0, 1.0001), 0.0),
+            (point(1.0001, 0.0, 0.0), 0.0),
+            (point(0.0, -1.0001, 0.0), 0.0),
+            (point(0.0, 0.0, 0.0), 0.0),
+        ];
+
+        for (p, expected) in cases {
+            assert_eq!(world.intensity_at(light, p), expected, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn area_light_intensity_is_fraction_of_visible_samples() {
+        let mut world = World::test_world();
+        world.lights = vec![AreaLight::with(
+            point(-0.5, -0.5, -5.0),
+            vector(1.0, 0.0, 0.0),
+            2,
+            vector(0.0, 1.0, 0.0),
+            2,
+            white(),
+        )
+        .into()];
+        let light = &world.lights[0];
+
+        let cases = [
+            (point(0.0, 0.0, 2.0), 0.0),
+            (point(1.0, -1.0, 2.0), 0.25),
+            (point(1.5, 0.0, 2.0), 0.5),
+            (point(1.25, 1.25, 3.0), 0.75),
+            (point(0.0, 0.0, -2.0), 1.0),
+        ];
+
+        for (p, expected) in cases {
+            assert_eq!(world.intensity_at(light, p), expected, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn directional_light_casts_parallel_shadows() {
+        let mut world = World::test_world();
+        world.lights = vec![DirectionalLight::with(vector(0.0, -1.0, 0.0), white()).into()];
+        let light = &world.lights[0];
+
+        assert_eq!(world.intensity_at(light, point(0.0, -5.0, 0.0)), 0.0);
+        assert_eq!(world.intensity_at(light, point(0.9, -100.0, 0.0)), 0.0);
+        assert_eq!(world.intensity_at(light, point(1.1, -5.0, 0.0)), 1.0);
+        assert_eq!(world.intensity_at(light, point(0.0, 5.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn emissive_color_is_added_when_shading() {
+        let world =
+            World::with_lights(vec![]).with_objects(vec![Shape::sphere_from_material(Material {
+                emissive: color(0.5, 0.25, 0.0),
+                ..Material::default()
+            })]);
+        let ray = Ray::with(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(world.color_at(&ray, 5), color(0.5, 0.25, 0.0));
+    }
+
+    #[test]
+    fn mesh_lights_cast_soft_shadows_but_not_on_themselves() {
+        let mut world = World::with_lights(vec![]);
+        let lamp = world.add_shape(
+            Shape::sphere_default()
+                .with_transform(Matrix::identity().translate(0.0, 10.0, 0.0))
+                .with_material(Material {
+                    emissive: white(),
+                    ..Material::default()
+                }),
+        );
+        world.add_shape(
+            Shape::sphere_default().with_transform(
+                Matrix::identity()
+                    .scale(0.25, 0.25, 0.25)
+                    .translate(0.0, 5.0, 0.0),
+            ),
+        );
+        let light: ray_tracer_challenge::lights::Light =
+            MeshLight::from_shape(&world, lamp, 64).unwrap().into();
+
+        let penumbra = world.intensity_at(&light, point(0.0, 0.0, 0.0));
+        let open = world.intensity_at(&light, point(20.0, 0.0, 0.0));
+
+        assert!(penumbra > 0.0 && penumbra < 1.0);
+        assert_eq!(open, 1.0);
+    }
+
+    #[test]
+    fn microfacet_materials_reflect_by_fresnel() {
+        let lamp = Shape::sphere_default()
+            .with_transform(Matrix::identity().translate(0.0, 1.0, 4.0))
+            .with_material(Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                emissive: white(),
+                ..Material::default()
+            });
+        let floor = |metallic| {
+            Shape::plane_default()
+                .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0))
+                .with_material(Material {
+                    ambient: 0.0,
+                    ..Material::microfacet(color(1.0, 0.0, 0.0), metallic, 0.0)
+                })
+        };
+        let ray = Ray::with(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 1.0).normalize());
+
+        let metal = World::with_lights(vec![])
+            .with_objects(vec![floor(1.0), lamp.clone()])
+            .color_at(&ray, 5);
+        let plastic = World::with_lights(vec![])
+            .with_objects(vec![floor(0.0), lamp])
+            .color_at(&ray, 5);
+
+        // (1 - cos 45°)^5 of white is added at this angle
+        assert_eq!(metal, color(1.0, 0.00216, 0.00216));
+        assert_eq!(plastic, color(0.04207, 0.04207, 0.04207));
+    }
+
+    #[test]
+    fn rough_reflections_and_refractions_are_blurred() {
+        let lamp = Shape::sphere_default()
+            .with_transform(Matrix::identity().translate(0.0, 1.0, 4.0))
+            .with_material(Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                emissive: white(),
+                ..Material::default()
+            });
+        let dark = Material {
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            ..Material::default()
+        };
+        let mirror = |roughness| {
+            Shape::plane_default()
+                .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0))
+                .with_material(Material {
+                    reflective: 1.0,
+                    roughness,
+                    ..dark.clone()
+                })
+        };
+        let pane = |roughness| {
+            Shape::cube_default()
+                .with_transform(
+                    Matrix::identity()
+                        .scale(10.0, 10.0, 0.1)
+                        .translate(0.0, 1.0, 2.0),
+                )
+                .with_material(Material {
+                    transparency: 1.0,
+                    roughness,
+                    ..dark.clone()
+                })
+        };
+        let seen = |shape, ray: &Ray| {
+            World::with_lights(vec![])
+                .with_objects(vec![shape, lamp.clone()])
+                .color_at(ray, 5)
+        };
+        let down = Ray::with(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 1.0).normalize());
+        let ahead = Ray::with(point(0.0, 1.0, 0.0), vector(0.0, 0.0, 1.0));
+
+        assert_eq!(seen(mirror(0.0), &down), white());
+        let brushed = seen(mirror(0.6), &down);
+        assert!(brushed.r > 0.1 && brushed.r < 0.9);
+        assert_eq!(brushed, seen(mirror(0.6), &down));
+
+        assert_eq!(seen(pane(0.0), &ahead), white());
+        let frosted = seen(pane(0.6), &ahead);
+        assert!(frosted.r > 0.1 && frosted.r < 0.9);
+    }
+
+    #[test]
+    fn thick_glass_absorbs_more_than_thin_glass() {
+        let lamp = Shape::sphere_default()
+            .with_transform(Matrix::identity().translate(0.0, 0.0, 10.0))
+            .with_material(Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                emissive: white(),
+                ..Material::default()
+            });
+        // straight through the middle of a ball, the ray travels its diameter inside
+        let ball = |diameter: f64| {
+            Shape::sphere_default()
+                .with_transform(
+                    Matrix::identity()
+                        .scale(diameter / 2.0, diameter / 2.0, diameter / 2.0)
+                        .translate(0.0, 0.0, 3.0),
+                )
+                .with_material(Material {
+                    ambient: 0.0,
+                    diffuse: 0.0,
+                    specular: 0.0,
+                    transparency: 1.0,
+                    absorption: color(0.5, 0.25, 0.0),
+                    ..Material::default()
+                })
+        };
+        let seen_through = |diameter| {
+            World::with_lights(vec![])
+                .with_objects(vec![ball(diameter), lamp.clone()])
+                .color_at(&Ray::with(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0)), 5)
+        };
+
+        assert_eq!(seen_through(0.2), color(0.90484, 0.95123, 1.0));
+        assert_eq!(seen_through(2.0), color(0.36788, 0.60653, 1.0));
+    }
+
+    #[test]
+    fn reflections_inside_an_object_are_absorbed() {
+        // a mirrored ball seen from inside, with a lamp behind the eye
+        let ball = Shape::sphere_default()
+            .with_transform(Matrix::identity().scale(5.0, 5.0, 5.0))
+            .with_material(Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                reflective: 1.0,
+                absorption: color(0.5, 0.0, 0.0),
+                ..Material::default()
+            });
+        let lamp = Shape::sphere_default()
+            .with_transform(
+                Matrix::identity()
+                    .scale(0.5, 0.5, 0.5)
+                    .translate(0.0, 0.0, -2.0),
+            )
+            .with_material(Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                emissive: white(),
+                ..Material::default()
+            });
+        let world = World::with_lights(vec![]).with_objects(vec![ball, lamp]);
+        let ray = Ray::with(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+
+        // from the mirror at z = 5 back to the lamp at z = -1.5
+        assert_eq!(world.color_at(&ray, 5), color(0.03877, 1.0, 1.0));
     }
 
     #[test]
@@ -187,8 +481,10 @@ mod world_test {
         let mut world = World::test_world();
         let mut material = Material::default();
         material.reflective = 0.5;
-        let plane = world.add_shape(Shape::plane_from_material(material)
-            .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0)));
+        let plane = world.add_shape(
+            Shape::plane_from_material(material)
+                .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0)),
+        );
         let ray = Ray::with(
             point(0.0, 0.0, -3.0),
             vector(0.0, -SQRT_2 / 2.0, SQRT_2 / 2.0),
@@ -209,7 +505,7 @@ mod world_test {
         let mut material = Material::default();
         material.reflective = 1.0;
 
-        let lower = Shape::plane_from_material(material)
+        let lower = Shape::plane_from_material(material.clone())
             .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0));
 
         let upper = Shape::plane_from_material(material)
@@ -229,8 +525,10 @@ mod world_test {
         let mut material = Material::default();
         material.reflective = 0.5;
 
-        world.add_shape(Shape::plane_from_material(material)
-            .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0)));
+        world.add_shape(
+            Shape::plane_from_material(material)
+                .with_transform(Matrix::identity().translate(0.0, -1.0, 0.0)),
+        );
 
         let ray = Ray::with(
             point(0.0, 0.0, -3.0),